@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use crate::engine::graphics::{Graphics, model::texture::ModelTexture};
+
+/// Overrides the root directory of every `AssetServer` when set
+pub const ASSET_ROOT_ENV: &str = "GAME_ASSET_ROOT";
+
+#[derive(Debug)]
+pub enum AssetError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Image {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+}
+
+impl std::fmt::Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "failed to read {}: {error}", path.display()),
+            Self::Image { path, error } => {
+                write!(f, "failed to decode {}: {error}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+struct Cached<T> {
+    asset: T,
+    /// Modification time when loaded, to detect changes
+    modified: Option<SystemTime>,
+}
+
+/// Loads assets from a directory at runtime, caching them by path.
+///
+/// The root is `./assets` unless `ASSET_ROOT_ENV` is set. Loading the same path again returns the
+/// cached asset, `reload_changed` picks up files modified on disk since they were loaded.
+pub struct AssetServer {
+    root: PathBuf,
+    textures: HashMap<PathBuf, Cached<Arc<ModelTexture>>>,
+    shaders: HashMap<PathBuf, Cached<Arc<str>>>,
+}
+
+impl Default for AssetServer {
+    fn default() -> Self {
+        Self::new("assets")
+    }
+}
+
+impl AssetServer {
+    /// `root` is ignored when `ASSET_ROOT_ENV` is set
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = std::env::var_os(ASSET_ROOT_ENV).map_or_else(|| root.into(), PathBuf::from);
+        Self {
+            root,
+            textures: HashMap::new(),
+            shaders: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of an asset relative to the root
+    pub fn resolve(&self, rel_path: impl AsRef<Path>) -> PathBuf {
+        self.root.join(rel_path)
+    }
+
+    pub fn load_texture(
+        &mut self,
+        ctx: &Graphics,
+        rel_path: impl AsRef<Path>,
+    ) -> Result<Arc<ModelTexture>, AssetError> {
+        let rel_path = rel_path.as_ref();
+        if let Some(cached) = self.textures.get(rel_path) {
+            return Ok(cached.asset.clone());
+        }
+        let cached = self.read_texture(ctx, rel_path)?;
+        let texture = cached.asset.clone();
+        self.textures.insert(rel_path.to_path_buf(), cached);
+        Ok(texture)
+    }
+
+    /// WGSL source, to concatenate with the engine declarations before creating the module
+    pub fn load_shader(&mut self, rel_path: impl AsRef<Path>) -> Result<Arc<str>, AssetError> {
+        let rel_path = rel_path.as_ref();
+        if let Some(cached) = self.shaders.get(rel_path) {
+            return Ok(cached.asset.clone());
+        }
+        let cached = self.read_shader(rel_path)?;
+        let source = cached.asset.clone();
+        self.shaders.insert(rel_path.to_path_buf(), cached);
+        Ok(source)
+    }
+
+    /// Reload the cached assets whose file changed since they were loaded and return their
+    /// relative paths. Holders of the previous versions keep them, load the paths again to swap.
+    /// Assets failing to reload keep their previous version and are reported on stdout.
+    pub fn reload_changed(&mut self, ctx: &Graphics) -> Vec<PathBuf> {
+        let mut changed = vec![];
+
+        let stale: Vec<_> = self
+            .textures
+            .iter()
+            .filter(|(path, cached)| self.is_stale(path, cached.modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in stale {
+            match self.read_texture(ctx, &path) {
+                Ok(cached) => {
+                    self.textures.insert(path.clone(), cached);
+                    changed.push(path);
+                }
+                Err(e) => crate::log!("Could not reload texture: {e}"),
+            }
+        }
+
+        let stale: Vec<_> = self
+            .shaders
+            .iter()
+            .filter(|(path, cached)| self.is_stale(path, cached.modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in stale {
+            match self.read_shader(&path) {
+                Ok(cached) => {
+                    self.shaders.insert(path.clone(), cached);
+                    changed.push(path);
+                }
+                Err(e) => crate::log!("Could not reload shader: {e}"),
+            }
+        }
+
+        changed
+    }
+
+    /// Forget every cached asset, the next loads read the files again
+    pub fn clear(&mut self) {
+        self.textures.clear();
+        self.shaders.clear();
+    }
+
+    fn is_stale(&self, rel_path: &Path, loaded: Option<SystemTime>) -> bool {
+        let modified = self.modified(&self.resolve(rel_path));
+        modified.is_some() && modified != loaded
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    fn read(&self, rel_path: &Path) -> Result<(PathBuf, Vec<u8>, Option<SystemTime>), AssetError> {
+        let path = self.resolve(rel_path);
+        let modified = self.modified(&path);
+        match std::fs::read(&path) {
+            Ok(bytes) => Ok((path, bytes, modified)),
+            Err(error) => Err(AssetError::Io { path, error }),
+        }
+    }
+
+    fn read_texture(
+        &self,
+        ctx: &Graphics,
+        rel_path: &Path,
+    ) -> Result<Cached<Arc<ModelTexture>>, AssetError> {
+        let (path, bytes, modified) = self.read(rel_path)?;
+        let label = rel_path.to_string_lossy();
+        match ModelTexture::from_bytes(ctx, &bytes, &label) {
+            Ok(texture) => Ok(Cached {
+                asset: Arc::new(texture),
+                modified,
+            }),
+            Err(error) => Err(AssetError::Image { path, error }),
+        }
+    }
+
+    fn read_shader(&self, rel_path: &Path) -> Result<Cached<Arc<str>>, AssetError> {
+        let (path, bytes, modified) = self.read(rel_path)?;
+        match String::from_utf8(bytes) {
+            Ok(source) => Ok(Cached {
+                asset: source.into(),
+                modified,
+            }),
+            Err(e) => Err(AssetError::Io {
+                path,
+                error: std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            }),
+        }
+    }
+}
@@ -1,55 +1,175 @@
-use std::time::Duration;
-
-use winit::keyboard::KeyCode;
+use std::{
+    f32::consts::{FRAC_PI_2, TAU},
+    time::Duration,
+};
 
 use super::{
-    graphics::camera::Camera,
+    graphics::camera::{Camera, MAX_PITCH},
+    input_map::{
+        MOVE_BACKWARD, MOVE_DOWN, MOVE_FORWARD, MOVE_LEFT, MOVE_RIGHT, MOVE_UP, ROLL_LEFT,
+        ROLL_RIGHT,
+    },
     inputs::Inputs,
-    maths::{Vec2f, Vec3f},
+    maths::{Aabb, Vec2f, Vec2u, Vec3f},
 };
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerMode {
+    /// Free flying first person camera
+    #[default]
+    Fly,
+    /// Mouse motion rotates the camera around `Controller::focus`
+    Orbit,
+}
+
+/// How the fly camera velocity follows the input, shaping how it speeds up and comes to a stop
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Damping {
+    /// No inertia, the camera stops as soon as the input is released
+    #[default]
+    None,
+    /// Closes a fraction of the gap each second, `rate` higher is snappier. Long soft tail.
+    Exponential { rate: f32 },
+    /// Constant acceleration in units per second squared, stops abruptly
+    Linear { acceleration: f32 },
+    /// Critically damped spring, settles without overshooting in about five times `smooth_time`
+    CriticallyDamped { smooth_time: f32 },
+}
+
+impl Damping {
+    /// Move `velocity` towards `target` over `dt` seconds. `rate` is the spring state of
+    /// `CriticallyDamped`, zero it when starting from rest.
+    pub fn apply(&self, velocity: &mut Vec3f, rate: &mut Vec3f, target: Vec3f, dt: f32) {
+        match *self {
+            Self::None => *velocity = target,
+            Self::Exponential { rate: k } => {
+                *velocity += (target - *velocity) * (1.0 - (-k * dt).exp());
+            }
+            Self::Linear { acceleration } => {
+                let delta = target - *velocity;
+                let step = acceleration * dt;
+                *velocity = if delta.norm() <= step {
+                    target
+                } else {
+                    *velocity + delta.normalize() * step
+                };
+            }
+            Self::CriticallyDamped { smooth_time } => {
+                // Closed form approximation of the spring, stable for large steps
+                let omega = 2.0 / smooth_time.max(1e-4);
+                let x = omega * dt;
+                let decay = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+                let change = *velocity - target;
+                let temp = (*rate + change * omega) * dt;
+                *rate = (*rate - temp * omega) * decay;
+                *velocity = target + (change + temp) * decay;
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Controller {
+    pub mode: ControllerMode,
+    /// Point the camera rotates around in orbit mode
+    pub focus: Vec3f,
+    /// Distance to the focus in orbit mode, changed by scrolling
+    pub orbit_radius: f32,
+    pub min_orbit_radius: f32,
+    pub max_orbit_radius: f32,
+
     pub forward: bool,
     pub backward: bool,
     pub left: bool,
     pub right: bool,
     pub up: bool,
     pub down: bool,
+    pub roll_left: bool,
+    pub roll_right: bool,
 
     pub speed: f32,
     pub sensitivity: f32,
+    /// Roll speed in radians per second
+    pub roll_speed: f32,
+
+    /// Smoothly return the roll to zero when no roll input is given
+    pub auto_level: bool,
+    /// Rate at which the roll converges to zero when auto leveling, per second
+    pub auto_level_speed: f32,
 
     pub mouse_delta: Vec2f,
+    /// Analog movement, x strafes right and y moves forward
+    pub move_axis: Vec2f,
+    /// Analog look, x turns right and y looks up, scaled by `look_speed`
+    pub look_axis: Vec2f,
+    /// Turn rate at full look deflection, in radians per second
+    pub look_speed: f32,
+
+    /// Region the camera position is clamped to after moving
+    pub bounds: Option<Aabb>,
+    /// Which of the x, y and z axes are clamped by `bounds`
+    pub bounds_axes: [bool; 3],
+
+    /// Inertia of the fly movement
+    pub damping: Damping,
+    velocity: Vec3f,
+    /// Spring state of `Damping::CriticallyDamped`
+    velocity_rate: Vec3f,
 }
 
 impl Default for Controller {
     fn default() -> Self {
         Controller {
+            mode: ControllerMode::Fly,
+            focus: Vec3f::zeros(),
+            orbit_radius: 5.0,
+            min_orbit_radius: 0.5,
+            max_orbit_radius: 100.0,
             forward: false,
             backward: false,
             left: false,
             right: false,
             up: false,
             down: false,
+            roll_left: false,
+            roll_right: false,
             speed: 2.0,
             sensitivity: 0.1,
+            roll_speed: 1.5,
+            auto_level: false,
+            auto_level_speed: 3.0,
             mouse_delta: Vec2f::new(0.0, 0.0),
+            move_axis: Vec2f::new(0.0, 0.0),
+            look_axis: Vec2f::new(0.0, 0.0),
+            look_speed: 2.5,
+            bounds: None,
+            bounds_axes: [true; 3],
+            damping: Damping::None,
+            velocity: Vec3f::zeros(),
+            velocity_rate: Vec3f::zeros(),
         }
     }
 }
 
 impl Controller {
     pub fn handle_inputs(&mut self, inputs: &Inputs, debug_speed: bool) {
-        self.forward = inputs.key_held(KeyCode::KeyW);
-        self.backward = inputs.key_held(KeyCode::KeyS);
-        self.left = inputs.key_held(KeyCode::KeyA);
-        self.right = inputs.key_held(KeyCode::KeyD);
+        self.forward = inputs.action_held(MOVE_FORWARD);
+        self.backward = inputs.action_held(MOVE_BACKWARD);
+        self.left = inputs.action_held(MOVE_LEFT);
+        self.right = inputs.action_held(MOVE_RIGHT);
 
-        self.up = inputs.key_held(KeyCode::Space);
-        self.down = inputs.key_held(KeyCode::ShiftLeft);
+        self.up = inputs.action_held(MOVE_UP);
+        self.down = inputs.action_held(MOVE_DOWN);
 
-        if debug_speed {
+        self.roll_left = inputs.action_held(ROLL_LEFT);
+        self.roll_right = inputs.action_held(ROLL_RIGHT);
+
+        if self.mode == ControllerMode::Orbit {
+            // zoom controlled by scrollwheel
+            let (_, scroll) = inputs.scroll_diff();
+            self.orbit_radius = (self.orbit_radius * 0.9f32.powf(scroll))
+                .clamp(self.min_orbit_radius, self.max_orbit_radius);
+        } else if debug_speed {
             // speed controlled by scrollwheel
             let (_, scroll) = inputs.scroll_diff();
             self.speed += scroll * 0.3;
@@ -58,6 +178,63 @@ impl Controller {
 
         let (mdx, mdy) = inputs.mouse_diff();
         self.mouse_delta = [mdx, mdy].into();
+        let (lx, ly) = inputs.gamepad_left_stick();
+        self.move_axis = Vec2f::new(lx, ly);
+        let (rx, ry) = inputs.gamepad_right_stick();
+        self.look_axis = Vec2f::new(rx, ry);
+    }
+
+    /// Clear transient input state and inertia, call when the input capture toggles to avoid a jerk
+    pub fn reset(&mut self) {
+        self.forward = false;
+        self.backward = false;
+        self.left = false;
+        self.right = false;
+        self.up = false;
+        self.down = false;
+        self.roll_left = false;
+        self.roll_right = false;
+        self.mouse_delta = Vec2f::zeros();
+        self.move_axis = Vec2f::zeros();
+        self.look_axis = Vec2f::zeros();
+        self.velocity = Vec3f::zeros();
+        self.velocity_rate = Vec3f::zeros();
+    }
+
+    /// Switch to orbit mode around the point under the cursor: the closest box hit by the
+    /// cursor ray, or the ground plane (y = 0) when nothing is hit. Returns the new focus.
+    pub fn pick_focus(
+        &mut self,
+        camera: &Camera,
+        cursor: Vec2f,
+        viewport: Vec2u,
+        boxes: &[Aabb],
+    ) -> Option<Vec3f> {
+        let (origin, direction) = camera.screen_ray(cursor, viewport);
+
+        let box_hit = boxes
+            .iter()
+            .filter_map(|b| b.intersects_ray(&origin, &direction))
+            .min_by(f32::total_cmp);
+        let ground_hit = || {
+            (direction.y.abs() > f32::EPSILON)
+                .then(|| -origin.y / direction.y)
+                .filter(|t| *t > 0.0)
+        };
+
+        let t = box_hit.or_else(ground_hit)?;
+        self.focus = origin + direction * t;
+        self.orbit_radius = (camera.position - self.focus)
+            .norm()
+            .clamp(self.min_orbit_radius, self.max_orbit_radius);
+        self.mode = ControllerMode::Orbit;
+        Some(self.focus)
+    }
+
+    /// Feed virtual joystick axes, call after `handle_inputs`.
+    pub fn handle_joysticks(&mut self, movement: Vec2f, look: Vec2f) {
+        self.move_axis += movement;
+        self.mouse_delta += look;
     }
 
     pub fn update_camera(&mut self, camera: &mut Camera, dt: &Duration) {
@@ -66,37 +243,621 @@ impl Controller {
         // Mouse movement for yaw and pitch
         camera.yaw += self.mouse_delta.x * self.sensitivity * dt;
         camera.pitch -= self.mouse_delta.y * self.sensitivity * dt;
+        camera.yaw += self.look_axis.x * self.look_speed * dt;
+        camera.pitch += self.look_axis.y * self.look_speed * dt;
 
         // Clamp pitch to prevent the camera from flipping over
-        camera.pitch = camera.pitch.clamp(
-            -std::f32::consts::FRAC_PI_2 + 0.01,
-            std::f32::consts::FRAC_PI_2 - 0.01,
-        );
+        camera.pitch = camera.pitch.clamp(-MAX_PITCH, MAX_PITCH);
 
         camera.update_direction_from_angles();
 
+        if self.mode == ControllerMode::Orbit {
+            camera.position = self.focus - camera.direction * self.orbit_radius;
+            self.velocity = Vec3f::zeros();
+            self.velocity_rate = Vec3f::zeros();
+            self.clamp_to_bounds(camera);
+            return;
+        }
+
+        // Roll, leveling back when released
+        if self.roll_left != self.roll_right {
+            let sign = if self.roll_left { -1.0 } else { 1.0 };
+            camera.roll += sign * self.roll_speed * dt;
+        } else if self.auto_level {
+            camera.roll *= (-self.auto_level_speed * dt).exp();
+        }
+
         // Keyboard movement
         let right = camera.up.cross(&camera.direction);
         //let up_movement = camera.up;
         let up_movement = Vec3f::y();
 
-        if self.forward {
-            camera.position += camera.direction * self.speed * dt;
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+        let target = (camera.direction * (axis(self.forward, self.backward) + self.move_axis.y)
+            + right * (axis(self.left, self.right) - self.move_axis.x)
+            + up_movement * axis(self.up, self.down))
+            * self.speed;
+        self.damping
+            .apply(&mut self.velocity, &mut self.velocity_rate, target, dt);
+        camera.position += self.velocity * dt;
+        self.clamp_to_bounds(camera);
+    }
+
+    /// Keep the camera inside `bounds` on the axes enabled in `bounds_axes`
+    fn clamp_to_bounds(&self, camera: &mut Camera) {
+        let Some(bounds) = self.bounds else {
+            return;
+        };
+        let clamped = bounds.clamp_point(&camera.position);
+        for (axis, enabled) in self.bounds_axes.into_iter().enumerate() {
+            if enabled {
+                camera.position[axis] = clamped[axis];
+            }
+        }
+    }
+}
+
+/// Automatic camera circling a focus point at a constant rate, for showcases
+#[derive(Debug, Clone)]
+pub struct TurntableController {
+    pub focus: Vec3f,
+    pub radius: f32,
+    /// Camera height above the focus
+    pub height: f32,
+    /// Radians per second, negative values turn clockwise seen from above
+    pub angular_speed: f32,
+    pub min_radius: f32,
+    pub max_radius: f32,
+
+    angle: f32,
+    paused: bool,
+}
+
+impl Default for TurntableController {
+    fn default() -> Self {
+        Self {
+            focus: Vec3f::zeros(),
+            radius: 5.0,
+            height: 1.5,
+            angular_speed: 0.4,
+            min_radius: 0.5,
+            max_radius: 100.0,
+            angle: 0.0,
+            paused: false,
+        }
+    }
+}
+
+impl TurntableController {
+    pub fn new(focus: Vec3f, radius: f32, height: f32, angular_speed: f32) -> Self {
+        Self {
+            focus,
+            radius,
+            height,
+            angular_speed,
+            ..Default::default()
         }
-        if self.backward {
-            camera.position -= camera.direction * self.speed * dt;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Scrolling zooms the radius
+    pub fn handle_inputs(&mut self, inputs: &Inputs) {
+        let (_, scroll) = inputs.scroll_diff();
+        self.radius = (self.radius * 0.9f32.powf(scroll)).clamp(self.min_radius, self.max_radius);
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: &Duration) {
+        if !self.paused {
+            self.angle = (self.angle + self.angular_speed * dt.as_secs_f32()) % TAU;
         }
-        if self.left {
-            camera.position += right * self.speed * dt;
+
+        camera.position = self.focus
+            + Vec3f::new(
+                self.angle.cos() * self.radius,
+                self.height,
+                self.angle.sin() * self.radius,
+            );
+        camera.roll = 0.0;
+        camera.look_at(self.focus);
+    }
+}
+
+/// Top-down camera panning over the XZ plane at a fixed pitch, for strategy games.
+///
+/// WASD and the cursor near the window edges pan, scrolling changes the height.
+#[derive(Debug, Clone)]
+pub struct PanController {
+    /// Ground point at the center of the view
+    pub target: Vec3f,
+    /// Camera height above the target
+    pub height: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Angle below the horizon in radians, looking straight down at `FRAC_PI_2`
+    pub pitch: f32,
+    /// Heading in radians, with the same convention as `Camera::yaw`
+    pub yaw: f32,
+    /// Pan speed per unit of height per second, so panning feels the same at every zoom
+    pub speed: f32,
+    /// Distance in pixels from the window edges that pans, 0 disables edge panning
+    pub edge_margin: f32,
+    /// Region the target is clamped to on the XZ plane, the y axis is ignored
+    pub bounds: Option<Aabb>,
+
+    /// x pans right and y forward, in [-1, 1]
+    pan: Vec2f,
+}
+
+impl Default for PanController {
+    fn default() -> Self {
+        Self {
+            target: Vec3f::zeros(),
+            height: 10.0,
+            min_height: 2.0,
+            max_height: 50.0,
+            pitch: 0.9,
+            yaw: -FRAC_PI_2,
+            speed: 1.0,
+            edge_margin: 16.0,
+            bounds: None,
+            pan: Vec2f::zeros(),
         }
-        if self.right {
-            camera.position -= right * self.speed * dt;
+    }
+}
+
+impl PanController {
+    pub fn handle_inputs(&mut self, inputs: &Inputs) {
+        let axis = |positive, negative| {
+            (inputs.action_held(positive) as i32 - inputs.action_held(negative) as i32) as f32
+        };
+        self.pan = Vec2f::new(
+            axis(MOVE_RIGHT, MOVE_LEFT),
+            axis(MOVE_FORWARD, MOVE_BACKWARD),
+        );
+
+        if self.edge_margin > 0.0
+            && let (Some((x, y)), Some((w, h))) = (inputs.cursor(), inputs.resolution())
+        {
+            let edge = |p: f32, size: u32| {
+                if p < self.edge_margin {
+                    -1.0
+                } else if p > size as f32 - self.edge_margin {
+                    1.0
+                } else {
+                    0.0
+                }
+            };
+            // Screen y grows downwards
+            self.pan += Vec2f::new(edge(x, w), -edge(y, h));
         }
-        if self.up {
-            camera.position += up_movement * self.speed * dt;
+        self.pan = self.pan.map(|v| v.clamp(-1.0, 1.0));
+
+        let (_, scroll) = inputs.scroll_diff();
+        self.height = (self.height * 0.9f32.powf(scroll)).clamp(self.min_height, self.max_height);
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: &Duration) {
+        let forward = Vec3f::new(self.yaw.cos(), 0.0, self.yaw.sin());
+        let right = Vec3f::new(-self.yaw.sin(), 0.0, self.yaw.cos());
+        self.target += (right * self.pan.x + forward * self.pan.y)
+            * self.speed
+            * self.height
+            * dt.as_secs_f32();
+        if let Some(bounds) = &self.bounds {
+            self.target.x = self.target.x.clamp(bounds.min.x, bounds.max.x);
+            self.target.z = self.target.z.clamp(bounds.min.z, bounds.max.z);
         }
-        if self.down {
-            camera.position -= up_movement * self.speed * dt;
+
+        let pitch = self.pitch.clamp(0.01, MAX_PITCH);
+        camera.yaw = self.yaw;
+        camera.pitch = -pitch;
+        camera.roll = 0.0;
+        camera.update_direction_from_angles();
+        camera.position =
+            self.target + Vec3f::y() * self.height - forward * self.height / pitch.tan();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoystickMode {
+    /// Axis is the offset from where the touch started, normalized by the joystick radius
+    Stick,
+    /// Axis is the touch movement in pixels during the last step, suitable for looking around
+    Drag,
+}
+
+/// Maps touches starting inside a screen region to a 2D axis.
+#[derive(Debug, Clone)]
+pub struct VirtualJoystick {
+    /// Region corners in normalized window coordinates (0..1, origin at the top left)
+    pub region_min: Vec2f,
+    pub region_max: Vec2f,
+    /// Distance in pixels for the stick to reach full deflection
+    pub radius: f32,
+    pub mode: JoystickMode,
+
+    touch_id: Option<u64>,
+    axis: Vec2f,
+}
+
+impl VirtualJoystick {
+    pub fn new(region_min: Vec2f, region_max: Vec2f, mode: JoystickMode) -> Self {
+        Self {
+            region_min,
+            region_max,
+            radius: 80.0,
+            mode,
+            touch_id: None,
+            axis: Vec2f::zeros(),
         }
     }
+
+    /// Left half of the screen, as a movement thumbstick
+    pub fn left_stick() -> Self {
+        Self::new(
+            Vec2f::new(0.0, 0.0),
+            Vec2f::new(0.5, 1.0),
+            JoystickMode::Stick,
+        )
+    }
+
+    /// Right half of the screen, as a look drag zone
+    pub fn right_drag() -> Self {
+        Self::new(
+            Vec2f::new(0.5, 0.0),
+            Vec2f::new(1.0, 1.0),
+            JoystickMode::Drag,
+        )
+    }
+
+    pub fn axis(&self) -> Vec2f {
+        self.axis
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.touch_id.is_some()
+    }
+
+    /// Track a touch inside the region and return the updated axis.
+    ///
+    /// In `Stick` mode, up on screen is positive y.
+    pub fn update(&mut self, inputs: &Inputs) -> Vec2f {
+        let touch = self.touch_id.and_then(|id| inputs.touch(id));
+        let touch = match touch {
+            Some(touch) => Some(touch),
+            None => {
+                self.touch_id = None;
+                inputs.resolution().and_then(|(w, h)| {
+                    inputs.touches().iter().find(|t| {
+                        let start = Vec2f::new(t.start.0 / w as f32, t.start.1 / h as f32);
+                        (0..2).all(|i| {
+                            start[i] >= self.region_min[i] && start[i] <= self.region_max[i]
+                        })
+                    })
+                })
+            }
+        };
+
+        self.axis = match touch {
+            Some(touch) => {
+                self.touch_id = Some(touch.id);
+                match self.mode {
+                    JoystickMode::Stick => {
+                        let offset = Vec2f::new(
+                            touch.position.0 - touch.start.0,
+                            touch.start.1 - touch.position.1,
+                        ) / self.radius.max(f32::EPSILON);
+                        offset.cap_magnitude(1.0)
+                    }
+                    JoystickMode::Drag => Vec2f::new(
+                        touch.position.0 - touch.position_prev.0,
+                        touch.position.1 - touch.position_prev.1,
+                    ),
+                }
+            }
+            None => Vec2f::zeros(),
+        };
+        self.axis
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Waypoint {
+    pub position: Vec3f,
+    /// Point the camera looks at when reaching the waypoint
+    pub target: Vec3f,
+}
+
+impl Waypoint {
+    pub fn new(position: Vec3f, target: Vec3f) -> Self {
+        Self { position, target }
+    }
+}
+
+/// Polyline of waypoints, parameterized by distance traveled so it is followed at a constant speed
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    waypoints: Vec<Waypoint>,
+    /// Distance from the first waypoint to each waypoint
+    distances: Vec<f32>,
+}
+
+impl CameraPath {
+    pub fn new(waypoints: Vec<Waypoint>) -> Self {
+        assert!(
+            !waypoints.is_empty(),
+            "A CameraPath needs at least one waypoint"
+        );
+        let mut distances = Vec::with_capacity(waypoints.len());
+        let mut total = 0.0;
+        distances.push(total);
+        for pair in waypoints.windows(2) {
+            total += (pair[1].position - pair[0].position).norm();
+            distances.push(total);
+        }
+        Self {
+            waypoints,
+            distances,
+        }
+    }
+
+    pub fn waypoints(&self) -> &[Waypoint] {
+        &self.waypoints
+    }
+
+    pub fn length(&self) -> f32 {
+        *self.distances.last().unwrap()
+    }
+
+    /// Interpolated waypoint `distance` units along the path, clamped to its ends
+    pub fn sample(&self, distance: f32) -> Waypoint {
+        let distance = distance.clamp(0.0, self.length());
+        let next = self
+            .distances
+            .partition_point(|&d| d < distance)
+            .clamp(1, self.waypoints.len().max(2) - 1);
+        let (Some(a), Some(b)) = (self.waypoints.get(next - 1), self.waypoints.get(next)) else {
+            return self.waypoints[0];
+        };
+
+        let segment = self.distances[next] - self.distances[next - 1];
+        let t = if segment > 0.0 {
+            (distance - self.distances[next - 1]) / segment
+        } else {
+            1.0
+        };
+        Waypoint {
+            position: a.position.lerp(&b.position, t),
+            target: a.target.lerp(&b.target, t),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed for the whole path
+    #[default]
+    Linear,
+    /// Accelerate from the start and decelerate into the end of the path
+    SmoothStep,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Moves a camera along a `CameraPath`, independently of the `Controller` speed settings
+#[derive(Debug, Clone)]
+pub struct CameraPathPlayer {
+    pub path: CameraPath,
+    /// Units per second along the path
+    pub speed: f32,
+    pub easing: Easing,
+    /// Restart from the first waypoint when reaching the end
+    pub looping: bool,
+
+    distance: f32,
+    playing: bool,
+}
+
+impl CameraPathPlayer {
+    pub fn new(path: CameraPath, speed: f32) -> Self {
+        Self {
+            path,
+            speed,
+            easing: Easing::Linear,
+            looping: false,
+            distance: 0.0,
+            playing: false,
+        }
+    }
+
+    /// Start from the first waypoint
+    pub fn play(&mut self) {
+        self.distance = 0.0;
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Normalized progress along the path before easing, in [0, 1]
+    pub fn t(&self) -> f32 {
+        let length = self.path.length();
+        if length > 0.0 {
+            self.distance / length
+        } else {
+            1.0
+        }
+    }
+
+    /// Advance and move the camera, does nothing when not playing
+    pub fn update(&mut self, camera: &mut Camera, dt: &Duration) {
+        if !self.playing {
+            return;
+        }
+
+        let length = self.path.length();
+        self.distance += self.speed * dt.as_secs_f32();
+        if self.distance >= length {
+            if self.looping && length > 0.0 {
+                self.distance %= length;
+            } else {
+                self.distance = length;
+                self.playing = false;
+            }
+        }
+
+        let waypoint = self.path.sample(self.easing.apply(self.t()) * length);
+        camera.position = waypoint.position;
+        camera.look_at(waypoint.target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_outside_bounds_is_clamped_onto_the_surface() {
+        let mut controller = Controller {
+            bounds: Some(Aabb::new(Vec3f::repeat(-1.0), Vec3f::repeat(1.0))),
+            bounds_axes: [true, false, true],
+            ..Default::default()
+        };
+        let mut camera = Camera {
+            position: Vec3f::new(3.0, 5.0, -4.0),
+            ..Default::default()
+        };
+
+        controller.update_camera(&mut camera, &Duration::ZERO);
+
+        assert_eq!(camera.position, Vec3f::new(1.0, 5.0, -1.0));
+    }
+
+    #[test]
+    fn reset_leaves_no_motion_behind() {
+        let mut controller = Controller {
+            damping: Damping::Exponential { rate: 1.0 },
+            forward: true,
+            mouse_delta: Vec2f::new(30.0, -20.0),
+            ..Default::default()
+        };
+        let mut camera = Camera::default();
+        let dt = Duration::from_millis(100);
+        controller.update_camera(&mut camera, &dt);
+
+        controller.reset();
+        let before = camera.clone();
+        controller.update_camera(&mut camera, &dt);
+
+        assert_eq!(camera.position, before.position);
+        assert_eq!((camera.yaw, camera.pitch), (before.yaw, before.pitch));
+    }
+
+    #[test]
+    fn damping_stops_within_expected_time() {
+        let curves = [
+            (Damping::Exponential { rate: 10.0 }, 1.0),
+            (Damping::Linear { acceleration: 20.0 }, 0.6),
+            (Damping::CriticallyDamped { smooth_time: 0.2 }, 1.0),
+        ];
+        for (damping, seconds) in curves {
+            let mut velocity = Vec3f::new(10.0, 0.0, 0.0);
+            let mut rate = Vec3f::zeros();
+            let dt = 1.0 / 60.0;
+            for _ in 0..(seconds / dt) as usize {
+                damping.apply(&mut velocity, &mut rate, Vec3f::zeros(), dt);
+            }
+            assert!(velocity.norm() < 0.01, "{damping:?} still at {velocity}");
+        }
+    }
+
+    #[test]
+    fn pick_focus_prefers_a_model_and_falls_back_to_the_ground() {
+        let mut camera = Camera {
+            position: Vec3f::new(0.0, 4.0, 4.0),
+            ..Default::default()
+        };
+        camera.look_at(Vec3f::zeros());
+        let (cursor, viewport) = (Vec2f::new(400.0, 300.0), Vec2u::new(800, 600));
+        let cube = |center: Vec3f| Aabb::new(center.add_scalar(-0.5), center.add_scalar(0.5));
+
+        let mut controller = Controller::default();
+        let focus = controller.pick_focus(
+            &camera,
+            cursor,
+            viewport,
+            &[cube(Vec3f::new(0.0, 2.0, 2.0))],
+        );
+        assert!((focus.unwrap() - Vec3f::new(0.0, 2.5, 2.5)).norm() < 1e-4);
+        assert_eq!(controller.focus, focus.unwrap());
+        assert_eq!(controller.mode, ControllerMode::Orbit);
+
+        let mut controller = Controller::default();
+        let focus = controller.pick_focus(
+            &camera,
+            cursor,
+            viewport,
+            &[cube(Vec3f::new(10.0, 0.0, 0.0))],
+        );
+        assert!(focus.unwrap().norm() < 1e-4);
+    }
+
+    #[test]
+    fn injected_keys_drive_the_camera_along_a_path() {
+        use winit::keyboard::KeyCode;
+
+        let mut inputs = Inputs::new();
+        let mut controller = Controller::default();
+        let mut camera = Camera::default();
+        let dt = Duration::from_millis(100);
+        let mut run = |steps: usize, keys: &[KeyCode], camera: &mut Camera| {
+            for i in 0..steps {
+                inputs.step();
+                // Pressed on the first step, held after
+                if i == 0 {
+                    keys.iter().for_each(|key| inputs.inject_key(*key, true));
+                }
+                inputs.end_step();
+                controller.handle_inputs(&inputs, false);
+                controller.update_camera(camera, &dt);
+            }
+            inputs.step();
+            keys.iter().for_each(|key| inputs.inject_key(*key, false));
+            inputs.end_step();
+            controller.handle_inputs(&inputs, false);
+        };
+
+        // Forward towards -z, then strafe right along +x, at 2 units per second
+        run(10, &[KeyCode::KeyW], &mut camera);
+        assert!((camera.position - Vec3f::new(0.0, 0.0, 3.0)).norm() < 1e-4);
+        run(5, &[KeyCode::KeyD], &mut camera);
+        assert!((camera.position - Vec3f::new(1.0, 0.0, 3.0)).norm() < 1e-4);
+
+        // Released keys leave the camera in place
+        controller.update_camera(&mut camera, &dt);
+        assert!((camera.position - Vec3f::new(1.0, 0.0, 3.0)).norm() < 1e-4);
+    }
 }
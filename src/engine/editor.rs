@@ -7,18 +7,66 @@ use egui_winit::{
     update_viewport_info,
 };
 use wgpu::{LoadOp, Operations, RenderPassColorAttachment, RenderPassDescriptor, StoreOp};
-use winit::event::WindowEvent;
+use winit::{
+    event::{ElementState, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use super::graphics::{self, Frame, frame_stats::FrameStats, renderer::Renderer};
+use crate::{
+    GameState,
+    engine::{
+        AppContext, FullscreenMode,
+        controller::{ControllerMode, Damping},
+        graphics::camera::{Camera, DEFAULT_FOV_Y, Projection, StandardView},
+        graphics::color::Color3f,
+        log,
+        maths::{Aabb, Vec2f, Vec2u, Vec3f},
+    },
+};
 
-use super::graphics::{self, Frame};
-use crate::{GameState, engine::maths::Vec3f};
+/// Engine state available to editor panels
+pub struct EditorContext<'a> {
+    pub app: &'a mut AppContext,
+    pub graphics: &'a graphics::Graphics,
+    pub renderer: &'a mut Renderer,
+    /// See `Editor::selection`
+    pub selection: &'a mut Option<Selection>,
+}
 
-pub(super) struct Editor {
+/// Scene item edited through its gizmo in the viewport
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Selection {
+    /// The scene light, see `direction_gizmo`
+    Light,
+}
+
+/// A modular editor window, toggled from the editor menu bar.
+pub trait EditorPanel {
+    fn name(&self) -> &str;
+    fn ui(&mut self, ui: &mut egui::Ui, state: &mut GameState, engine: &mut EditorContext);
+}
+
+struct RegisteredPanel {
+    panel: Box<dyn EditorPanel>,
+    visible: bool,
+}
+
+pub struct Editor {
+    /// Hidden editors neither render nor consume events, see `toggle_key`
+    pub visible: bool,
+    /// Shows or hides the editor, F1 by default
+    pub toggle_key: KeyCode,
     init: bool,
     repaint: bool,
     vinfo: ViewportInfo,
     sdesc: ScreenDescriptor,
     ui: egui_winit::State,
     paint_jobs: Vec<ClippedPrimitive>,
+    panels: Vec<RegisteredPanel>,
+    renderer: egui_wgpu::Renderer,
+    /// Item whose gizmo was pressed last, cleared by clicking the scene outside of any handle
+    pub selection: Option<Selection>,
 }
 
 fn size_desc(
@@ -38,17 +86,31 @@ fn size_desc(
         },
         ScreenDescriptor {
             size_in_pixels: [screen_size.x as u32, screen_size.y as u32],
-            pixels_per_point: pixels_per_point,
+            pixels_per_point,
         },
     )
 }
 
+/// Visibility after a key event, None when the event is not a fresh press of `toggle_key`
+fn toggled_visibility(
+    visible: bool,
+    toggle_key: KeyCode,
+    key: PhysicalKey,
+    state: ElementState,
+    repeat: bool,
+) -> Option<bool> {
+    (state == ElementState::Pressed && !repeat && key == PhysicalKey::Code(toggle_key))
+        .then_some(!visible)
+}
+
 impl Editor {
-    pub fn new(window: Arc<winit::window::Window>) -> Self {
+    pub fn new(window: Arc<winit::window::Window>, g: &graphics::Graphics) -> Self {
         let ctx = egui::Context::default();
         let viewport_id = ctx.viewport_id();
         let (vinfo, sdesc) = size_desc(&ctx, &window);
-        Self {
+        let mut editor = Self {
+            visible: true,
+            toggle_key: KeyCode::F1,
             init: true,
             repaint: false,
             ui: egui_winit::State::new(
@@ -62,9 +124,63 @@ impl Editor {
             vinfo,
             sdesc,
             paint_jobs: vec![],
+            panels: vec![],
+            selection: None,
+            renderer: egui_wgpu::Renderer::new(
+                &g.device,
+                g.surface_format,
+                None, // Some(TextureWrapper::DEPTH_FORMAT)
+                1,
+                false,
+            ),
+        };
+        editor.add_panel(CameraPanel::default(), true);
+        editor.add_panel(StatsPanel, false);
+        editor.add_panel(FrameTimePanel::default(), false);
+        editor.add_panel(UniformsPanel, false);
+        editor.add_panel(PostPanel, false);
+        editor.add_panel(MaterialsPanel, false);
+        editor.add_panel(DisplayPanel, false);
+        editor.add_panel(LightPanel::default(), false);
+        editor.add_panel(MemoryPanel, false);
+        editor.add_panel(LogPanel, false);
+        editor
+    }
+
+    pub fn add_panel(&mut self, panel: impl EditorPanel + 'static, visible: bool) {
+        self.panels.push(RegisteredPanel {
+            panel: Box::new(panel),
+            visible,
+        });
+    }
+
+    /// Flip `visible` when the event is a press of `toggle_key`, return true if it was
+    pub fn on_toggle_key(&mut self, event: &winit::event::WindowEvent) -> bool {
+        let WindowEvent::KeyboardInput { event, .. } = event else {
+            return false;
+        };
+        match toggled_visibility(
+            self.visible,
+            self.toggle_key,
+            event.physical_key,
+            event.state,
+            event.repeat,
+        ) {
+            Some(visible) => {
+                self.set_visible(visible);
+                true
+            }
+            None => false,
         }
     }
 
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        // Drop the last frame so that showing the editor again starts from a fresh one
+        self.paint_jobs.clear();
+        self.repaint = true;
+    }
+
     /// return true if event is consumed
     pub fn on_window_event_consume(
         &mut self,
@@ -72,10 +188,13 @@ impl Editor {
         event: &winit::event::WindowEvent,
     ) -> bool {
         if let WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } = event {
-            let (vinfo, sdesc) = size_desc(&self.ui.egui_ctx(), &window);
+            let (vinfo, sdesc) = size_desc(self.ui.egui_ctx(), window);
             self.vinfo = vinfo;
             self.sdesc = sdesc;
         }
+        if !self.visible {
+            return false;
+        }
 
         let res = self.ui.on_window_event(window, event);
         self.repaint |= res.repaint;
@@ -89,17 +208,32 @@ impl Editor {
     pub fn render(
         &mut self,
         state: &mut GameState,
+        app: &mut AppContext,
         window: &winit::window::Window,
-        renderer: &mut egui_wgpu::Renderer,
+        game_renderer: &mut Renderer,
         g: &graphics::Graphics,
         frame: &mut Frame,
     ) {
+        if !self.visible {
+            return;
+        }
+        let renderer = &mut self.renderer;
         if self.repaint {
             update_viewport_info(&mut self.vinfo, self.ui.egui_ctx(), window, self.init);
             self.init = false;
 
             let input = self.ui.take_egui_input(window);
-            let output = self.ui.egui_ctx().run(input, |ctx| state.editor_ui(ctx));
+            let panels = &mut self.panels;
+            let mut engine = EditorContext {
+                app,
+                graphics: g,
+                renderer: game_renderer,
+                selection: &mut self.selection,
+            };
+            let output = self.ui.egui_ctx().run(input, |ctx| {
+                panels_ui(ctx, panels, state, &mut engine);
+                state.editor_ui(ctx);
+            });
 
             let paint_jobs = self
                 .ui
@@ -122,7 +256,11 @@ impl Editor {
             );
 
             self.paint_jobs = paint_jobs;
-            self.repaint = false;
+            // Panels showing live values ask for the next frame with `request_repaint`
+            self.repaint = output
+                .viewport_output
+                .values()
+                .any(|viewport| viewport.repaint_delay.is_zero());
         }
 
         let render_pass = frame.encoder.begin_render_pass(&RenderPassDescriptor {
@@ -151,10 +289,324 @@ impl std::fmt::Debug for Editor {
             .field("init", &self.init)
             .field("repaint", &self.repaint)
             .field("vinfo", &self.vinfo)
+            .field(
+                "panels",
+                &self
+                    .panels
+                    .iter()
+                    .map(|p| p.panel.name())
+                    .collect::<Vec<_>>(),
+            )
             .finish()
     }
 }
 
+fn panels_ui(
+    ctx: &egui::Context,
+    panels: &mut [RegisteredPanel],
+    state: &mut GameState,
+    engine: &mut EditorContext,
+) {
+    egui::TopBottomPanel::top("Editor menu bar").show(ctx, |ui| {
+        egui::MenuBar::new().ui(ui, |ui| {
+            ui.menu_button("Panels", |ui| {
+                for p in panels.iter_mut() {
+                    ui.checkbox(&mut p.visible, p.panel.name());
+                }
+            });
+        });
+    });
+
+    // Before the panels, whose gizmo handles select on the same click
+    if ctx.input(|i| i.pointer.primary_clicked()) && !ctx.is_pointer_over_area() {
+        *engine.selection = None;
+    }
+    for p in panels.iter_mut() {
+        let RegisteredPanel { panel, visible } = p;
+        egui::Window::new(panel.name().to_owned())
+            .open(visible)
+            .show(ctx, |ui| panel.ui(ui, state, engine));
+    }
+}
+
+pub struct CameraPanel {
+    pub framing_distance: f32,
+}
+
+impl Default for CameraPanel {
+    fn default() -> Self {
+        Self {
+            framing_distance: 5.0,
+        }
+    }
+}
+
+impl EditorPanel for CameraPanel {
+    fn name(&self) -> &str {
+        "Camera"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, state: &mut GameState, engine: &mut EditorContext) {
+        colored_vec3_label(ui, "Position:", &state.camera.position);
+        colored_f32_label(ui, "Yaw:", state.camera.yaw, egui::Color32::YELLOW);
+        colored_f32_label(ui, "Pitch:", state.camera.pitch, egui::Color32::MAGENTA);
+        ui.add(egui::Slider::new(&mut state.controller.speed, 0.1..=20.).text("Speed"));
+        ui.add(egui::Slider::new(&mut state.controller.sensitivity, 0.01..=1.).text("Sensitivity"));
+        ui.horizontal(|ui| {
+            let mode = &mut state.controller.mode;
+            ui.selectable_value(mode, ControllerMode::Fly, "Fly");
+            ui.selectable_value(mode, ControllerMode::Orbit, "Orbit");
+        });
+        if state.controller.mode == ControllerMode::Fly {
+            damping_ui(ui, &mut state.controller.damping);
+        }
+        if state.controller.mode == ControllerMode::Orbit {
+            colored_vec3_label(ui, "Focus:", &state.controller.focus);
+            let controller = &mut state.controller;
+            ui.add(
+                egui::Slider::new(
+                    &mut controller.orbit_radius,
+                    controller.min_orbit_radius..=controller.max_orbit_radius,
+                )
+                .text("Orbit radius"),
+            );
+        }
+
+        ui.separator();
+
+        let mut grid = engine.renderer.grid_visible();
+        if ui.checkbox(&mut grid, "Grid").changed() {
+            engine.renderer.set_grid_visible(engine.graphics, grid);
+        }
+        let mut orthographic = matches!(state.camera.projection, Projection::Orthographic { .. });
+        if ui.checkbox(&mut orthographic, "Orthographic").changed() {
+            let (z_near, z_far) = (
+                state.camera.projection.z_near(),
+                state.camera.projection.z_far(),
+            );
+            state.camera.projection = if orthographic {
+                Projection::Orthographic {
+                    height: self.framing_distance,
+                    z_near,
+                    z_far,
+                }
+            } else {
+                Projection::Perspective {
+                    fov_y: DEFAULT_FOV_Y,
+                    z_near,
+                    z_far,
+                }
+            };
+        }
+        let (z_near, z_far) = match &mut state.camera.projection {
+            Projection::Perspective {
+                fov_y,
+                z_near,
+                z_far,
+            } => {
+                ui.add(egui::Slider::new(fov_y, 0.05..=3.0).text("FOV (rad)"));
+                (z_near, z_far)
+            }
+            Projection::Orthographic {
+                height,
+                z_near,
+                z_far,
+            } => {
+                ui.add(egui::Slider::new(height, 0.1..=200.0).text("Height"));
+                (z_near, z_far)
+            }
+        };
+        ui.add(
+            egui::Slider::new(z_near, 0.001..=10.0)
+                .logarithmic(true)
+                .text("Near"),
+        );
+        ui.add(
+            egui::Slider::new(z_far, 10.0..=10000.0)
+                .logarithmic(true)
+                .text("Far"),
+        );
+        *z_far = z_far.max(*z_near + 0.01);
+
+        if let Some(turntable) = &mut state.turntable {
+            ui.separator();
+            ui.label("Turntable");
+            ui.add(egui::Slider::new(&mut turntable.angular_speed, -3.0..=3.0).text("Speed"));
+            ui.add(
+                egui::Slider::new(
+                    &mut turntable.radius,
+                    turntable.min_radius..=turntable.max_radius,
+                )
+                .text("Radius"),
+            );
+            ui.add(egui::Slider::new(&mut turntable.height, -10.0..=10.0).text("Height"));
+            let mut paused = turntable.is_paused();
+            if ui.checkbox(&mut paused, "Paused").changed() {
+                if paused {
+                    turntable.pause();
+                } else {
+                    turntable.resume();
+                }
+            }
+        }
+
+        ui.separator();
+
+        ui.add(egui::Slider::new(&mut self.framing_distance, 0.5..=50.).text("Framing distance"));
+        ui.horizontal(|ui| {
+            for view in StandardView::ALL {
+                if ui.button(format!("{view:?}")).clicked() {
+                    state
+                        .camera
+                        .snap_to_view(view, Vec3f::zeros(), self.framing_distance);
+                }
+            }
+        });
+    }
+}
+
+fn damping_ui(ui: &mut egui::Ui, damping: &mut Damping) {
+    ui.horizontal(|ui| {
+        ui.label("Inertia");
+        let presets = [
+            ("None", Damping::None),
+            ("Exponential", Damping::Exponential { rate: 8.0 }),
+            ("Linear", Damping::Linear { acceleration: 20.0 }),
+            ("Spring", Damping::CriticallyDamped { smooth_time: 0.2 }),
+        ];
+        for (name, preset) in presets {
+            let selected = std::mem::discriminant(damping) == std::mem::discriminant(&preset);
+            if ui.selectable_label(selected, name).clicked() && !selected {
+                *damping = preset;
+            }
+        }
+    });
+    match damping {
+        Damping::None => {}
+        Damping::Exponential { rate } => {
+            ui.add(egui::Slider::new(rate, 0.5..=30.0).text("Rate"));
+        }
+        Damping::Linear { acceleration } => {
+            ui.add(egui::Slider::new(acceleration, 1.0..=100.0).text("Acceleration"));
+        }
+        Damping::CriticallyDamped { smooth_time } => {
+            ui.add(egui::Slider::new(smooth_time, 0.01..=2.0).text("Smooth time (s)"));
+        }
+    }
+}
+
+pub struct StatsPanel;
+
+impl EditorPanel for StatsPanel {
+    fn name(&self) -> &str {
+        "Stats"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &mut GameState, engine: &mut EditorContext) {
+        let frame_stats = &engine.graphics.frame_stats;
+        colored_f32_label(
+            ui,
+            "Frame time (ms):",
+            engine.graphics.frame_time_ms(),
+            egui::Color32::LIGHT_BLUE,
+        );
+        colored_f32_label(
+            ui,
+            "FPS:",
+            engine.graphics.fps(),
+            egui::Color32::LIGHT_GREEN,
+        );
+        ui.label(format!(
+            "Min/max (ms): {:.2} / {:.2} over {} frames",
+            frame_stats.min().as_secs_f32() * 1000.0,
+            frame_stats.max().as_secs_f32() * 1000.0,
+            frame_stats.len()
+        ));
+
+        ui.separator();
+
+        let model = &mut engine.renderer.model;
+        let stats = model.stats();
+        ui.label(format!("Models drawn: {}", stats.models_drawn));
+        ui.label(format!("Models culled: {}", stats.models_culled));
+        ui.label(format!("Visibility changes: {}", stats.visibility_changes));
+        ui.label(format!("LOD histogram: {:?}", stats.lod_histogram));
+        ui.checkbox(&mut model.culling_enabled, "Frustum culling");
+        ui.add(egui::Slider::new(&mut model.culling_margin, 0.0..=5.0).text("Culling margin"));
+    }
+}
+
+/// Plot of the recent frame times from `Graphics::frame_stats`
+pub struct FrameTimePanel {
+    /// Number of frames shown, up to the capacity of the frame stats
+    pub window: usize,
+    /// Frames slower than this are drawn in red
+    pub budget_ms: f32,
+}
+
+impl Default for FrameTimePanel {
+    fn default() -> Self {
+        Self {
+            window: FrameStats::DEFAULT_CAPACITY,
+            budget_ms: 1000.0 / 60.0,
+        }
+    }
+}
+
+impl EditorPanel for FrameTimePanel {
+    fn name(&self) -> &str {
+        "Frame time"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &mut GameState, engine: &mut EditorContext) {
+        let frame_stats = &engine.graphics.frame_stats;
+        ui.add(egui::Slider::new(&mut self.window, 2..=frame_stats.capacity()).text("Frames"));
+        ui.add(egui::Slider::new(&mut self.budget_ms, 1.0..=50.0).text("Budget (ms)"));
+        if ui.button("Reset").clicked() {
+            engine.app.reset_frame_stats();
+        }
+
+        let shown = self.window.min(frame_stats.len());
+        let samples: Vec<f32> = frame_stats
+            .frame_times()
+            .skip(frame_stats.len() - shown)
+            .map(|t| t.as_secs_f32() * 1000.0)
+            .collect();
+        let over_budget = samples.iter().filter(|&&ms| ms > self.budget_ms).count();
+        ui.label(format!("Over budget: {over_budget} / {}", samples.len()));
+
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(ui.available_width(), 100.0),
+            egui::Sense::hover(),
+        );
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(64));
+        let max_ms = samples.iter().copied().fold(self.budget_ms * 1.5, f32::max);
+        let y = |ms: f32| rect.bottom() - ms / max_ms * rect.height();
+        let x = |i: usize| rect.left() + i as f32 / (self.window - 1) as f32 * rect.width();
+        painter.hline(
+            rect.x_range(),
+            y(self.budget_ms),
+            egui::Stroke::new(1.0, egui::Color32::GRAY),
+        );
+        for (i, pair) in samples.windows(2).enumerate() {
+            let color = if pair[1] > self.budget_ms {
+                egui::Color32::RED
+            } else {
+                egui::Color32::LIGHT_GREEN
+            };
+            painter.line_segment(
+                [
+                    egui::pos2(x(i), y(pair[0])),
+                    egui::pos2(x(i + 1), y(pair[1])),
+                ],
+                egui::Stroke::new(1.5, color),
+            );
+        }
+        ui.ctx().request_repaint();
+    }
+}
+
 pub fn colored_vec3_label(ui: &mut egui::Ui, label_prefix: &str, vec: &Vec3f) {
     ui.horizontal(|ui| {
         ui.label(label_prefix);
@@ -182,6 +634,17 @@ pub fn colored_f32_label(ui: &mut egui::Ui, label_prefix: &str, value: f32, colo
     });
 }
 
+/// Edit a color in place, return true when it changed. The components are linear like every
+/// color of the engine, egui shows them converted to sRGB as `Color3f::to_srgba_unorm` does.
+pub fn color_picker(ui: &mut egui::Ui, label: &str, color: &mut Color3f) -> bool {
+    ui.horizontal(|ui| {
+        let changed = ui.color_edit_button_rgb(color.array_mut()).changed();
+        ui.label(label);
+        changed
+    })
+    .inner
+}
+
 pub fn bool_label(ui: &mut egui::Ui, label_prefix: &str, value: bool) {
     let color = if value {
         egui::Color32::from_rgb(0, 255, 0) // Bright Green
@@ -193,3 +656,341 @@ pub fn bool_label(ui: &mut egui::Ui, label_prefix: &str, value: bool) {
         ui.label(egui::RichText::new(format!("{}", value)).color(color));
     });
 }
+
+/// Live content of the uniforms registered in the renderer's `UniformInspector`
+pub struct UniformsPanel;
+
+impl EditorPanel for UniformsPanel {
+    fn name(&self) -> &str {
+        "Uniforms"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &mut GameState, engine: &mut EditorContext) {
+        let inspector = &mut engine.renderer.inspector;
+        inspector.refresh(engine.graphics);
+        inspector.ui(ui);
+    }
+}
+
+pub struct DisplayPanel;
+
+impl EditorPanel for DisplayPanel {
+    fn name(&self) -> &str {
+        "Display"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &mut GameState, engine: &mut EditorContext) {
+        let app = &mut *engine.app;
+        let current = app.current_monitor().and_then(|m| m.name());
+        ui.label(format!(
+            "Current monitor: {}",
+            current.as_deref().unwrap_or("unknown")
+        ));
+        match app.video_mode() {
+            Some(mode) => ui.label(format!("Video mode: {}", video_mode_label(mode))),
+            None => ui.label("Video mode: windowed"),
+        };
+        ui.horizontal(|ui| {
+            let current = app.fullscreen_mode();
+            for (mode, name) in [
+                (FullscreenMode::Windowed, "Windowed"),
+                (FullscreenMode::Borderless, "Borderless"),
+                (FullscreenMode::Exclusive, "Exclusive"),
+            ] {
+                if ui.selectable_label(current == mode, name).clicked() {
+                    app.set_fullscreen(mode);
+                }
+            }
+        });
+
+        let mut request = None;
+        for (i, monitor) in app.monitors().iter().enumerate() {
+            let name = monitor.name().unwrap_or_else(|| format!("Monitor {i}"));
+            egui::CollapsingHeader::new(name).id_salt(i).show(ui, |ui| {
+                for mode in monitor.video_modes() {
+                    if ui.button(video_mode_label(&mode)).clicked() {
+                        request =
+                            Some((monitor.clone(), mode.size(), mode.refresh_rate_millihertz()));
+                    }
+                }
+            });
+        }
+        if let Some((monitor, size, refresh_rate)) = request {
+            app.set_exclusive_fullscreen(&monitor, size, refresh_rate);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("MSAA");
+            for &count in &engine.graphics.capabilities.sample_counts {
+                let selected = engine.graphics.sample_count == count;
+                if ui.selectable_label(selected, format!("{count}x")).clicked() {
+                    engine.renderer.set_msaa(count);
+                }
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Present mode");
+            for &mode in &engine.graphics.surface_capabilities.present_modes {
+                let selected = engine.graphics.present_mode == mode;
+                if ui.selectable_label(selected, format!("{mode:?}")).clicked() {
+                    engine.app.set_present_mode(mode);
+                }
+            }
+        });
+    }
+}
+
+fn video_mode_label(mode: &winit::monitor::VideoModeHandle) -> String {
+    format!(
+        "{}x{} @ {:.0}Hz, {} bit",
+        mode.size().width,
+        mode.size().height,
+        mode.refresh_rate_millihertz() as f32 / 1000.0,
+        mode.bit_depth()
+    )
+}
+
+pub struct MaterialsPanel;
+
+impl EditorPanel for MaterialsPanel {
+    fn name(&self) -> &str {
+        "Materials"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &mut GameState, engine: &mut EditorContext) {
+        for (i, (_, material)) in engine.renderer.model.materials_mut().enumerate() {
+            egui::CollapsingHeader::new(material.name())
+                .id_salt(i)
+                .show(ui, |ui| material.ui(ui));
+        }
+    }
+}
+
+pub struct LightPanel {
+    show_gizmo: bool,
+}
+
+impl Default for LightPanel {
+    fn default() -> Self {
+        Self { show_gizmo: true }
+    }
+}
+
+impl EditorPanel for LightPanel {
+    fn name(&self) -> &str {
+        "Light"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, state: &mut GameState, engine: &mut EditorContext) {
+        let light = &mut engine.renderer.light;
+        color_picker(ui, "Color", &mut light.color);
+        ui.add(egui::Slider::new(&mut light.ambient, 0.0..=1.0).text("Ambient"));
+        ui.checkbox(&mut self.show_gizmo, "Direction gizmo");
+
+        if self.show_gizmo {
+            // The handle points towards the light, opposite to the direction it travels in
+            let camera = &state.camera;
+            let origin = camera.position + camera.direction * 5.0;
+            let mut towards_light = -engine.renderer.light.direction;
+            if direction_gizmo(
+                ui.ctx(),
+                engine,
+                Selection::Light,
+                camera,
+                origin,
+                1.5,
+                &mut towards_light,
+            ) {
+                engine.renderer.light.direction = -towards_light;
+            }
+            // Gizmos are queued again every frame
+            ui.ctx().request_repaint();
+        }
+        let direction = engine.renderer.light.direction;
+        ui.label(format!(
+            "Direction: {:.2} {:.2} {:.2}",
+            direction.x, direction.y, direction.z
+        ));
+    }
+}
+
+pub struct MemoryPanel;
+
+impl EditorPanel for MemoryPanel {
+    fn name(&self) -> &str {
+        "GPU memory"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &mut GameState, engine: &mut EditorContext) {
+        let usage = engine.renderer.memory_usage();
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        ui.label(format!(
+            "Buffers: {} ({:.2} MiB)",
+            usage.buffers,
+            mib(usage.buffer_bytes)
+        ));
+        ui.label(format!(
+            "Textures: {} ({:.2} MiB)",
+            usage.textures,
+            mib(usage.texture_bytes)
+        ));
+        ui.label(format!("Total: {:.2} MiB", mib(usage.total_bytes())));
+        ui.weak("Estimated from the resource sizes");
+    }
+}
+
+/// Engine messages sent with the `log!` macro
+pub struct LogPanel;
+
+impl EditorPanel for LogPanel {
+    fn name(&self) -> &str {
+        "Log"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &mut GameState, _engine: &mut EditorContext) {
+        if ui.button("Clear").clicked() {
+            log::clear();
+        }
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for message in log::messages() {
+                    ui.label(format!(
+                        "[{:>8.3}] {}",
+                        message.time.as_secs_f32(),
+                        message.text
+                    ));
+                }
+            });
+    }
+}
+
+pub struct PostPanel;
+
+impl EditorPanel for PostPanel {
+    fn name(&self) -> &str {
+        "Post processing"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &mut GameState, engine: &mut EditorContext) {
+        let effects = &mut engine.renderer.post.effects;
+        if effects.is_empty() {
+            ui.label("No post effect");
+        }
+        for (i, effect) in effects.iter_mut().enumerate() {
+            egui::CollapsingHeader::new(effect.name())
+                .id_salt(i)
+                .default_open(true)
+                .show(ui, |ui| effect.ui(ui));
+        }
+    }
+}
+
+/// Draggable direction handle: a gizmo line from `origin` to a handle at
+/// `origin + direction * length`. Pressing the handle makes `item` the editor selection, and
+/// dragging the selected handle projects the cursor onto the sphere of radius `length` around
+/// `origin`. Returns true when `direction` changed.
+pub fn direction_gizmo(
+    ctx: &egui::Context,
+    engine: &mut EditorContext,
+    item: Selection,
+    camera: &Camera,
+    origin: Vec3f,
+    length: f32,
+    direction: &mut Vec3f,
+) -> bool {
+    const HANDLE_RADIUS: f32 = 8.0;
+
+    let selected = *engine.selection == Some(item);
+    let color = if selected {
+        Color3f::WHITE
+    } else {
+        Color3f::YELLOW
+    };
+    let handle = origin + direction.normalize() * length;
+    let gizmos = &mut engine.renderer.model.gizmos;
+    gizmos.line(origin, handle, color);
+    let half_size = Vec3f::repeat(length * 0.05);
+    gizmos.aabb(&Aabb::new(handle - half_size, handle + half_size), color);
+
+    let screen = ctx.screen_rect();
+    let dims = Vec2u::new(
+        screen.width().max(1.) as u32,
+        screen.height().max(1.) as u32,
+    );
+    let (view, proj) = camera.get_view_proj_matrices(dims);
+    let clip = proj * view * handle.push(1.0);
+    if clip.w <= 0.0 {
+        return false;
+    }
+    let ndc = clip.xyz() / clip.w;
+    let handle_pos = egui::pos2(
+        screen.min.x + (ndc.x * 0.5 + 0.5) * screen.width(),
+        screen.min.y + (0.5 - ndc.y * 0.5) * screen.height(),
+    );
+
+    // Invisible hit area, so presses on the handle are kept from the game
+    let response = egui::Area::new(egui::Id::new(("direction_gizmo", item)))
+        .fixed_pos(handle_pos - egui::vec2(HANDLE_RADIUS, HANDLE_RADIUS))
+        .order(egui::Order::Background)
+        .show(ctx, |ui| {
+            ui.allocate_response(
+                egui::Vec2::splat(HANDLE_RADIUS * 2.0),
+                egui::Sense::click_and_drag(),
+            )
+        })
+        .inner;
+    if response.clicked() || response.drag_started() {
+        *engine.selection = Some(item);
+    }
+
+    let Some(pointer) = response
+        .interact_pointer_pos()
+        .filter(|_| response.dragged() && *engine.selection == Some(item))
+    else {
+        return false;
+    };
+    let cursor = Vec2f::new(pointer.x - screen.min.x, pointer.y - screen.min.y);
+    let (ray_origin, ray_dir) = camera.screen_ray(cursor, dims);
+
+    // Closest hit on the sphere, or the point of the ray closest to the sphere when missing it
+    let to_origin = origin - ray_origin;
+    let t_closest = to_origin.dot(&ray_dir);
+    let closest_sq = to_origin.norm_squared() - t_closest * t_closest;
+    let t = if closest_sq <= length * length {
+        t_closest - (length * length - closest_sq).sqrt()
+    } else {
+        t_closest
+    };
+    let new_direction = (ray_origin + ray_dir * t - origin).normalize();
+
+    if new_direction.iter().all(|c| c.is_finite()) {
+        *direction = new_direction;
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_key_presses_flip_the_visibility() {
+        let toggle = |visible, key, state, repeat| {
+            toggled_visibility(visible, KeyCode::F1, PhysicalKey::Code(key), state, repeat)
+        };
+        let (pressed, released) = (ElementState::Pressed, ElementState::Released);
+
+        assert_eq!(toggle(true, KeyCode::F1, pressed, false), Some(false));
+        assert_eq!(toggle(false, KeyCode::F1, pressed, false), Some(true));
+        // Held keys, releases and other keys leave the editor as it is
+        assert_eq!(toggle(true, KeyCode::F1, pressed, true), None);
+        assert_eq!(toggle(true, KeyCode::F1, released, false), None);
+        assert_eq!(toggle(true, KeyCode::F2, pressed, false), None);
+    }
+}
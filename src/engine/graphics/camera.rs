@@ -1,15 +1,151 @@
-use std::f32::consts::FRAC_PI_2;
+use std::f32::consts::{FRAC_PI_2, PI};
 
 use bytemuck::{Pod, Zeroable};
 use nalgebra::Point3;
+use serde::{Deserialize, Serialize};
 use wgpu::util::DeviceExt;
 
 use crate::engine::{
-    graphics::Graphics,
-    maths::{Mat4f, Vec2u, Vec3f},
+    graphics::{Graphics, memory::TrackedMemory},
+    maths::{
+        Aabb, Mat4f, Quatf, Vec2f, Vec2u, Vec3f, Vec4f, quat_forward, quat_from_yaw_pitch_roll,
+        quat_to_yaw_pitch_roll, quat_up,
+    },
 };
 
-#[derive(Debug)]
+/// Pitch limit preventing the camera from flipping over
+pub const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// Parameters of `Projection::default`, set `Camera::projection` to change them
+pub const DEFAULT_FOV_Y: f32 = FRAC_PI_2;
+pub const DEFAULT_Z_NEAR: f32 = 0.1;
+pub const DEFAULT_Z_FAR: f32 = 100.0;
+
+/// Camera uniform declaration and helpers shared by materials, prepend it to your shader source.
+///
+/// `log_depth` must be applied to the clip position in the vertex shader so that materials agree
+/// on the depth distribution when logarithmic depth is enabled on the renderer.
+pub const CAMERA_WGSL: &str = r#"
+struct CameraUniform {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    // x: logarithmic depth enabled, y: far plane, z: C constant
+    depth: vec4<f32>,
+};
+
+fn log_depth(clip: vec4<f32>, depth: vec4<f32>) -> vec4<f32> {
+    if (depth.x == 0.0) {
+        return clip;
+    }
+    var out = clip;
+    out.z = log2(max(1e-6, depth.z * clip.w + 1.0)) / log2(depth.z * depth.y + 1.0) * clip.w;
+    return out;
+}
+
+// Inverse of the projection depth mapping, returns the distance along the view axis.
+// `planes` comes from `CameraUniform::depth_planes`.
+fn linearize_depth(depth: f32, planes: vec4<f32>) -> f32 {
+    let near = planes.x;
+    let far = planes.y;
+    if (planes.w > 0.0) {
+        return (exp2(depth * log2(planes.w * far + 1.0)) - 1.0) / planes.w;
+    }
+    if (planes.z != 0.0) {
+        return (depth * (far - near) + far + near) * 0.5;
+    }
+    return 2.0 * near * far / ((far + near) - depth * (far - near));
+}
+"#;
+
+/// Axis aligned views, named after the side of the scene they look at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandardView {
+    Top,
+    Bottom,
+    Front,
+    Back,
+    Left,
+    Right,
+}
+
+impl StandardView {
+    pub const ALL: [Self; 6] = [
+        Self::Top,
+        Self::Bottom,
+        Self::Front,
+        Self::Back,
+        Self::Left,
+        Self::Right,
+    ];
+
+    /// Returns the (yaw, pitch) looking at this side of the scene
+    pub fn angles(&self) -> (f32, f32) {
+        match self {
+            Self::Top => (-FRAC_PI_2, -MAX_PITCH),
+            Self::Bottom => (-FRAC_PI_2, MAX_PITCH),
+            Self::Front => (-FRAC_PI_2, 0.0),
+            Self::Back => (FRAC_PI_2, 0.0),
+            Self::Left => (0.0, 0.0),
+            Self::Right => (PI, 0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    /// `fov_y` is the vertical field of view in radians
+    Perspective { fov_y: f32, z_near: f32, z_far: f32 },
+    /// `height` is the extent of the view in world units, the width follows the aspect ratio
+    Orthographic {
+        height: f32,
+        z_near: f32,
+        z_far: f32,
+    },
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::Perspective {
+            fov_y: DEFAULT_FOV_Y,
+            z_near: DEFAULT_Z_NEAR,
+            z_far: DEFAULT_Z_FAR,
+        }
+    }
+}
+
+impl Projection {
+    pub fn z_near(&self) -> f32 {
+        match *self {
+            Self::Perspective { z_near, .. } | Self::Orthographic { z_near, .. } => z_near,
+        }
+    }
+
+    pub fn z_far(&self) -> f32 {
+        match *self {
+            Self::Perspective { z_far, .. } | Self::Orthographic { z_far, .. } => z_far,
+        }
+    }
+
+    pub fn matrix(&self, aspect_ratio: f32) -> Mat4f {
+        match *self {
+            Self::Perspective {
+                fov_y,
+                z_near,
+                z_far,
+            } => Mat4f::new_perspective(aspect_ratio, fov_y, z_near, z_far),
+            Self::Orthographic {
+                height,
+                z_near,
+                z_far,
+            } => {
+                let (half_w, half_h) = (height * aspect_ratio * 0.5, height * 0.5);
+                Mat4f::new_orthographic(-half_w, half_w, -half_h, half_h, z_near, z_far)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Camera {
     pub position: Vec3f,
     pub direction: Vec3f,
@@ -18,6 +154,11 @@ pub struct Camera {
     pub yaw: f32,
     pub pitch: f32,
     pub roll: f32,
+    /// Orientation used instead of the angles when set, free of gimbal lock near the poles.
+    /// Enabled by `rotate_local` or `set_orientation`, the angles follow it for display and
+    /// controllers, and `up` includes the roll.
+    pub orientation: Option<Quatf>,
+    pub projection: Projection,
 }
 
 impl Default for Camera {
@@ -31,6 +172,8 @@ impl Default for Camera {
             yaw: -FRAC_PI_2,
             pitch: 0.0,
             roll: 0.0,
+            orientation: None,
+            projection: Projection::default(),
         };
         camera.update_direction_from_angles();
         camera
@@ -50,13 +193,88 @@ impl Camera {
         let world_up = Vec3f::new(0.0, 1.0, 0.0);
         let right = world_up.cross(&self.direction).normalize();
         self.up = self.direction.cross(&right).normalize();
+
+        if self.orientation.is_some() {
+            let orientation = quat_from_yaw_pitch_roll(self.yaw, self.pitch, self.roll);
+            self.orientation = Some(orientation);
+            self.up = quat_up(&orientation);
+        }
+    }
+
+    /// Use `orientation` instead of the angles from now on, see `Camera::orientation`
+    pub fn set_orientation(&mut self, orientation: Quatf) {
+        self.orientation = Some(orientation);
+        (self.yaw, self.pitch, self.roll) = quat_to_yaw_pitch_roll(&orientation);
+        self.direction = quat_forward(&orientation);
+        self.up = quat_up(&orientation);
+    }
+
+    /// Apply `rotation` in the camera frame, where `-Z` is forward and `+Y` up, e.g. a rotation
+    /// around `Vec3f::x_axis()` pitches whatever the current orientation. Switches to the
+    /// quaternion orientation if the angles were used.
+    pub fn rotate_local(&mut self, rotation: &Quatf) {
+        let orientation = self
+            .orientation
+            .unwrap_or_else(|| quat_from_yaw_pitch_roll(self.yaw, self.pitch, self.roll));
+        self.set_orientation(orientation * rotation);
+    }
+
+    /// Point the camera at `target`, keeping its position and roll
+    pub fn look_at(&mut self, target: Vec3f) {
+        let Some(direction) = (target - self.position).try_normalize(f32::EPSILON) else {
+            return;
+        };
+        if self.orientation.is_some() {
+            // Straight up or down the pitch would be clamped by the angles, keep the current up
+            let up = if direction.cross(&Vec3f::y()).norm() > 1e-4 {
+                Vec3f::y()
+            } else {
+                self.up
+            };
+            let unrolled = Quatf::look_at_rh(&direction, &up).inverse();
+            let roll = Quatf::from_axis_angle(&Vec3f::z_axis(), -self.roll);
+            self.set_orientation(unrolled * roll);
+            return;
+        }
+        self.yaw = direction.z.atan2(direction.x);
+        self.pitch = direction.y.asin().clamp(-MAX_PITCH, MAX_PITCH);
+        self.update_direction_from_angles();
+    }
+
+    /// Look down an axis at `focus` from `distance` units away
+    pub fn snap_to_view(&mut self, view: StandardView, focus: Vec3f, distance: f32) {
+        (self.yaw, self.pitch) = view.angles();
+        self.roll = 0.0;
+        self.update_direction_from_angles();
+        self.position = focus - self.direction * distance;
+    }
+
+    /// Look down an axis so that the whole `aabb` fits in view, `margin` scales the framing distance,
+    /// or the view height of an orthographic camera
+    pub fn frame_aabb(&mut self, view: StandardView, aabb: &Aabb, margin: f32) {
+        let radius = aabb.half_extents().norm();
+        let distance = match &mut self.projection {
+            Projection::Perspective { fov_y, .. } => radius / (*fov_y * 0.5).sin() * margin,
+            Projection::Orthographic { height, z_near, .. } => {
+                *height = radius * 2.0 * margin;
+                radius * margin + *z_near
+            }
+        };
+        self.snap_to_view(view, aabb.center(), distance);
     }
 
     pub fn get_view_proj_matrices(&self, dims: Vec2u) -> (Mat4f, Mat4f) {
         let aspect_ratio = dims.x as f32 / dims.y as f32;
-        let fov_y = FRAC_PI_2;
-        let z_near = 0.1;
-        let z_far = 100.0;
+        let projection_matrix = self.projection.matrix(aspect_ratio);
+
+        if let Some(orientation) = self.orientation {
+            let camera_to_world =
+                nalgebra::Isometry3::from_parts(self.position.into(), orientation);
+            return (
+                camera_to_world.inverse().to_homogeneous(),
+                projection_matrix,
+            );
+        }
 
         let axis = nalgebra::Unit::new_normalize(self.direction);
         let roll_rotation = nalgebra::Rotation3::from_axis_angle(&axis, self.roll);
@@ -68,38 +286,106 @@ impl Camera {
             &rolled_up,
         );
 
-        let projection_matrix = Mat4f::new_perspective(aspect_ratio, fov_y, z_near, z_far);
         (view_matrix, projection_matrix)
     }
 }
 
+impl Camera {
+    /// World space ray going through a cursor position in pixels, returns (origin, normalized direction).
+    /// Falls back to the view direction when the viewport is empty, e.g. while minimized.
+    pub fn screen_ray(&self, cursor: Vec2f, viewport: Vec2u) -> (Vec3f, Vec3f) {
+        if viewport.x == 0 || viewport.y == 0 {
+            return (self.position, self.direction);
+        }
+        let (view, proj) = self.get_view_proj_matrices(viewport);
+        let Some(inv_view_proj) = (proj * view).try_inverse() else {
+            return (self.position, self.direction);
+        };
+
+        let ndc_x = cursor.x / viewport.x as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - cursor.y / viewport.y as f32 * 2.0;
+        let unproject = |z: f32| {
+            let p = inv_view_proj * Vec4f::new(ndc_x, ndc_y, z, 1.0);
+            p.xyz() / p.w
+        };
+
+        let near = unproject(-1.0);
+        let direction = (unproject(1.0) - near).normalize();
+        match self.projection {
+            Projection::Perspective { .. } => (self.position, direction),
+            // Parallel rays start from the cursor on the near plane
+            Projection::Orthographic { .. } => (near, direction),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
 #[repr(C)]
 pub struct CameraData {
     pub view: [[f32; 4]; 4],
     pub proj: [[f32; 4]; 4],
+    /// Logarithmic depth parameters, see `CAMERA_WGSL`
+    pub depth: [f32; 4],
 }
 
 pub struct CameraUniform {
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub uniform_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
+
+    /// C constant of the logarithmic depth when enabled, lower values favor precision far away
+    pub log_depth: Option<f32>,
+    /// Camera position at the last update
+    pub position: Vec3f,
+    /// Camera projection at the last update
+    pub projection: Projection,
+    matrices: CameraMatrices,
+    _memory: TrackedMemory,
+}
+
+/// View and projection of a camera for a viewport, as cached by `CameraUniform`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraMatrices {
+    pub view: Mat4f,
+    pub proj: Mat4f,
+    pub view_proj: Mat4f,
+}
+
+impl CameraMatrices {
+    pub fn new(camera: &Camera, viewport: Vec2u) -> Self {
+        let (view, proj) = camera.get_view_proj_matrices(viewport);
+        Self {
+            view,
+            proj,
+            view_proj: proj * view,
+        }
+    }
+
+    /// Uniform data, `log_depth` is the C constant of the logarithmic depth when enabled
+    pub fn data(&self, log_depth: Option<f32>, z_far: f32) -> CameraData {
+        CameraData {
+            view: self.view.into(),
+            proj: self.proj.into(),
+            depth: match log_depth {
+                Some(c) => [1.0, z_far, c, 0.0],
+                None => [0.0; 4],
+            },
+        }
+    }
 }
 
 impl CameraUniform {
     pub fn new(ctx: &Graphics) -> Self {
-        let (view_matrix, proj_matrix) =
-            Camera::default().get_view_proj_matrices(ctx.viewport_size);
-        let data = CameraData {
-            view: view_matrix.into(),
-            proj: proj_matrix.into(),
-        };
+        let matrices = CameraMatrices::new(&Camera::default(), ctx.viewport_size);
+        let data = matrices.data(None, Projection::default().z_far());
         let camera_uniform_buffer =
             ctx.device
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("Camera Uniform Buffer"),
                     contents: bytemuck::cast_slice(&[data]),
-                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    usage: wgpu::BufferUsages::UNIFORM
+                        | wgpu::BufferUsages::COPY_DST
+                        | wgpu::BufferUsages::COPY_SRC,
                 });
 
         let camera_bind_group_layout =
@@ -130,22 +416,127 @@ impl CameraUniform {
         });
 
         Self {
+            _memory: ctx.memory.track_buffer(&camera_uniform_buffer),
             bind_group_layout: camera_bind_group_layout,
             uniform_buffer: camera_uniform_buffer,
             bind_group: camera_bind_group,
+            log_depth: None,
+            position: Camera::default().position,
+            projection: Projection::default(),
+            matrices,
         }
     }
 
-    pub fn update(&self, ctx: &Graphics, camera: &Camera) {
-        let (view_matrix, proj_matrix) = camera.get_view_proj_matrices(ctx.viewport_size);
-        let camera_matrices = CameraData {
-            view: view_matrix.into(),
-            proj: proj_matrix.into(),
+    /// View and projection matrices uploaded by the last update
+    pub fn matrices(&self) -> (Mat4f, Mat4f) {
+        (self.matrices.view, self.matrices.proj)
+    }
+
+    /// Projection times view matrix uploaded by the last update
+    pub fn view_proj(&self) -> &Mat4f {
+        &self.matrices.view_proj
+    }
+
+    /// Parameters of `linearize_depth` in `CAMERA_WGSL` matching the last update: near plane,
+    /// far plane, 1 when orthographic, and the logarithmic depth C constant or 0 when disabled
+    pub fn depth_planes(&self) -> [f32; 4] {
+        let orthographic = matches!(self.projection, Projection::Orthographic { .. });
+        [
+            self.projection.z_near(),
+            self.projection.z_far(),
+            if orthographic { 1.0 } else { 0.0 },
+            self.log_depth.unwrap_or(0.0),
+        ]
+    }
+
+    pub fn update(&mut self, ctx: &Graphics, camera: &Camera) {
+        self.position = camera.position;
+        self.projection = camera.projection;
+        self.matrices = CameraMatrices::new(camera, ctx.viewport_size);
+        let data = self
+            .matrices
+            .data(self.log_depth, camera.projection.z_far());
+        ctx.queue
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[data]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_through_screen_center_follows_the_view_direction() {
+        let mut camera = Camera {
+            position: Vec3f::new(1.0, 2.0, 3.0),
+            yaw: 0.7,
+            pitch: -0.3,
+            ..Default::default()
         };
-        ctx.queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[camera_matrices]),
+        camera.update_direction_from_angles();
+        let viewport = Vec2u::new(800, 600);
+
+        let (origin, direction) = camera.screen_ray(Vec2f::new(400.0, 300.0), viewport);
+
+        assert_eq!(origin, camera.position);
+        assert!((direction - camera.direction).norm() < 1e-4);
+        assert!((direction.norm() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_through_top_left_corner_points_up_and_left() {
+        let camera = Camera::default();
+        let (_, direction) = camera.screen_ray(Vec2f::zeros(), Vec2u::new(800, 600));
+        let right = camera.direction.cross(&camera.up);
+        assert!(direction.dot(&right) < 0.0);
+        assert!(direction.dot(&camera.up) > 0.0);
+    }
+
+    #[test]
+    fn orthographic_size_does_not_depend_on_distance() {
+        let ndc_length = |projection: &Projection, depth: f32| {
+            let matrix = projection.matrix(4.0 / 3.0);
+            let project = |x: f32| {
+                let clip = matrix * Vec4f::new(x, 0.0, -depth, 1.0);
+                clip.x / clip.w
+            };
+            project(0.5) - project(-0.5)
+        };
+        let orthographic = Projection::Orthographic {
+            height: 10.0,
+            z_near: 0.1,
+            z_far: 100.0,
+        };
+        let (near, far) = (
+            ndc_length(&orthographic, 2.0),
+            ndc_length(&orthographic, 50.0),
         );
+        assert!((near - far).abs() < 1e-6);
+        assert!((near - 1.0 / (10.0 * 4.0 / 3.0) * 2.0).abs() < 1e-6);
+
+        // Unlike perspective, where the far segment shrinks
+        let perspective = Projection::default();
+        assert!(ndc_length(&perspective, 50.0) < ndc_length(&perspective, 2.0));
+    }
+
+    #[test]
+    fn cached_matrices_match_a_fresh_computation() {
+        let mut camera = Camera {
+            position: Vec3f::new(2.0, 1.0, 4.0),
+            ..Default::default()
+        };
+        camera.look_at(Vec3f::new(0.0, 0.5, 0.0));
+        let viewport = Vec2u::new(1280, 720);
+
+        let matrices = CameraMatrices::new(&camera, viewport);
+        let (view, proj) = camera.get_view_proj_matrices(viewport);
+        assert_eq!((matrices.view, matrices.proj), (view, proj));
+        assert_eq!(matrices.view_proj, proj * view);
+
+        let data = matrices.data(Some(1.0), 100.0);
+        assert_eq!(data.view, <[[f32; 4]; 4]>::from(view));
+        assert_eq!(data.proj, <[[f32; 4]; 4]>::from(proj));
+        assert_eq!(data.depth, [1.0, 100.0, 1.0, 0.0]);
+        assert_eq!(matrices.data(None, 100.0).depth, [0.0; 4]);
     }
 }
@@ -1,5 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+/// RGB color, used through the `Color3f` alias across the engine
 #[repr(C)]
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Color3<T> {
     pub r: T,
     pub g: T,
@@ -8,26 +11,26 @@ pub struct Color3<T> {
 
 pub type Color3f = Color3<f32>;
 
-impl Into<wgpu::Color> for Color3f {
-    fn into(self) -> wgpu::Color {
+impl From<Color3f> for wgpu::Color {
+    fn from(val: Color3f) -> Self {
         wgpu::Color {
-            r: self.r as f64,
-            g: self.g as f64,
-            b: self.b as f64,
+            r: val.r as f64,
+            g: val.g as f64,
+            b: val.b as f64,
             a: 1.0,
         }
     }
 }
 
-impl Into<[f32; 3]> for Color3f {
-    fn into(self) -> [f32; 3] {
-        [self.r, self.g, self.b]
+impl From<Color3f> for [f32; 3] {
+    fn from(val: Color3f) -> Self {
+        [val.r, val.g, val.b]
     }
 }
 
-impl Into<[f32; 4]> for Color3f {
-    fn into(self) -> [f32; 4] {
-        [self.r, self.g, self.b, 1.]
+impl From<Color3f> for [f32; 4] {
+    fn from(val: Color3f) -> Self {
+        [val.r, val.g, val.b, 1.]
     }
 }
 
@@ -49,16 +52,74 @@ impl Color3f {
         Self::new(l, l, l)
     }
 
+    pub const fn with_alpha(self, a: f32) -> Color4f {
+        Color4::new(self.r, self.g, self.b, a)
+    }
+
+    /// Hue in degrees (wraps around), saturation and value in [0, 1]
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let s = s.clamp(0.0, 1.0);
+        let v = v.clamp(0.0, 1.0);
+        let h = h.rem_euclid(360.0) / 60.0;
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        Self::new(r + m, g + m, b + m)
+    }
+
+    /// Returns (hue in degrees [0, 360), saturation, value). Grays have a hue and saturation of 0
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let delta = max - min;
+        if max <= 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+        if delta <= 0.0 {
+            return (0.0, 0.0, max);
+        }
+        let h = if max == self.r {
+            ((self.g - self.b) / delta).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / delta + 2.0
+        } else {
+            (self.r - self.g) / delta + 4.0
+        };
+        ((h * 60.0) % 360.0, delta / max, max)
+    }
+
+    /// Linear interpolation towards `other`, `t` is clamped to [0, 1]
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        self + (other - self) * t
+    }
+
     pub fn array_mut(&mut self) -> &mut [f32; 3] {
         // SAFETY: The struct is #[repr(C)] and is memory equivalent to [f32 ; 3]
         unsafe { std::mem::transmute(self) }
     }
 
+    /// Opaque 8 bit sRGB encoded channels, as uploaded by `ModelTexture::from_color`.
+    /// Like every color of the engine the components are linear, they are encoded here so that
+    /// sampling the sRGB texture gives them back.
+    ///
+    /// ```
+    /// use your_game_name::engine::graphics::color::{Color3, Color3f};
+    ///
+    /// let orange: Color3<f32> = Color3f::new(1.0, 0.5, 0.0);
+    /// assert_eq!(orange.to_srgba_unorm(), [255, 188, 0, 255]);
+    /// ```
     pub fn to_srgba_unorm(&self) -> [u8; 4] {
-        let r = (self.r * 255.0).clamp(0.0, 255.0) as u8;
-        let g = (self.g * 255.0).clamp(0.0, 255.0) as u8;
-        let b = (self.b * 255.0).clamp(0.0, 255.0) as u8;
-        [r, g, b, 255]
+        let [r, g, b] = [self.r, self.g, self.b].map(linear_to_srgb);
+        [r, g, b, 1.0].map(unorm8)
     }
 }
 
@@ -89,3 +150,207 @@ impl std::ops::Sub for Color3f {
         Color3f::new(self.r - rhs.r, self.g - rhs.g, self.b - rhs.b)
     }
 }
+
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Color4<T> {
+    pub r: T,
+    pub g: T,
+    pub b: T,
+    pub a: T,
+}
+
+pub type Color4f = Color4<f32>;
+
+impl From<Color4f> for wgpu::Color {
+    fn from(val: Color4f) -> Self {
+        wgpu::Color {
+            r: val.r as f64,
+            g: val.g as f64,
+            b: val.b as f64,
+            a: val.a as f64,
+        }
+    }
+}
+
+impl From<Color4f> for [f32; 4] {
+    fn from(val: Color4f) -> Self {
+        [val.r, val.g, val.b, val.a]
+    }
+}
+
+impl From<[f32; 4]> for Color4f {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Self::new(r, g, b, a)
+    }
+}
+
+/// Opaque
+impl From<Color3f> for Color4f {
+    fn from(val: Color3f) -> Self {
+        val.with_alpha(1.0)
+    }
+}
+
+impl Color4f {
+    pub const WHITE: Self = Color3f::WHITE.with_alpha(1.0);
+    pub const BLACK: Self = Color3f::BLACK.with_alpha(1.0);
+    pub const TRANSPARENT: Self = Color3f::BLACK.with_alpha(0.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Drops the alpha
+    pub const fn rgb(&self) -> Color3f {
+        Color3::new(self.r, self.g, self.b)
+    }
+
+    pub fn array_mut(&mut self) -> &mut [f32; 4] {
+        // SAFETY: The struct is #[repr(C)] and is memory equivalent to [f32 ; 4]
+        unsafe { std::mem::transmute(self) }
+    }
+
+    /// 8 bit sRGB encoded channels with the alpha kept linear, see `Color3f::to_srgba_unorm`
+    ///
+    /// ```
+    /// use your_game_name::engine::graphics::color::Color4f;
+    ///
+    /// let color = Color4f::new(1.0, 0.5, 0.0, 0.25);
+    /// assert_eq!(color.to_srgba_unorm(), [255, 188, 0, 64]);
+    /// ```
+    pub fn to_srgba_unorm(&self) -> [u8; 4] {
+        let [r, g, b] = [self.r, self.g, self.b].map(linear_to_srgb);
+        [r, g, b, self.a].map(unorm8)
+    }
+}
+
+/// sRGB transfer function, from a linear channel in [0, 1]
+fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Nearest 8 bit value of a channel in [0, 1]
+fn unorm8(channel: f32) -> u8 {
+    (channel * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+impl std::ops::Mul<f32> for Color4f {
+    type Output = Color4f;
+    fn mul(self, rhs: f32) -> Self::Output {
+        Color4::new(self.r * rhs, self.g * rhs, self.b * rhs, self.a * rhs)
+    }
+}
+
+impl std::ops::Mul<Color4f> for f32 {
+    type Output = Color4f;
+    fn mul(self, rhs: Color4f) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl std::ops::Add for Color4f {
+    type Output = Color4f;
+    fn add(self, rhs: Color4f) -> Self::Output {
+        Color4::new(
+            self.r + rhs.r,
+            self.g + rhs.g,
+            self.b + rhs.b,
+            self.a + rhs.a,
+        )
+    }
+}
+
+impl std::ops::Sub for Color4f {
+    type Output = Color4f;
+    fn sub(self, rhs: Color4f) -> Self::Output {
+        Color4::new(
+            self.r - rhs.r,
+            self.g - rhs.g,
+            self.b - rhs.b,
+            self.a - rhs.a,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color4_conversions_round_trip() {
+        let color = Color4f::new(0.1, 0.2, 0.3, 0.4);
+        let array: [f32; 4] = color.into();
+        assert_eq!(array, [0.1, 0.2, 0.3, 0.4]);
+        assert_eq!(Color4f::from(array), color);
+
+        let opaque = Color4f::from(Color3f::CYAN);
+        assert_eq!(opaque.a, 1.0);
+        assert_eq!(opaque.rgb(), Color3f::CYAN);
+        assert_eq!(Color3f::CYAN.with_alpha(0.5).rgb(), Color3f::CYAN);
+
+        let wgpu = wgpu::Color::from(color);
+        assert_eq!(
+            [wgpu.r, wgpu.g, wgpu.b, wgpu.a].map(|c| c as f32),
+            [0.1, 0.2, 0.3, 0.4]
+        );
+    }
+
+    #[test]
+    fn red_round_trips_through_hsv() {
+        assert_eq!(Color3f::from_hsv(0.0, 1.0, 1.0), Color3f::RED);
+        assert_eq!(Color3f::RED.to_hsv(), (0.0, 1.0, 1.0));
+        // Hue wraps around
+        assert_eq!(Color3f::from_hsv(360.0, 1.0, 1.0), Color3f::RED);
+        assert_eq!(Color3f::from_hsv(-240.0, 1.0, 1.0), Color3f::GREEN);
+    }
+
+    #[test]
+    fn grays_have_no_hue() {
+        assert_eq!(Color3f::from_hsv(120.0, 0.0, 0.5), Color3f::splat(0.5));
+        assert_eq!(Color3f::splat(0.5).to_hsv(), (0.0, 0.0, 0.5));
+        assert_eq!(Color3f::from_hsv(120.0, 1.0, 0.0), Color3f::BLACK);
+        assert_eq!(Color3f::BLACK.to_hsv(), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn lerp_halfway_from_black_to_white_is_mid_gray() {
+        assert_eq!(
+            Color3f::BLACK.lerp(Color3f::WHITE, 0.5),
+            Color3f::splat(0.5)
+        );
+        assert_eq!(Color3f::BLACK.lerp(Color3f::WHITE, 2.0), Color3f::WHITE);
+        assert_eq!(Color3f::BLACK.lerp(Color3f::WHITE, -1.0), Color3f::BLACK);
+    }
+
+    #[test]
+    fn srgb_encoding_keeps_black_white_and_alpha() {
+        assert_eq!(Color3f::BLACK.to_srgba_unorm(), [0, 0, 0, 255]);
+        assert_eq!(Color3f::WHITE.to_srgba_unorm(), [255; 4]);
+        // Linear mid gray is brighter once encoded
+        assert_eq!(Color3f::splat(0.5).to_srgba_unorm(), [188, 188, 188, 255]);
+        assert_eq!(
+            Color4f::new(0.0, 0.0, 0.0, 0.5).to_srgba_unorm(),
+            [0, 0, 0, 128]
+        );
+    }
+
+    #[test]
+    fn array_mut_round_trips_the_channels() {
+        let mut color = Color3f::new(0.1, 0.2, 0.3);
+        assert_eq!(*color.array_mut(), [0.1, 0.2, 0.3]);
+        // What the editor color picker does
+        color.array_mut()[1] = 0.9;
+        *color.array_mut() = color.array_mut().map(|c| c * 0.5);
+        assert_eq!(color, Color3f::new(0.05, 0.45, 0.15));
+        assert_eq!(<[f32; 3]>::from(color), *color.array_mut());
+
+        let mut color = Color4f::new(0.1, 0.2, 0.3, 0.4);
+        color.array_mut()[3] = 1.0;
+        assert_eq!(color, Color4f::new(0.1, 0.2, 0.3, 1.0));
+    }
+}
@@ -0,0 +1,195 @@
+use wgpu::{BindGroup, BindGroupLayout, CommandEncoder};
+
+use crate::engine::graphics::Graphics;
+
+/// How a compute shader accesses the buffer bound at the same index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferAccess {
+    /// `var<storage, read>`
+    ReadOnly,
+    /// `var<storage, read_write>`
+    ReadWrite,
+    /// `var<uniform>`
+    Uniform,
+}
+
+/// A compute shader entry point with its buffers at `@group(0)`, binding `i` for the buffer `i`.
+///
+/// Buffers written here can be read by later passes of the same encoder, e.g. a storage buffer
+/// created with `BufferUsages::VERTEX` and drawn as instances after a simulation step.
+pub struct ComputeKernel {
+    label: String,
+    pipeline: wgpu::ComputePipeline,
+    pub bind_group_layout: BindGroupLayout,
+    /// Invocations per workgroup along x, must match `@workgroup_size` in the shader
+    pub workgroup_size: u32,
+}
+
+impl ComputeKernel {
+    pub fn new(
+        ctx: &Graphics,
+        label: &str,
+        source: &str,
+        entry_point: &str,
+        buffers: &[BufferAccess],
+        workgroup_size: u32,
+    ) -> Self {
+        let entries: Vec<wgpu::BindGroupLayoutEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(i, access)| wgpu::BindGroupLayoutEntry {
+                binding: i as u32,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: match access {
+                        BufferAccess::ReadOnly => {
+                            wgpu::BufferBindingType::Storage { read_only: true }
+                        }
+                        BufferAccess::ReadWrite => {
+                            wgpu::BufferBindingType::Storage { read_only: false }
+                        }
+                        BufferAccess::Uniform => wgpu::BufferBindingType::Uniform,
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect();
+        let bind_group_layout =
+            ctx.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(label),
+                    entries: &entries,
+                });
+        let module = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        Self {
+            label: label.to_owned(),
+            pipeline,
+            bind_group_layout,
+            workgroup_size: workgroup_size.max(1),
+        }
+    }
+
+    /// Bind the buffers in the order of the accesses given to `new`
+    pub fn bind_group(&self, ctx: &Graphics, buffers: &[&wgpu::Buffer]) -> BindGroup {
+        ctx.create_buffer_bind_group(&self.bind_group_layout, buffers)
+    }
+
+    /// Record a dispatch of enough workgroups for `invocations` invocations along x.
+    /// The shader should skip the extra invocations of the last workgroup.
+    pub fn dispatch(&self, encoder: &mut CommandEncoder, bind_group: &BindGroup, invocations: u32) {
+        self.dispatch_workgroups(
+            encoder,
+            bind_group,
+            [invocations.div_ceil(self.workgroup_size), 1, 1],
+        );
+    }
+
+    pub fn dispatch_workgroups(
+        &self,
+        encoder: &mut CommandEncoder,
+        bind_group: &BindGroup,
+        [x, y, z]: [u32; 3],
+    ) {
+        if x == 0 || y == 0 || z == 0 {
+            return;
+        }
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(&self.label),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(x, y, z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOUBLE_WGSL: &str = r#"
+@group(0) @binding(0)
+var<storage, read> input: array<f32>;
+@group(0) @binding(1)
+var<storage, read_write> output: array<f32>;
+
+@compute @workgroup_size(64)
+fn double(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= arrayLength(&input) {
+        return;
+    }
+    output[id.x] = input[id.x] * 2.0;
+}
+"#;
+
+    #[test]
+    fn doubled_values_are_read_back() {
+        let Some(ctx) = Graphics::headless(8, 8) else {
+            return;
+        };
+        if !ctx
+            .capabilities
+            .downlevel
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return;
+        }
+        let kernel = ComputeKernel::new(
+            &ctx,
+            "double",
+            DOUBLE_WGSL,
+            "double",
+            &[BufferAccess::ReadOnly, BufferAccess::ReadWrite],
+            64,
+        );
+        // Not a multiple of the workgroup size, the last invocations are skipped
+        let input: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let input_buffer = ctx.create_storage_buffer(
+            "double_input",
+            bytemuck::cast_slice(&input),
+            wgpu::BufferUsages::empty(),
+        );
+        let output_buffer = ctx.create_storage_buffer(
+            "double_output",
+            &vec![0; size_of_val(input.as_slice())],
+            wgpu::BufferUsages::empty(),
+        );
+
+        let bind_group = kernel.bind_group(&ctx, &[&input_buffer, &output_buffer]);
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        kernel.dispatch(&mut encoder, &bind_group, input.len() as u32);
+        ctx.queue.submit(Some(encoder.finish()));
+
+        let output = ctx.read_buffer(&output_buffer);
+        let expected: Vec<f32> = input.iter().map(|value| value * 2.0).collect();
+        assert_eq!(bytemuck::cast_slice::<_, f32>(&output), expected);
+    }
+}
@@ -0,0 +1,136 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// Frame times of the last frames, for a rolling average and the min/max spikes.
+/// Until the window is full, the statistics only cover the recorded frames.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    frame_times: VecDeque<Duration>,
+    capacity: usize,
+    total: Duration,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl FrameStats {
+    pub const DEFAULT_CAPACITY: usize = 120;
+
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            frame_times: VecDeque::with_capacity(capacity),
+            capacity,
+            total: Duration::ZERO,
+        }
+    }
+
+    pub fn push(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == self.capacity
+            && let Some(oldest) = self.frame_times.pop_front()
+        {
+            self.total -= oldest;
+        }
+        self.frame_times.push_back(frame_time);
+        self.total += frame_time;
+    }
+
+    pub fn clear(&mut self) {
+        self.frame_times.clear();
+        self.total = Duration::ZERO;
+    }
+
+    /// Number of recorded frames, at most `capacity`
+    pub fn len(&self) -> usize {
+        self.frame_times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frame_times.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Zero when no frame was recorded
+    pub fn average(&self) -> Duration {
+        match self.len() {
+            0 => Duration::ZERO,
+            n => self.total / n as u32,
+        }
+    }
+
+    pub fn min(&self) -> Duration {
+        self.frame_times.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.frame_times.iter().max().copied().unwrap_or_default()
+    }
+
+    /// Frames per second from the average frame time, zero when no frame was recorded
+    pub fn fps(&self) -> f32 {
+        let average = self.average().as_secs_f32();
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+
+    /// Recorded frame times, oldest first
+    pub fn frame_times(&self) -> impl ExactSizeIterator<Item = Duration> + '_ {
+        self.frame_times.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn average_covers_recorded_frames_until_the_window_is_full() {
+        let mut stats = FrameStats::new(4);
+        assert_eq!(stats.average(), Duration::ZERO);
+        assert_eq!(stats.fps(), 0.0);
+
+        stats.push(ms(10));
+        stats.push(ms(20));
+        assert_eq!(stats.average(), ms(15));
+        assert_eq!((stats.min(), stats.max()), (ms(10), ms(20)));
+    }
+
+    #[test]
+    fn average_rolls_over_the_last_frames() {
+        let mut stats = FrameStats::new(4);
+        for frame_time in [40, 10, 10, 10, 10] {
+            stats.push(ms(frame_time));
+        }
+        // The 40ms spike left the window
+        assert_eq!(stats.average(), ms(10));
+        assert_eq!(stats.max(), ms(10));
+        assert!((stats.fps() - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn history_keeps_the_newest_frames_in_order() {
+        let mut stats = FrameStats::new(3);
+        for frame_time in 1..=5 {
+            stats.push(ms(frame_time));
+        }
+        assert_eq!(stats.len(), stats.capacity());
+        assert_eq!(
+            stats.frame_times().collect::<Vec<_>>(),
+            [ms(3), ms(4), ms(5)]
+        );
+
+        stats.clear();
+        assert!(stats.is_empty());
+        assert_eq!(stats.frame_times().len(), 0);
+        stats.push(ms(7));
+        assert_eq!(stats.average(), ms(7));
+    }
+}
@@ -0,0 +1,271 @@
+use wgpu::RenderPass;
+
+use crate::engine::{
+    graphics::{
+        Frame, Graphics,
+        camera::{CAMERA_WGSL, CameraUniform},
+        color::Color4f,
+        memory::TrackedMemory,
+        model::renderer::BlendMode,
+    },
+    maths::{Aabb, Vec3f},
+};
+
+const GIZMO_WGSL: &str = r#"
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct GizmoOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_gizmo(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> GizmoOutput {
+    var out: GizmoOutput;
+    out.clip_position = log_depth(camera.proj * camera.view * vec4<f32>(position, 1.0), camera.depth);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_gizmo(in: GizmoOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GizmoVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl GizmoVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GizmoVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Immediate mode debug lines in world space, see `ModelRenderer::gizmos`.
+///
+/// Shapes are queued during the frame and drawn by the next `ModelRenderer::render` in a
+/// single draw, then cleared. Depth tested against the scene without writing depth.
+#[derive(Debug, Default, Clone)]
+pub struct Gizmos {
+    vertices: Vec<GizmoVertex>,
+}
+
+impl Gizmos {
+    pub fn line(&mut self, a: Vec3f, b: Vec3f, color: impl Into<Color4f>) {
+        let color = color.into().into();
+        self.vertices.extend([a, b].map(|p| GizmoVertex {
+            position: p.into(),
+            color,
+        }));
+    }
+
+    /// Segment from `origin` to `origin + direction`
+    pub fn ray(&mut self, origin: Vec3f, direction: Vec3f, color: impl Into<Color4f>) {
+        self.line(origin, origin + direction, color);
+    }
+
+    /// The 12 edges of the box
+    pub fn aabb(&mut self, aabb: &Aabb, color: impl Into<Color4f>) {
+        let color = color.into();
+        let corner = |i: usize| {
+            Vec3f::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            )
+        };
+        // Corners whose index differ by a single bit share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    /// Queued vertices, two per line
+    pub fn vertices(&self) -> &[GizmoVertex] {
+        &self.vertices
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+/// Line list pipeline and vertex buffer drawing the queued `Gizmos`
+pub struct GizmoRenderer {
+    pipeline: wgpu::RenderPipeline,
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    buffer: Option<(wgpu::Buffer, TrackedMemory)>,
+    vertex_count: u32,
+}
+
+impl GizmoRenderer {
+    pub fn new(ctx: &Graphics, camera_uniform: &CameraUniform) -> Self {
+        let shader_module = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("gizmos"),
+                source: wgpu::ShaderSource::Wgsl(format!("{CAMERA_WGSL}{GIZMO_WGSL}").into()),
+            });
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("gizmos"),
+                bind_group_layouts: &[&camera_uniform.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        Self {
+            pipeline: create_pipeline(ctx, &shader_module, &pipeline_layout),
+            shader_module,
+            pipeline_layout,
+            buffer: None,
+            vertex_count: 0,
+        }
+    }
+
+    pub fn on_sample_count_changed(&mut self, ctx: &Graphics) {
+        self.pipeline = create_pipeline(ctx, &self.shader_module, &self.pipeline_layout);
+    }
+
+    /// Upload the queued lines and clear them, before the render pass begins
+    pub fn prepare(&mut self, ctx: &Graphics, frame: &mut Frame, gizmos: &mut Gizmos) {
+        self.vertex_count = gizmos.vertices.len() as u32;
+        if gizmos.is_empty() {
+            return;
+        }
+        let data: &[u8] = bytemuck::cast_slice(&gizmos.vertices);
+        let too_small = self
+            .buffer
+            .as_ref()
+            .is_none_or(|(buffer, _)| buffer.size() < data.len() as u64);
+        if too_small {
+            let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Gizmo Vertex Buffer"),
+                // Grown by powers of two to avoid reallocating every frame
+                size: (data.len() as u64).next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let memory = ctx.memory.track_buffer(&buffer);
+            self.buffer = Some((buffer, memory));
+        }
+        if let Some((buffer, _)) = &self.buffer {
+            frame.write_buffer(ctx, buffer, 0, data);
+        }
+        gizmos.clear();
+    }
+
+    pub fn render(&self, render_pass: &mut RenderPass, camera_uniform: &CameraUniform) {
+        let Some((buffer, _)) = &self.buffer else {
+            return;
+        };
+        if self.vertex_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &camera_uniform.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+fn create_pipeline(
+    ctx: &Graphics,
+    module: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+) -> wgpu::RenderPipeline {
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("gizmos"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: Some("vs_gizmo"),
+                buffers: &[GizmoVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: Some("fs_gizmo"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: Some(BlendMode::Alpha.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: ctx.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::graphics::color::Color3f;
+
+    #[test]
+    fn lines_queue_two_vertices_each() {
+        let mut gizmos = Gizmos::default();
+        for i in 0..5 {
+            gizmos.line(Vec3f::zeros(), Vec3f::repeat(i as f32), Color3f::RED);
+        }
+        assert_eq!(gizmos.vertices().len(), 10);
+        assert_eq!(gizmos.vertices()[3].position, [1.0; 3]);
+        assert_eq!(gizmos.vertices()[3].color, [1.0, 0.0, 0.0, 1.0]);
+
+        gizmos.clear();
+        assert!(gizmos.is_empty());
+    }
+
+    #[test]
+    fn aabb_queues_its_twelve_edges() {
+        let mut gizmos = Gizmos::default();
+        let aabb = Aabb::new(Vec3f::repeat(-1.0), Vec3f::repeat(1.0));
+        gizmos.aabb(&aabb, Color3f::GREEN);
+        let vertices = gizmos.vertices();
+        assert_eq!(vertices.len(), 24);
+        // Every edge is axis aligned with the length of the box
+        for edge in vertices.chunks(2) {
+            let delta = Vec3f::from(edge[1].position) - Vec3f::from(edge[0].position);
+            assert_eq!(delta.iter().filter(|&&d| d != 0.0).count(), 1);
+            assert_eq!(delta.norm(), 2.0);
+        }
+    }
+}
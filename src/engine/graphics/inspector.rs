@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use crate::engine::graphics::Graphics;
+
+/// Layout of a value in an inspected uniform buffer, fields are read in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformField {
+    F32,
+    Vec4,
+    Mat4,
+}
+
+impl UniformField {
+    fn len(&self) -> usize {
+        match self {
+            Self::F32 => 1,
+            Self::Vec4 => 4,
+            Self::Mat4 => 16,
+        }
+    }
+}
+
+struct InspectedUniform {
+    name: String,
+    buffer: wgpu::Buffer,
+    fields: Vec<(&'static str, UniformField)>,
+    values: Vec<f32>,
+}
+
+/// Registry of uniform buffers whose content is read back for display in the editor.
+///
+/// Readbacks stall the GPU, they are throttled to once per `interval`.
+pub struct UniformInspector {
+    pub interval: Duration,
+    uniforms: Vec<InspectedUniform>,
+    last_readback: Option<Instant>,
+}
+
+impl Default for UniformInspector {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(500),
+            uniforms: vec![],
+            last_readback: None,
+        }
+    }
+}
+
+impl UniformInspector {
+    /// The buffer must have the `COPY_SRC` usage
+    pub fn register(
+        &mut self,
+        name: &str,
+        buffer: &wgpu::Buffer,
+        fields: Vec<(&'static str, UniformField)>,
+    ) {
+        self.uniforms.push(InspectedUniform {
+            name: name.to_owned(),
+            buffer: buffer.clone(),
+            fields,
+            values: vec![],
+        });
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.uniforms.retain(|u| u.name != name);
+    }
+
+    /// Read back the uniforms if the throttling interval elapsed
+    pub fn refresh(&mut self, ctx: &Graphics) {
+        if self
+            .last_readback
+            .is_some_and(|t| t.elapsed() < self.interval)
+        {
+            return;
+        }
+        self.last_readback = Some(Instant::now());
+
+        for uniform in &mut self.uniforms {
+            let bytes = ctx.read_buffer(&uniform.buffer);
+            uniform.values = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+        }
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        for uniform in &self.uniforms {
+            egui::CollapsingHeader::new(&uniform.name)
+                .default_open(true)
+                .show(ui, |ui| {
+                    let mut offset = 0;
+                    for (name, field) in &uniform.fields {
+                        let Some(values) = uniform.values.get(offset..offset + field.len()) else {
+                            ui.label(format!("{name}: <not read>"));
+                            continue;
+                        };
+                        offset += field.len();
+
+                        match field {
+                            UniformField::F32 | UniformField::Vec4 => {
+                                ui.label(format!("{name}: {}", format_row(values)));
+                            }
+                            UniformField::Mat4 => {
+                                ui.label(format!("{name}:"));
+                                // Matrices are stored column major, display them row by row
+                                for row in 0..4 {
+                                    let row: Vec<f32> =
+                                        (0..4).map(|c| values[c * 4 + row]).collect();
+                                    ui.monospace(format_row(&row));
+                                }
+                            }
+                        }
+                    }
+                });
+        }
+    }
+}
+
+fn format_row(values: &[f32]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{v:>9.3}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
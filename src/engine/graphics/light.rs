@@ -0,0 +1,124 @@
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+
+use crate::engine::{
+    graphics::{Graphics, color::Color3f, memory::TrackedMemory},
+    maths::Vec3f,
+};
+
+/// Light uniform declaration, prepend it to your shader source
+pub const LIGHT_WGSL: &str = r#"
+struct LightUniform {
+    // xyz: direction the light travels in, w: ambient factor
+    direction: vec4<f32>,
+    color: vec4<f32>,
+};
+
+// Lambert diffuse plus ambient term for a world space normal
+fn lambert(light: LightUniform, normal: vec3<f32>) -> vec3<f32> {
+    let n_dot_l = max(dot(normalize(normal), -light.direction.xyz), 0.0);
+    return light.color.rgb * (n_dot_l + light.direction.w);
+}
+"#;
+
+/// Directional light, such as the sun
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Light {
+    /// Direction the light travels in
+    pub direction: Vec3f,
+    pub color: Color3f,
+    /// Fraction of the color applied to surfaces facing away from the light
+    pub ambient: f32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            direction: Vec3f::new(-0.4, -1.0, -0.3).normalize(),
+            color: Color3f::new(1.0, 0.96, 0.9),
+            ambient: 0.15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct LightData {
+    pub direction: [f32; 4],
+    pub color: [f32; 4],
+}
+
+impl From<&Light> for LightData {
+    fn from(light: &Light) -> Self {
+        let direction = light
+            .direction
+            .try_normalize(f32::EPSILON)
+            .unwrap_or(-Vec3f::y());
+        Self {
+            direction: direction.push(light.ambient).into(),
+            color: light.color.into(),
+        }
+    }
+}
+
+pub struct LightUniform {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    _memory: TrackedMemory,
+}
+
+impl LightUniform {
+    pub fn new(ctx: &Graphics, light: &Light) -> Self {
+        let uniform_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Light Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[LightData::from(light)]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+            });
+
+        let bind_group_layout =
+            ctx.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                    label: Some("Light Bind Group Layout"),
+                });
+
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("Light Bind Group"),
+        });
+
+        Self {
+            _memory: ctx.memory.track_buffer(&uniform_buffer),
+            bind_group_layout,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, ctx: &Graphics, light: &Light) {
+        ctx.queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[LightData::from(light)]),
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use wgpu::RenderPass;
+
+use crate::engine::{
+    graphics::{
+        Graphics,
+        camera::{CAMERA_WGSL, CameraUniform},
+        color::Color4f,
+        model::{
+            Model, Vertex,
+            instance::{INSTANCE_WGSL, InstanceData},
+            renderer::{BlendMode, MaterialRenderer, ModelsIter},
+        },
+    },
+    maths::Mat4f,
+};
+
+const LINE_WGSL: &str = r#"
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct LineOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_line(@location(0) position: vec3<f32>, instance: InstanceInput) -> LineOutput {
+    let world_position = instance_matrix(instance) * vec4<f32>(position, 1.0);
+    var out: LineOutput;
+    out.clip_position = log_depth(camera.proj * camera.view * world_position, camera.depth);
+    out.color = instance.color;
+    return out;
+}
+
+@fragment
+fn fs_line(in: LineOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+/// Unlit material drawing the models as `LineList`, every pair of indices is a segment.
+/// The color is the instance color, alpha blended and depth tested without writing depth.
+pub struct LineMaterial {
+    pipeline: wgpu::RenderPipeline,
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+}
+
+impl LineMaterial {
+    pub fn new(ctx: &Graphics, camera_uniform: &CameraUniform) -> Self {
+        let shader_module = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("line"),
+                source: wgpu::ShaderSource::Wgsl(
+                    format!("{CAMERA_WGSL}{INSTANCE_WGSL}{LINE_WGSL}").into(),
+                ),
+            });
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("line"),
+                bind_group_layouts: &[&camera_uniform.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = create_pipeline(ctx, &shader_module, &pipeline_layout);
+        Self {
+            pipeline,
+            shader_module,
+            pipeline_layout,
+        }
+    }
+}
+
+impl MaterialRenderer for LineMaterial {
+    fn name(&self) -> &str {
+        "Lines"
+    }
+
+    fn is_transparent(&self) -> bool {
+        true
+    }
+
+    fn on_sample_count_changed(&mut self, ctx: &Graphics) {
+        self.pipeline = create_pipeline(ctx, &self.shader_module, &self.pipeline_layout);
+    }
+
+    fn render(
+        &mut self,
+        _ctx: &Graphics,
+        render_pass: &mut RenderPass,
+        camera_uniform: &CameraUniform,
+        models: ModelsIter,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &camera_uniform.bind_group, &[]);
+        for model in models {
+            model.draw(render_pass);
+        }
+    }
+}
+
+fn create_pipeline(
+    ctx: &Graphics,
+    module: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+) -> wgpu::RenderPipeline {
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("line"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: Some("vs_line"),
+                buffers: &[Vertex::desc(), InstanceData::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: Some("fs_line"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: Some(BlendMode::Alpha.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: ctx.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        })
+}
+
+/// Reference grid on the XZ plane, see `Renderer::set_grid_visible`
+pub struct Grid {
+    material: LineMaterial,
+    model: Model,
+}
+
+impl Grid {
+    pub const DEFAULT_HALF_EXTENT: f32 = 20.0;
+    pub const DEFAULT_CELL_SIZE: f32 = 1.0;
+
+    pub fn new(
+        ctx: &Graphics,
+        camera_uniform: &CameraUniform,
+        half_extent: f32,
+        cell_size: f32,
+        color: Color4f,
+    ) -> Self {
+        let mut model = Model::grid(ctx, half_extent, cell_size);
+        model.set_instances(ctx, &[InstanceData::new(Mat4f::identity(), color.into())]);
+        Self {
+            material: LineMaterial::new(ctx, camera_uniform),
+            model,
+        }
+    }
+
+    pub fn on_sample_count_changed(&mut self, ctx: &Graphics) {
+        self.material.on_sample_count_changed(ctx);
+    }
+
+    pub fn render(
+        &mut self,
+        ctx: &Graphics,
+        render_pass: &mut RenderPass,
+        camera_uniform: &CameraUniform,
+    ) {
+        self.material.render(
+            ctx,
+            render_pass,
+            camera_uniform,
+            vec![&self.model].into_iter(),
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+/// Estimated GPU memory of the tracked buffers and textures, from their sizes at creation.
+/// Not what the driver allocates, but enough to spot leaks and growth.
+#[derive(Debug, Default)]
+pub struct MemoryTracker {
+    buffer_bytes: AtomicU64,
+    buffers: AtomicU64,
+    texture_bytes: AtomicU64,
+    textures: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub buffer_bytes: u64,
+    pub buffers: u64,
+    pub texture_bytes: u64,
+    pub textures: u64,
+}
+
+impl MemoryUsage {
+    pub fn total_bytes(&self) -> u64 {
+        self.buffer_bytes + self.texture_bytes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceKind {
+    Buffer,
+    Texture,
+}
+
+/// Accounts for a resource in its tracker until dropped, store it next to the resource
+#[derive(Debug)]
+pub struct TrackedMemory {
+    tracker: Arc<MemoryTracker>,
+    kind: ResourceKind,
+    bytes: u64,
+}
+
+impl MemoryTracker {
+    pub fn track_buffer(self: &Arc<Self>, buffer: &wgpu::Buffer) -> TrackedMemory {
+        self.track(ResourceKind::Buffer, buffer.size())
+    }
+
+    /// Accounts for every mip level and layer
+    pub fn track_texture(self: &Arc<Self>, texture: &wgpu::Texture) -> TrackedMemory {
+        let size = texture.size();
+        let bytes = (0..texture.mip_level_count())
+            .map(|level| {
+                let mip_size = size.mip_level_size(level, texture.dimension());
+                texture.format().theoretical_memory_footprint(mip_size)
+            })
+            .sum::<u64>()
+            * texture.sample_count() as u64;
+        self.track(ResourceKind::Texture, bytes)
+    }
+
+    pub fn usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buffer_bytes: self.buffer_bytes.load(Ordering::Relaxed),
+            buffers: self.buffers.load(Ordering::Relaxed),
+            texture_bytes: self.texture_bytes.load(Ordering::Relaxed),
+            textures: self.textures.load(Ordering::Relaxed),
+        }
+    }
+
+    fn track(self: &Arc<Self>, kind: ResourceKind, bytes: u64) -> TrackedMemory {
+        let (total, count) = self.counters(kind);
+        total.fetch_add(bytes, Ordering::Relaxed);
+        count.fetch_add(1, Ordering::Relaxed);
+        TrackedMemory {
+            tracker: self.clone(),
+            kind,
+            bytes,
+        }
+    }
+
+    fn counters(&self, kind: ResourceKind) -> (&AtomicU64, &AtomicU64) {
+        match kind {
+            ResourceKind::Buffer => (&self.buffer_bytes, &self.buffers),
+            ResourceKind::Texture => (&self.texture_bytes, &self.textures),
+        }
+    }
+}
+
+impl Drop for TrackedMemory {
+    fn drop(&mut self) {
+        let (total, count) = self.tracker.counters(self.kind);
+        total.fetch_sub(self.bytes, Ordering::Relaxed);
+        count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
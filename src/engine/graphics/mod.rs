@@ -4,41 +4,168 @@ use std::{
     time::{Duration, Instant},
 };
 
-use wgpu::{util::StagingBelt, *};
+use wgpu::{
+    util::{DeviceExt, StagingBelt},
+    *,
+};
 use winit::window::Window;
 
 use super::maths::Vec2u;
+use color::Color3f;
+use frame_stats::FrameStats;
+use memory::MemoryTracker;
 
 pub mod camera;
 pub mod color;
+pub mod compute;
+pub mod frame_stats;
+#[cfg(debug_assertions)]
+pub mod gizmos;
+#[cfg(debug_assertions)]
+pub mod inspector;
+pub mod light;
+pub mod line;
+pub mod memory;
 pub mod model;
+pub mod particles;
+pub mod per_draw;
+pub mod picking;
+pub mod post;
 pub mod renderer;
+pub mod screenshot;
+pub mod skybox;
+pub mod sprite;
+pub mod text;
+
+/// Push constant space requested from the device when supported, the portable minimum
+const MAX_PUSH_CONSTANT_SIZE: u32 = 128;
+
+/// Size of the staging belt buffers, larger uploads get a dedicated buffer
+const STAGING_CHUNK_SIZE: BufferAddress = 64 * 1024;
 
 pub struct Graphics {
     pub device: Device,
     pub queue: Queue,
-    pub surface: Surface<'static>,
+    /// None for `Graphics::headless`, frames are then rendered to an offscreen texture
+    pub surface: Option<Surface<'static>>,
     pub surface_format: TextureFormat,
     pub surface_capabilities: SurfaceCapabilities,
+    /// Present mode the surface is configured with, change it with `set_present_mode`
+    pub present_mode: PresentMode,
     pub viewport_size: Vec2u,
+    /// Physical pixels per logical pixel of the window's current monitor
+    pub scale_factor: f64,
+    /// Background color the scene is cleared to at the start of each frame
+    pub clear_color: Color3f,
+    /// Samples per pixel of the scene attachments, 1 disables multisampling.
+    /// Set it with `Renderer::set_msaa` so that the attachments and pipelines follow.
+    pub sample_count: u32,
+    pub capabilities: GraphicsCapabilities,
+    /// Number of frames presented, labels the frame command encoders for GPU debuggers
+    pub frame_index: u64,
+    /// Estimated memory of the engine buffers and textures, see `Renderer::memory_usage`
+    pub memory: Arc<MemoryTracker>,
+    /// Durations between the last presented frames
+    pub frame_stats: FrameStats,
+    /// Lent to the current frame, its buffers are reused once the GPU is done with them
+    staging_belt: Option<StagingBelt>,
 
     pub last_frame: Option<Instant>,
 }
 
+/// What the adapter supports, queried once at startup
+#[derive(Debug, Clone)]
+pub struct GraphicsCapabilities {
+    pub features: Features,
+    pub limits: Limits,
+    pub downlevel: DownlevelCapabilities,
+    /// MSAA sample counts usable with both the surface and depth formats, in increasing order
+    pub sample_counts: Vec<u32>,
+}
+
+impl GraphicsCapabilities {
+    /// Highest sampler anisotropy supported, 1 when anisotropic filtering is unavailable.
+    /// wgpu reports no per device level, any device with the downlevel flag accepts up to 16x
+    /// and picks the closest level its hardware has.
+    pub fn max_anisotropy(&self) -> u16 {
+        if self
+            .downlevel
+            .flags
+            .contains(DownlevelFlags::ANISOTROPIC_FILTERING)
+        {
+            16
+        } else {
+            1
+        }
+    }
+
+    /// Whether push constants of `size` bytes can be used, see `per_draw::PerDrawData`
+    pub fn supports_push_constants(&self, size: u32) -> bool {
+        self.features.contains(Features::PUSH_CONSTANTS)
+            && size <= self.limits.max_push_constant_size
+    }
+
+    /// Highest supported sample count not above the requested one, 1 when MSAA is unavailable
+    pub fn clamp_sample_count(&self, requested: u32) -> u32 {
+        let clamped = self
+            .sample_counts
+            .iter()
+            .copied()
+            .filter(|c| *c <= requested.max(1))
+            .max()
+            .unwrap_or(1);
+        if clamped != requested {
+            crate::log!("{requested}x MSAA is not supported, using {clamped}x");
+        }
+        clamped
+    }
+
+    /// Clamp a requested anisotropy level to what the device supports, to avoid validation errors
+    pub fn clamp_anisotropy(&self, requested: u16) -> u16 {
+        let clamped = requested.clamp(1, self.max_anisotropy());
+        if clamped != requested {
+            crate::log!("Anisotropy {requested}x is not supported, clamped to {clamped}x");
+        }
+        clamped
+    }
+}
+
 pub struct Frame {
     pub view: TextureView,
+    /// Texture of `view`, the surface texture or the offscreen texture of a headless frame
+    pub texture: Texture,
     pub encoder: CommandEncoder,
-    pub surface_texture: SurfaceTexture,
+    pub surface_texture: Option<SurfaceTexture>,
     pub staging_belt: StagingBelt,
 }
 
+impl Frame {
+    /// Upload `data` to `buffer` at `offset` through the staging belt. The copy is recorded in
+    /// the frame encoder, so only the passes recorded after this call see the new contents.
+    ///
+    /// `buffer` needs the `COPY_DST` usage, `offset` and the length of `data` must be multiples
+    /// of `wgpu::COPY_BUFFER_ALIGNMENT`.
+    pub fn write_buffer(
+        &mut self,
+        ctx: &Graphics,
+        buffer: &Buffer,
+        offset: BufferAddress,
+        data: &[u8],
+    ) {
+        let Some(size) = BufferSize::new(data.len() as u64) else {
+            return;
+        };
+        self.staging_belt
+            .write_buffer(&mut self.encoder, buffer, offset, size, &ctx.device)
+            .copy_from_slice(data);
+    }
+}
+
 impl Graphics {
     pub fn new(window: Arc<Window>) -> Self {
         let (width, height) = window.inner_size().into();
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: Backends::from_env().unwrap_or_default(),
-            ..Default::default()
-        });
+        let scale_factor = window.scale_factor();
+        let instance = create_instance();
         let surface = instance
             .create_surface(window)
             .unwrap_or_else(|e| panic!("Could not create graphics surface: {e}"));
@@ -48,17 +175,67 @@ impl Graphics {
             force_fallback_adapter: false,
         }))
         .unwrap();
-        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
-            label: None,
-            required_features: wgpu::Features::INDIRECT_FIRST_INSTANCE
-                | wgpu::Features::MULTI_DRAW_INDIRECT,
-            required_limits: wgpu::Limits::default(),
-            memory_hints: wgpu::MemoryHints::default(),
-            trace: Trace::Off,
-        }))
-        .unwrap_or_else(|e| panic!("Could not acquire graphics device: {e}"));
+        let (device, queue) = pollster::block_on(request_device(&adapter))
+            .unwrap_or_else(|e| panic!("Could not acquire graphics device: {e}"));
 
         let surface_capabilities = surface.get_capabilities(&adapter);
+        let mut _self = Self::from_device(
+            &adapter,
+            device,
+            queue,
+            Some(surface),
+            surface_capabilities,
+            (width, height),
+            scale_factor,
+        );
+
+        _self.resize((width, height));
+
+        _self
+    }
+
+    /// Graphics without a window, rendering frames of `width` x `height` to an offscreen
+    /// texture, e.g. for tests. None when no adapter is available.
+    pub fn headless(width: u32, height: u32) -> Option<Self> {
+        let instance = create_instance();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }))
+        .map_err(|e| crate::log!("No graphics adapter available: {e}"))
+        .ok()?;
+        let (device, queue) = pollster::block_on(request_device(&adapter))
+            .map_err(|e| crate::log!("Could not acquire graphics device: {e}"))
+            .ok()?;
+
+        // What a typical surface offers, so the pipelines match the windowed ones
+        let surface_capabilities = SurfaceCapabilities {
+            formats: vec![TextureFormat::Rgba8UnormSrgb],
+            present_modes: vec![PresentMode::Fifo],
+            alpha_modes: vec![CompositeAlphaMode::Opaque],
+            usages: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        };
+        Some(Self::from_device(
+            &adapter,
+            device,
+            queue,
+            None,
+            surface_capabilities,
+            (width.max(1), height.max(1)),
+            1.0,
+        ))
+    }
+
+    fn from_device(
+        adapter: &Adapter,
+        device: Device,
+        queue: Queue,
+        surface: Option<Surface<'static>>,
+        surface_capabilities: SurfaceCapabilities,
+        (width, height): (u32, u32),
+        scale_factor: f64,
+    ) -> Self {
         let surface_texture_format = surface_capabilities
             .formats
             .iter()
@@ -66,20 +243,54 @@ impl Graphics {
             .find(|f| f.is_srgb())
             .unwrap_or(surface_capabilities.formats[0]);
 
-        let mut _self = Self {
+        // Without adapter specific format features, only the counts guaranteed by WebGPU are valid
+        let format_flags = |format: TextureFormat| {
+            if device
+                .features()
+                .contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            {
+                adapter.get_texture_format_features(format).flags
+            } else {
+                format.guaranteed_format_features(device.features()).flags
+            }
+        };
+        let sample_counts = [1, 2, 4, 8, 16]
+            .into_iter()
+            .filter(|count| {
+                [surface_texture_format, TextureFormat::Depth32Float]
+                    .into_iter()
+                    .all(|format| format_flags(format).sample_count_supported(*count))
+            })
+            .collect();
+
+        let capabilities = GraphicsCapabilities {
+            features: device.features(),
+            limits: device.limits(),
+            downlevel: adapter.get_downlevel_capabilities(),
+            sample_counts,
+        };
+
+        let present_mode =
+            select_present_mode(PresentMode::Fifo, &surface_capabilities.present_modes);
+        Self {
             device,
             queue,
             surface,
             surface_capabilities,
             surface_format: surface_texture_format,
+            present_mode,
             viewport_size: [width, height].into(),
+            scale_factor,
+            clear_color: Color3f::BLACK,
+            sample_count: 1,
+            capabilities,
+            frame_index: 0,
+            memory: Arc::default(),
+            frame_stats: FrameStats::default(),
+            staging_belt: None,
 
             last_frame: None,
-        };
-
-        _self.resize((width, height));
-
-        _self
+        }
     }
 
     pub fn is_init(&self) -> bool {
@@ -92,28 +303,39 @@ impl Graphics {
             .unwrap_or(Duration::ZERO)
     }
 
-    pub fn next_frame(&self) -> Option<Frame> {
-        let surface_texture = self
-            .surface
-            .get_current_texture()
-            .map_err(|e| match e {
-                wgpu::SurfaceError::OutOfMemory => {
-                    panic!("The system is out of memory for rendering!")
-                }
-                _ => format!("An error occured during surface texture acquisition: {e}"),
-            })
-            .ok()?;
+    /// Frames per second averaged over the last frames, see `frame_stats`
+    pub fn fps(&self) -> f32 {
+        self.frame_stats.fps()
+    }
 
-        let view = surface_texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    /// Average frame time in milliseconds over the last frames, see `frame_stats`
+    pub fn frame_time_ms(&self) -> f32 {
+        self.frame_stats.average().as_secs_f32() * 1000.0
+    }
+
+    pub fn next_frame(&mut self) -> Option<Frame> {
+        let (texture, surface_texture) = match &self.surface {
+            Some(_) => {
+                let surface_texture = self.acquire_surface_texture()?;
+                (surface_texture.texture.clone(), Some(surface_texture))
+            }
+            None => (self.create_offscreen_texture(), None),
+        };
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let encoder = self
             .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(&format!("frame_{}", self.frame_index)),
+            });
 
-        let staging_belt = StagingBelt::new(1024);
+        let staging_belt = self
+            .staging_belt
+            .take()
+            .unwrap_or_else(|| StagingBelt::new(STAGING_CHUNK_SIZE));
 
         Some(Frame {
+            texture,
             surface_texture,
             encoder,
             view,
@@ -121,32 +343,271 @@ impl Graphics {
         })
     }
 
+    fn acquire_surface_texture(&mut self) -> Option<SurfaceTexture> {
+        self.surface.as_ref()?;
+        acquire_or_reconfigure(
+            self,
+            |ctx| {
+                let surface = ctx.surface.as_ref().expect("Checked above");
+                surface.get_current_texture()
+            },
+            |ctx| ctx.resize((ctx.viewport_size.x, ctx.viewport_size.y)),
+        )
+    }
+
+    /// Stands in for the surface texture of headless frames
+    fn create_offscreen_texture(&self) -> Texture {
+        self.device.create_texture(&TextureDescriptor {
+            label: Some("Offscreen Frame Texture"),
+            size: Extent3d {
+                width: self.viewport_size.x,
+                height: self.viewport_size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.surface_format,
+            usage: self.surface_capabilities.usages,
+            view_formats: &[],
+        })
+    }
+
     pub(crate) fn resize(&mut self, (width, height): (u32, u32)) {
-        if width > 0 && height > 0 {
-            self.surface.configure(
+        if width == 0 || height == 0 {
+            return;
+        }
+        if let Some(surface) = &self.surface {
+            surface.configure(
                 &self.device,
                 &wgpu::SurfaceConfiguration {
-                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    // Copies of the surface are used for screenshots when available
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | (self.surface_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
                     format: self.surface_format,
                     width,
                     height,
-                    present_mode: self.surface_capabilities.present_modes[0],
+                    present_mode: self.present_mode,
                     alpha_mode: self.surface_capabilities.alpha_modes[0],
                     view_formats: vec![],
                     desired_maximum_frame_latency: 2,
                 },
             );
-            self.viewport_size = [width, height].into();
         }
+        self.viewport_size = [width, height].into();
     }
 
-    pub fn present(&mut self, frame: Frame) {
+    /// Reconfigure the surface with `mode`, or with Fifo (vsync) when the surface does not
+    /// support it since every surface supports Fifo. Returns the mode in use.
+    pub fn set_present_mode(&mut self, mode: PresentMode) -> PresentMode {
+        self.present_mode = select_present_mode(mode, &self.surface_capabilities.present_modes);
+        if self.present_mode != mode {
+            crate::log!("Present mode {mode:?} is unsupported, falling back to Fifo");
+        }
+        self.resize((self.viewport_size.x, self.viewport_size.y));
+        self.present_mode
+    }
+
+    /// Buffer readable and writable from shaders, initialized with `contents`. Also copyable
+    /// both ways, add `BufferUsages::VERTEX` to draw from it.
+    pub fn create_storage_buffer(
+        &self,
+        label: &str,
+        contents: &[u8],
+        usage: BufferUsages,
+    ) -> Buffer {
+        self.device.create_buffer_init(&util::BufferInitDescriptor {
+            label: Some(label),
+            contents,
+            usage: usage | BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        })
+    }
+
+    /// Bind each buffer whole, at the binding of its index
+    pub fn create_buffer_bind_group(
+        &self,
+        layout: &BindGroupLayout,
+        buffers: &[&Buffer],
+    ) -> BindGroup {
+        let entries: Vec<BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| BindGroupEntry {
+                binding: i as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        self.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("buffer_bind_group"),
+            layout,
+            entries: &entries,
+        })
+    }
+
+    /// Copy a buffer back to the CPU, blocking until the GPU is done.
+    /// The buffer must have the `COPY_SRC` usage. Slow, meant for debugging and tests.
+    pub fn read_buffer(&self, buffer: &Buffer) -> Vec<u8> {
+        let readback = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: buffer.size(),
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, buffer.size());
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(MapMode::Read, |_| ());
+        self.device
+            .poll(PollType::Wait)
+            .unwrap_or_else(|e| panic!("Failed to read back buffer: {e}"));
+        let data = slice.get_mapped_range().to_vec();
+        readback.unmap();
+        data
+    }
+
+    /// Copy the first mip level of a 2D texture back to the CPU, rows tightly packed, blocking
+    /// until the GPU is done. The texture must have the `COPY_SRC` usage and an uncompressed color
+    /// format. Slow, meant for debugging and tests.
+    pub fn read_texture(&self, texture: &Texture) -> Vec<u8> {
+        let bytes_per_pixel = texture
+            .format()
+            .block_copy_size(None)
+            .expect("Texture format is not copyable");
+        let bytes_per_row = texture.width() * bytes_per_pixel;
+        let padded_bytes_per_row = bytes_per_row.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Texture Readback Buffer"),
+            size: (padded_bytes_per_row * texture.height()) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Texture Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(texture.height()),
+                },
+            },
+            Extent3d {
+                width: texture.width(),
+                height: texture.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(MapMode::Read, |_| ());
+        self.device
+            .poll(PollType::Wait)
+            .unwrap_or_else(|e| panic!("Failed to read back texture: {e}"));
+        let data = slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..bytes_per_row as usize])
+            .copied()
+            .collect();
+        readback.unmap();
+        data
+    }
+
+    pub fn present(&mut self, mut frame: Frame) {
+        frame.staging_belt.finish();
         self.queue.submit(Some(frame.encoder.finish()));
-        frame.surface_texture.present();
-        self.last_frame = Some(Instant::now());
+        if let Some(surface_texture) = frame.surface_texture {
+            surface_texture.present();
+        }
+        // The buffers come back once mapped again, polling resolves the mappings without waiting
+        frame.staging_belt.recall();
+        let _ = self.device.poll(PollType::Poll);
+        self.staging_belt = Some(frame.staging_belt);
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.frame_stats.push(now - last_frame);
+        }
+        self.last_frame = Some(now);
+        self.frame_index += 1;
+    }
+}
+
+/// `requested` when the surface supports it, Fifo (vsync) otherwise since every surface does
+pub fn select_present_mode(requested: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    match requested {
+        // Resolved by wgpu to a supported mode
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync => requested,
+        _ if supported.contains(&requested) => requested,
+        _ => PresentMode::Fifo,
     }
 }
 
+/// Acquire from `surface`, reconfiguring it once then retrying when it no longer matches the
+/// window, e.g. after a resize or a GPU reset
+fn acquire_or_reconfigure<S, T>(
+    surface: &mut S,
+    acquire: impl Fn(&S) -> Result<T, SurfaceError>,
+    reconfigure: impl FnOnce(&mut S),
+) -> Option<T> {
+    match acquire(surface) {
+        Ok(texture) => Some(texture),
+        Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+            reconfigure(surface);
+            acquire(surface)
+                .map_err(|e| {
+                    crate::log!("Failed to acquire the surface texture after reconfiguring: {e}")
+                })
+                .ok()
+        }
+        Err(SurfaceError::OutOfMemory) => panic!("The system is out of memory for rendering!"),
+        Err(e) => {
+            crate::log!("An error occured during surface texture acquisition: {e}");
+            None
+        }
+    }
+}
+
+fn create_instance() -> Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: Backends::from_env().unwrap_or_default(),
+        ..Default::default()
+    })
+}
+
+async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), RequestDeviceError> {
+    adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::INDIRECT_FIRST_INSTANCE
+                | wgpu::Features::MULTI_DRAW_INDIRECT
+                | (adapter.features()
+                    & (wgpu::Features::PUSH_CONSTANTS
+                        | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)),
+            required_limits: wgpu::Limits {
+                max_push_constant_size: adapter
+                    .limits()
+                    .max_push_constant_size
+                    .min(MAX_PUSH_CONSTANT_SIZE),
+                ..Default::default()
+            },
+            memory_hints: wgpu::MemoryHints::default(),
+            trace: Trace::Off,
+        })
+        .await
+}
+
 impl std::fmt::Debug for Graphics {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Graphics")
@@ -155,8 +616,148 @@ impl std::fmt::Debug for Graphics {
             .field("surface", &self.surface)
             .field("surface_format", &self.surface_format)
             .field("surface_capabilities", &self.surface_capabilities)
+            .field("present_mode", &self.present_mode)
             .field("viewport_size", &self.viewport_size)
+            .field("capabilities", &self.capabilities)
             .field("last_frame", &self.last_frame)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(downlevel_flags: DownlevelFlags) -> GraphicsCapabilities {
+        GraphicsCapabilities {
+            features: Features::empty(),
+            limits: Limits::default(),
+            downlevel: DownlevelCapabilities {
+                flags: downlevel_flags,
+                ..Default::default()
+            },
+            sample_counts: vec![1],
+        }
+    }
+
+    #[test]
+    fn anisotropy_is_clamped_to_the_supported_range() {
+        let supported = capabilities(DownlevelFlags::ANISOTROPIC_FILTERING);
+        assert_eq!(supported.clamp_anisotropy(8), 8);
+        assert_eq!(supported.clamp_anisotropy(32), 16);
+        assert_eq!(supported.clamp_anisotropy(0), 1);
+
+        let unsupported = capabilities(DownlevelFlags::empty());
+        assert_eq!(unsupported.clamp_anisotropy(16), 1);
+    }
+
+    #[test]
+    fn unsupported_present_mode_falls_back_to_fifo() {
+        let supported = [PresentMode::Fifo, PresentMode::Mailbox];
+        let select = |mode| select_present_mode(mode, &supported);
+        assert_eq!(select(PresentMode::Mailbox), PresentMode::Mailbox);
+        assert_eq!(select(PresentMode::Immediate), PresentMode::Fifo);
+        assert_eq!(select(PresentMode::AutoNoVsync), PresentMode::AutoNoVsync);
+        // Fifo even when listed after another mode
+        let supported = [PresentMode::Mailbox, PresentMode::Fifo];
+        assert_eq!(
+            select_present_mode(PresentMode::Fifo, &supported),
+            PresentMode::Fifo
+        );
+    }
+
+    #[test]
+    fn set_present_mode_falls_back_without_panicking() {
+        let Some(mut ctx) = Graphics::headless(8, 8) else {
+            return;
+        };
+        assert_eq!(ctx.present_mode, PresentMode::Fifo);
+        assert_eq!(
+            ctx.set_present_mode(PresentMode::Immediate),
+            PresentMode::Fifo
+        );
+        assert_eq!(ctx.present_mode, PresentMode::Fifo);
+    }
+
+    /// Surface reporting `error` until reconfigured, or always when `persistent`
+    struct FakeSurface {
+        error: Option<SurfaceError>,
+        persistent: bool,
+        reconfigurations: u32,
+    }
+
+    impl FakeSurface {
+        fn new(error: Option<SurfaceError>, persistent: bool) -> Self {
+            Self {
+                error,
+                persistent,
+                reconfigurations: 0,
+            }
+        }
+
+        fn acquire(&mut self) -> Option<&'static str> {
+            acquire_or_reconfigure(
+                self,
+                |surface| match &surface.error {
+                    Some(error) => Err(error.clone()),
+                    None => Ok("texture"),
+                },
+                |surface| {
+                    surface.reconfigurations += 1;
+                    if !surface.persistent {
+                        surface.error = None;
+                    }
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn lost_or_outdated_surfaces_are_reconfigured_once() {
+        let mut surface = FakeSurface::new(None, false);
+        assert_eq!(surface.acquire(), Some("texture"));
+        assert_eq!(surface.reconfigurations, 0);
+
+        for error in [SurfaceError::Lost, SurfaceError::Outdated] {
+            let mut surface = FakeSurface::new(Some(error.clone()), false);
+            assert_eq!(surface.acquire(), Some("texture"));
+            assert_eq!(surface.reconfigurations, 1);
+
+            // Skips the frame rather than looping when reconfiguring does not help
+            let mut surface = FakeSurface::new(Some(error), true);
+            assert_eq!(surface.acquire(), None);
+            assert_eq!(surface.reconfigurations, 1);
+        }
+
+        let mut surface = FakeSurface::new(Some(SurfaceError::Timeout), false);
+        assert_eq!(surface.acquire(), None);
+        assert_eq!(surface.reconfigurations, 0);
+    }
+
+    #[test]
+    fn staging_belt_uploads_are_read_back() {
+        let Some(mut ctx) = Graphics::headless(8, 8) else {
+            return;
+        };
+        // Larger than a staging chunk, to cover the dedicated buffer path too
+        let size = STAGING_CHUNK_SIZE * 2;
+        let buffer = ctx.device.create_buffer(&BufferDescriptor {
+            label: Some("Staging Test Buffer"),
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        // The belt is recalled and reused across frames
+        for value in 1..=3u8 {
+            let mut frame = ctx.next_frame().unwrap();
+            frame.write_buffer(&ctx, &buffer, 0, &[value; 16]);
+            frame.write_buffer(&ctx, &buffer, 16, &vec![value + 10; size as usize - 16]);
+            ctx.present(frame);
+
+            let data = ctx.read_buffer(&buffer);
+            assert_eq!(data[..16], [value; 16]);
+            assert!(data[16..].iter().all(|byte| *byte == value + 10));
+        }
+    }
+}
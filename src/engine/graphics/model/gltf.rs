@@ -0,0 +1,343 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::engine::{
+    graphics::{
+        Graphics,
+        model::{
+            Model, Vertex,
+            texture::{ModelTexture, TextureUniform},
+        },
+    },
+    maths::Mat4f,
+};
+
+/// A mesh primitive of a glTF scene, with its node transform baked into the model transform
+pub struct GltfPrimitive {
+    pub model: Model<u32>,
+    /// Index of the base color texture in `GltfScene::textures`
+    pub base_color_texture: Option<usize>,
+    /// Multiplies the base color texture, linear RGBA
+    pub base_color_factor: [f32; 4],
+}
+
+/// Triangle primitives of the default scene of a glTF file. Materials are reduced to their base
+/// color, other primitive modes and textures are skipped.
+pub struct GltfScene {
+    pub primitives: Vec<GltfPrimitive>,
+    pub textures: Vec<TextureUniform>,
+}
+
+impl GltfScene {
+    /// Load a `.gltf` with its external buffers and images, or a `.glb`
+    pub fn load(ctx: &Graphics, path: impl AsRef<Path>) -> Result<Self, ::gltf::Error> {
+        Ok(Self::upload(ctx, &GltfData::load(path)?))
+    }
+
+    /// Same as `load`, from the content of a self contained file, e.g. with `include_bytes!`
+    pub fn from_bytes(ctx: &Graphics, bytes: &[u8]) -> Result<Self, ::gltf::Error> {
+        Ok(Self::upload(ctx, &GltfData::from_bytes(bytes)?))
+    }
+
+    /// Create the models and textures of meshes read with `GltfData`
+    pub fn upload(ctx: &Graphics, data: &GltfData) -> Self {
+        let textures = data
+            .images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                let texture = ModelTexture::from_rgba8(
+                    ctx,
+                    image.width,
+                    image.height,
+                    &image.rgba,
+                    &format!("gltf_image_{index}"),
+                );
+                TextureUniform::new(ctx, &texture)
+            })
+            .collect();
+        let primitives = data
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let mut model = Model::new(ctx, &mesh.vertices, &mesh.indices);
+                model.set_transform(mesh.transform);
+                GltfPrimitive {
+                    model,
+                    base_color_texture: mesh.base_color_texture,
+                    base_color_factor: mesh.base_color_factor,
+                }
+            })
+            .collect();
+        Self {
+            primitives,
+            textures,
+        }
+    }
+}
+
+/// A mesh primitive of a glTF scene read on the CPU, before `GltfScene::upload`
+#[derive(Debug, Clone)]
+pub struct GltfMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Transform of the node holding the mesh, parents included
+    pub transform: Mat4f,
+    /// Index of the base color texture in `GltfData::images`
+    pub base_color_texture: Option<usize>,
+    /// Multiplies the base color texture, linear RGBA
+    pub base_color_factor: [f32; 4],
+}
+
+/// A decoded image of a glTF file, converted to RGBA8
+#[derive(Debug, Clone)]
+pub struct GltfImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Meshes and base color images of the default scene of a glTF file, without a device
+#[derive(Debug, Clone, Default)]
+pub struct GltfData {
+    pub meshes: Vec<GltfMesh>,
+    /// Only the images used as a base color texture, each once
+    pub images: Vec<GltfImage>,
+}
+
+impl GltfData {
+    /// Read a `.gltf` with its external buffers and images, or a `.glb`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ::gltf::Error> {
+        let (document, buffers, images) = ::gltf::import(path)?;
+        Ok(Self::from_import(&document, &buffers, &images))
+    }
+
+    /// Same as `load`, from the content of a self contained file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ::gltf::Error> {
+        let (document, buffers, images) = ::gltf::import_slice(bytes)?;
+        Ok(Self::from_import(&document, &buffers, &images))
+    }
+
+    fn from_import(
+        document: &::gltf::Document,
+        buffers: &[::gltf::buffer::Data],
+        images: &[::gltf::image::Data],
+    ) -> Self {
+        let mut data = Self::default();
+        // Index in `data.images` for each decoded image, shared by the primitives using it
+        let mut loaded_images = HashMap::new();
+
+        let Some(root) = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        else {
+            return data;
+        };
+
+        let mut nodes: Vec<_> = root.nodes().map(|n| (n, Mat4f::identity())).collect();
+        while let Some((node, parent)) = nodes.pop() {
+            let transform = parent * Mat4f::from(node.transform().matrix());
+            nodes.extend(node.children().map(|child| (child, transform)));
+
+            let Some(mesh) = node.mesh() else {
+                continue;
+            };
+            for primitive in mesh.primitives() {
+                let Some((vertices, indices)) = read_primitive(&primitive, buffers) else {
+                    continue;
+                };
+
+                let pbr = primitive.material().pbr_metallic_roughness();
+                let base_color_texture = pbr.base_color_texture().map(|info| {
+                    let image = info.texture().source().index();
+                    *loaded_images.entry(image).or_insert_with(|| {
+                        let source = &images[image];
+                        data.images.push(GltfImage {
+                            width: source.width,
+                            height: source.height,
+                            rgba: to_rgba8(source),
+                        });
+                        data.images.len() - 1
+                    })
+                });
+
+                data.meshes.push(GltfMesh {
+                    vertices,
+                    indices,
+                    transform,
+                    base_color_texture,
+                    base_color_factor: pbr.base_color_factor(),
+                });
+            }
+        }
+
+        data
+    }
+}
+
+/// None for non triangle primitives and primitives without positions
+fn read_primitive(
+    primitive: &::gltf::Primitive,
+    buffers: &[::gltf::buffer::Data],
+) -> Option<(Vec<Vertex>, Vec<u32>)> {
+    if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+        return None;
+    }
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let positions: Vec<[f32; 3]> = reader.read_positions()?.collect();
+    let mut uvs = reader.read_tex_coords(0).map(|uvs| uvs.into_f32());
+    let mut normals = reader.read_normals();
+    let has_normals = normals.is_some();
+
+    let mut vertices: Vec<_> = positions
+        .into_iter()
+        .map(|position| Vertex {
+            position,
+            uv: uvs.as_mut().and_then(Iterator::next).unwrap_or_default(),
+            normal: normals
+                .as_mut()
+                .and_then(Iterator::next)
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    // Handles u8, u16 and u32 index accessors, non indexed primitives draw vertices in order
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+
+    if !has_normals {
+        Model::compute_flat_normals(&mut vertices, &indices);
+    }
+    Some((vertices, indices))
+}
+
+fn to_rgba8(data: &::gltf::image::Data) -> Vec<u8> {
+    use ::gltf::image::Format;
+
+    let (channels, channel_size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    data.pixels
+        .chunks_exact(channels * channel_size)
+        .flat_map(|pixel| {
+            let mut rgba = [0, 0, 0, 255];
+            for (channel, value) in rgba.iter_mut().zip(pixel.chunks_exact(channel_size)) {
+                *channel = match *value {
+                    [v] => v,
+                    [lo, hi] => (u16::from_ne_bytes([lo, hi]) >> 8) as u8,
+                    [a, b, c, d] => {
+                        (f32::from_ne_bytes([a, b, c, d]).clamp(0.0, 1.0) * 255.0).round() as u8
+                    }
+                    _ => unreachable!(),
+                };
+            }
+            rgba
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A binary glTF with a single textured quad, in the spirit of the BoxTextured sample
+    fn textured_quad_glb() -> Vec<u8> {
+        let positions: [[f32; 3]; 4] = [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]];
+        let uvs: [[f32; 2]; 4] = [[0., 1.], [1., 1.], [1., 0.], [0., 0.]];
+        let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let png = include_bytes!("../../../../assets/debug.png");
+
+        let mut bin = vec![];
+        bin.extend_from_slice(bytemuck::cast_slice(&positions));
+        bin.extend_from_slice(bytemuck::cast_slice(&uvs));
+        bin.extend_from_slice(bytemuck::cast_slice(&indices));
+        bin.extend_from_slice(png);
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [{{ "mesh": 0, "translation": [2, 0, 0] }}],
+                "meshes": [{{ "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }},
+                    "indices": 2,
+                    "material": 0
+                }}] }}],
+                "materials": [{{ "pbrMetallicRoughness": {{
+                    "baseColorTexture": {{ "index": 0 }},
+                    "baseColorFactor": [1, 0.5, 0.5, 1]
+                }} }}],
+                "textures": [{{ "source": 0 }}],
+                "images": [{{ "bufferView": 3, "mimeType": "image/png" }}],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC2" }},
+                    {{ "bufferView": 2, "componentType": 5123, "count": 6, "type": "SCALAR" }}
+                ],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+                    {{ "buffer": 0, "byteOffset": 48, "byteLength": 32 }},
+                    {{ "buffer": 0, "byteOffset": 80, "byteLength": 12 }},
+                    {{ "buffer": 0, "byteOffset": 92, "byteLength": {} }}
+                ],
+                "buffers": [{{ "byteLength": {} }}]
+            }}"#,
+            png.len(),
+            bin.len()
+        );
+        let mut json = json.into_bytes();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+
+        let mut glb = vec![];
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    #[test]
+    fn textured_mesh_is_read_with_its_image() {
+        let data = GltfData::from_bytes(&textured_quad_glb()).unwrap();
+
+        assert_eq!(data.meshes.len(), 1);
+        let mesh = &data.meshes[0];
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.vertices[2].uv, [1.0, 0.0]);
+        // Normals are missing from the file and computed from the triangles
+        assert_eq!(mesh.vertices[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(mesh.transform[(0, 3)], 2.0);
+        assert_eq!(mesh.base_color_texture, Some(0));
+        assert_eq!(mesh.base_color_factor, [1.0, 0.5, 0.5, 1.0]);
+
+        assert_eq!(data.images.len(), 1);
+        let image = &data.images[0];
+        assert_eq!(image.rgba.len(), (image.width * image.height * 4) as usize);
+    }
+}
@@ -0,0 +1,216 @@
+use wgpu::util::DeviceExt;
+
+use crate::engine::{
+    graphics::{Graphics, memory::TrackedMemory},
+    maths::{Aabb, Mat4f},
+};
+
+/// Per-instance vertex input declaration, prepend it to shaders drawing instanced models
+pub const INSTANCE_WGSL: &str = r#"
+struct InstanceInput {
+    @location(3) model_0: vec4<f32>,
+    @location(4) model_1: vec4<f32>,
+    @location(5) model_2: vec4<f32>,
+    @location(6) model_3: vec4<f32>,
+    @location(7) color: vec4<f32>,
+};
+
+fn instance_matrix(instance: InstanceInput) -> mat4x4<f32> {
+    return mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceData {
+    pub model: [[f32; 4]; 4],
+    /// Tint multiplied into the output color
+    pub color: [f32; 4],
+}
+
+impl Default for InstanceData {
+    fn default() -> Self {
+        Self::new(Mat4f::identity(), [1.0; 4])
+    }
+}
+
+impl InstanceData {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+    ];
+
+    pub fn new(model: Mat4f, color: [f32; 4]) -> Self {
+        Self {
+            model: model.into(),
+            color,
+        }
+    }
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceData>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Instance buffer, grown when more instances than its capacity are set.
+///
+/// Instances are relative to a shared transform, composed on the CPU when uploading.
+pub struct Instances {
+    pub buffer: wgpu::Buffer,
+    local: Vec<InstanceData>,
+    transform: Mat4f,
+    dirty: bool,
+    memory: TrackedMemory,
+}
+
+impl Instances {
+    pub fn new(ctx: &Graphics, instances: &[InstanceData]) -> Self {
+        let buffer = create_buffer(ctx, instances);
+        Self {
+            memory: ctx.memory.track_buffer(&buffer),
+            buffer,
+            local: instances.to_vec(),
+            transform: Mat4f::identity(),
+            dirty: false,
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.local.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.local.is_empty()
+    }
+
+    /// Instances relative to the transform
+    pub fn local(&self) -> &[InstanceData] {
+        &self.local
+    }
+
+    pub fn transform(&self) -> &Mat4f {
+        &self.transform
+    }
+
+    /// The new transform is uploaded by the next `sync`
+    pub fn set_transform(&mut self, transform: Mat4f) {
+        self.transform = transform;
+        self.dirty = true;
+    }
+
+    /// Upload the instances if the transform changed since the last upload, returns true if it did
+    pub fn sync(&mut self, ctx: &Graphics) -> bool {
+        let dirty = self.dirty;
+        if dirty {
+            self.upload(ctx);
+        }
+        dirty
+    }
+
+    /// World space box containing every instance of a mesh with the given local bounds
+    pub fn world_bounds(&self, local: &Aabb) -> Option<Aabb> {
+        self.local
+            .iter()
+            .map(|i| local.transformed(&(self.transform * Mat4f::from(i.model))))
+            .reduce(|a, b| a.merge(&b))
+    }
+
+    pub fn capacity(&self) -> u32 {
+        (self.buffer.size() / std::mem::size_of::<InstanceData>() as u64) as u32
+    }
+
+    pub fn update(&mut self, ctx: &Graphics, instances: &[InstanceData]) {
+        self.local = instances.to_vec();
+        self.upload(ctx);
+    }
+
+    fn upload(&mut self, ctx: &Graphics) {
+        let world = world_instances(&self.transform, &self.local);
+
+        if world.len() as u32 > self.capacity() {
+            self.buffer = create_buffer(ctx, &world);
+            self.memory = ctx.memory.track_buffer(&self.buffer);
+        } else {
+            ctx.queue
+                .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&world));
+        }
+        self.dirty = false;
+    }
+}
+
+/// Instances as uploaded, with the model transform applied
+fn world_instances(transform: &Mat4f, local: &[InstanceData]) -> Vec<InstanceData> {
+    local
+        .iter()
+        .map(|i| InstanceData {
+            model: (transform * Mat4f::from(i.model)).into(),
+            color: i.color,
+        })
+        .collect()
+}
+
+fn create_buffer(ctx: &Graphics, instances: &[InstanceData]) -> wgpu::Buffer {
+    let placeholder = [InstanceData::default()];
+    ctx.device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Buffer"),
+            // Buffers can't be empty, keep room for one instance
+            contents: if instances.is_empty() {
+                bytemuck::cast_slice(&placeholder)
+            } else {
+                bytemuck::cast_slice(instances)
+            },
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::maths::Vec3f;
+
+    #[test]
+    fn instance_layout_matches_transform_and_color() {
+        let layout = InstanceData::desc();
+        assert_eq!(layout.array_stride, 80);
+        assert_eq!(layout.step_mode, wgpu::VertexStepMode::Instance);
+
+        let offsets: Vec<_> = layout.attributes.iter().map(|a| a.offset).collect();
+        assert_eq!(offsets, [0, 16, 32, 48, 64]);
+        assert_eq!(
+            std::mem::offset_of!(InstanceData, color) as u64,
+            layout.attributes[4].offset
+        );
+        let locations: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|a| a.shader_location)
+            .collect();
+        assert_eq!(locations, [3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn two_cubes_are_drawn_at_their_own_positions() {
+        let corner = nalgebra::Point3::new(0.5, 0.5, 0.5);
+        let unit_cube = Aabb::new(Vec3f::repeat(-0.5), Vec3f::repeat(0.5));
+        for position in [Vec3f::new(-2.0, 0.0, 0.0), Vec3f::new(3.0, 1.0, -4.0)] {
+            let transform = Mat4f::new_translation(&position);
+            let [instance] = world_instances(&transform, &[InstanceData::default()])[..] else {
+                panic!("one instance per cube");
+            };
+            let model = Mat4f::from(instance.model);
+            assert_eq!(
+                model.transform_point(&corner).coords,
+                position.add_scalar(0.5)
+            );
+            assert_eq!(unit_cube.transformed(&model).center(), position);
+        }
+    }
+}
@@ -3,9 +3,20 @@ use std::marker::PhantomData;
 use bytemuck::Pod;
 use wgpu::util::DeviceExt;
 
-use crate::engine::graphics::Graphics;
+use crate::engine::{
+    graphics::{
+        Graphics,
+        memory::TrackedMemory,
+        model::instance::{InstanceData, Instances},
+    },
+    maths::{Aabb, Mat4f, Vec3f},
+};
 
+pub mod gltf;
+pub mod instance;
+pub mod obj;
 pub mod renderer;
+pub mod shapes;
 pub mod texture;
 
 #[repr(C)]
@@ -13,6 +24,7 @@ pub mod texture;
 pub struct Vertex {
     pub position: [f32; 3],
     pub uv: [f32; 2],
+    pub normal: [f32; 3],
 }
 
 impl Vertex {
@@ -31,6 +43,11 @@ impl Vertex {
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x2,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
             ],
         }
     }
@@ -39,17 +56,64 @@ impl Vertex {
 pub struct Model<I = u16> {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    /// Drawn once per instance, a single untinted instance by default
+    pub instances: Instances,
+    /// Local bounds of the vertices at creation, None for a model created without vertices
+    bounds: Option<Aabb>,
+    world_bounds: Option<Aabb>,
+    _memory: [TrackedMemory; 2],
     _marker: PhantomData<I>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelUpdateError {
+    /// The buffer was not created with `BufferUsages::COPY_DST`
+    NotCopyDst,
+    /// The new data does not fit in the existing buffer
+    CapacityExceeded { capacity: u64, requested: u64 },
+}
+
+impl std::fmt::Display for ModelUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotCopyDst => write!(f, "model buffer is not updatable (missing COPY_DST usage)"),
+            Self::CapacityExceeded {
+                capacity,
+                requested,
+            } => write!(
+                f,
+                "model buffer too small: {requested} bytes requested, capacity is {capacity} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModelUpdateError {}
+
 impl<I: Pod> Model<I> {
     pub fn new(ctx: &Graphics, vertices: &[Vertex], indices: &[I]) -> Self {
+        Self::new_with_usage(ctx, vertices, indices, wgpu::BufferUsages::empty())
+    }
+
+    /// Create a model whose vertices and indices can be rewritten with `update_vertices` and
+    /// `update_indices`, up to their initial sizes
+    pub fn new_dynamic(ctx: &Graphics, vertices: &[Vertex], indices: &[I]) -> Self {
+        Self::new_with_usage(ctx, vertices, indices, wgpu::BufferUsages::COPY_DST)
+    }
+
+    /// Create a model whose buffers have additional usages, such as `COPY_DST` to allow updates
+    pub fn new_with_usage(
+        ctx: &Graphics,
+        vertices: &[Vertex],
+        indices: &[I],
+        usage: wgpu::BufferUsages,
+    ) -> Self {
         let vertex_buffer = ctx
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Vertex Buffer"),
                 contents: bytemuck::cast_slice(vertices),
-                usage: wgpu::BufferUsages::VERTEX,
+                usage: wgpu::BufferUsages::VERTEX | usage,
             });
 
         let index_buffer = ctx
@@ -57,12 +121,20 @@ impl<I: Pod> Model<I> {
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Index Buffer"),
                 contents: bytemuck::cast_slice(indices),
-                usage: wgpu::BufferUsages::INDEX,
+                usage: wgpu::BufferUsages::INDEX | usage,
             });
 
+        let bounds = Aabb::from_points(vertices.iter().map(|v| Vec3f::from(v.position)));
+        let instances = Instances::new(ctx, &[InstanceData::default()]);
+        let world_bounds = bounds.and_then(|b| instances.world_bounds(&b));
+
         Self {
+            _memory: [&vertex_buffer, &index_buffer].map(|b| ctx.memory.track_buffer(b)),
             vertex_buffer,
             index_buffer,
+            instances,
+            bounds,
+            world_bounds,
             _marker: PhantomData,
         }
     }
@@ -71,65 +143,8 @@ impl<I: Pod> Model<I> {
     where
         I: From<u8>,
     {
-        let positions = [
-            // Front face
-            ([-0.5, -0.5, 0.5], [0.0, 0.0]),
-            ([0.5, -0.5, 0.5], [1.0, 0.0]),
-            ([0.5, 0.5, 0.5], [1.0, 1.0]),
-            ([-0.5, 0.5, 0.5], [0.0, 1.0]),
-            // Back face
-            ([0.5, -0.5, -0.5], [0.0, 0.0]),
-            ([-0.5, -0.5, -0.5], [1.0, 0.0]),
-            ([-0.5, 0.5, -0.5], [1.0, 1.0]),
-            ([0.5, 0.5, -0.5], [0.0, 1.0]),
-            // Left face
-            ([-0.5, -0.5, -0.5], [0.0, 0.0]),
-            ([-0.5, -0.5, 0.5], [1.0, 0.0]),
-            ([-0.5, 0.5, 0.5], [1.0, 1.0]),
-            ([-0.5, 0.5, -0.5], [0.0, 1.0]),
-            // Right face
-            ([0.5, -0.5, 0.5], [0.0, 0.0]),
-            ([0.5, -0.5, -0.5], [1.0, 0.0]),
-            ([0.5, 0.5, -0.5], [1.0, 1.0]),
-            ([0.5, 0.5, 0.5], [0.0, 1.0]),
-            // Top face
-            ([-0.5, 0.5, 0.5], [0.0, 0.0]),
-            ([0.5, 0.5, 0.5], [1.0, 0.0]),
-            ([0.5, 0.5, -0.5], [1.0, 1.0]),
-            ([-0.5, 0.5, -0.5], [0.0, 1.0]),
-            // Bottom face
-            ([-0.5, -0.5, -0.5], [0.0, 0.0]),
-            ([0.5, -0.5, -0.5], [1.0, 0.0]),
-            ([0.5, -0.5, 0.5], [1.0, 1.0]),
-            ([-0.5, -0.5, 0.5], [0.0, 1.0]),
-        ];
-
-        let vertices: Vec<Vertex> = positions
-            .iter()
-            .map(|(pos, uv)| Vertex {
-                position: *pos,
-                uv: *uv,
-            })
-            .collect();
-
-        #[rustfmt::skip]
-        let mut indices: Vec<I> = vec![
-            0 .into(), 1 .into(), 2 .into(), 0 .into(), 2 .into(), 3 .into(), // Front
-            4 .into(), 5 .into(), 6 .into(), 4 .into(), 6 .into(), 7 .into(), // Back
-            8 .into(), 9 .into(), 10.into(), 8 .into(), 10.into(), 11.into(), // Left
-            12.into(), 13.into(), 14.into(), 12.into(), 14.into(), 15.into(), // Right
-            16.into(), 17.into(), 18.into(), 16.into(), 18.into(), 19.into(), // Top
-            20.into(), 21.into(), 22.into(), 20.into(), 22.into(), 23.into(), // Bottom
-        ];
-
-        // Reverse winding order if inward facing
-        if inward_facing {
-            for tri in indices.chunks_mut(3) {
-                tri.swap(1, 2);
-            }
-        }
-
-        Self::new(ctx, &vertices, &indices)
+        let (vertices, indices) = cube_mesh(inward_facing);
+        Self::new(ctx, &vertices, &indices.map(I::from))
     }
 
     pub fn plane(ctx: &Graphics) -> Self
@@ -141,26 +156,314 @@ impl<I: Pod> Model<I> {
                 Vertex {
                     position: [-0.5, 0.0, -0.5],
                     uv: [0.0, 1.0],
+                    normal: [0.0, 1.0, 0.0],
                 },
                 Vertex {
                     position: [0.5, 0.0, -0.5],
                     uv: [1.0, 1.0],
+                    normal: [0.0, 1.0, 0.0],
                 },
                 Vertex {
                     position: [0.5, 0.0, 0.5],
                     uv: [1.0, 0.0],
+                    normal: [0.0, 1.0, 0.0],
                 },
                 Vertex {
                     position: [-0.5, 0.0, 0.5],
                     uv: [0.0, 0.0],
+                    normal: [0.0, 1.0, 0.0],
                 },
             ],
-            [0.into(), 1.into(), 2.into(), 0.into(), 2.into(), 3.into()],
+            // Counter clockwise seen from above, facing +Y
+            [0.into(), 2.into(), 1.into(), 0.into(), 3.into(), 2.into()],
         );
         Self::new(ctx, &vertices, &indices)
     }
 
+    /// Set each vertex normal from the winding of the triangles using it, counter clockwise
+    /// triangles face towards the viewer. Vertices shared by several triangles get their
+    /// area weighted average, duplicate vertices per face for hard edges.
+    pub fn compute_flat_normals(vertices: &mut [Vertex], indices: &[I])
+    where
+        I: Into<u32>,
+    {
+        let mut normals = vec![Vec3f::zeros(); vertices.len()];
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i].into() as usize);
+            let p = |i: usize| Vec3f::from(vertices[i].position);
+            // Not normalized, its length is twice the triangle area
+            let normal = (p(b) - p(a)).cross(&(p(c) - p(a)));
+            for i in [a, b, c] {
+                normals[i] += normal;
+            }
+        }
+
+        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+            vertex.normal = normal
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vec3f::zeros)
+                .into();
+        }
+    }
+
+    /// Overwrite the first vertices, the bounds used for culling are recomputed from `vertices` only.
+    /// Fails when the vertex buffer lacks the `COPY_DST` usage given by `new_dynamic` or
+    /// `new_with_usage`, or when the vertices do not fit in it.
+    pub fn update_vertices(
+        &mut self,
+        ctx: &Graphics,
+        vertices: &[Vertex],
+    ) -> Result<(), ModelUpdateError> {
+        write_buffer(ctx, &self.vertex_buffer, bytemuck::cast_slice(vertices))?;
+        self.bounds = Aabb::from_points(vertices.iter().map(|v| Vec3f::from(v.position)));
+        self.update_world_bounds();
+        Ok(())
+    }
+
+    /// Overwrite the first indices. Every index of the buffer is drawn, so shrinking geometry
+    /// should repeat an index to leave degenerate triangles at the end.
+    pub fn update_indices(&self, ctx: &Graphics, indices: &[I]) -> Result<(), ModelUpdateError> {
+        write_buffer(ctx, &self.index_buffer, bytemuck::cast_slice(indices))
+    }
+
+    pub fn set_instances(&mut self, ctx: &Graphics, instances: &[InstanceData]) {
+        self.instances.update(ctx, instances);
+        self.update_world_bounds();
+    }
+
+    /// Model matrix applied to every instance, uploaded when rendered through `ModelRenderer`
+    /// or by calling `sync`
+    pub fn set_transform(&mut self, transform: Mat4f) {
+        self.instances.set_transform(transform);
+    }
+
+    pub fn transform(&self) -> &Mat4f {
+        self.instances.transform()
+    }
+
+    pub fn sync(&mut self, ctx: &Graphics) {
+        if self.instances.sync(ctx) {
+            self.update_world_bounds();
+        }
+    }
+
+    pub fn bounds(&self) -> Option<&Aabb> {
+        self.bounds.as_ref()
+    }
+
+    /// Bounds of every instance in world space as of the last upload
+    pub fn world_bounds(&self) -> Option<&Aabb> {
+        self.world_bounds.as_ref()
+    }
+
+    fn update_world_bounds(&mut self) {
+        self.world_bounds = self.bounds.and_then(|b| self.instances.world_bounds(&b));
+    }
+
     pub fn indices_count(&self) -> u32 {
-        self.index_buffer.size() as u32 / std::mem::size_of::<u16>() as u32
+        self.index_buffer.size() as u32 / std::mem::size_of::<I>() as u32
+    }
+
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        match std::mem::size_of::<I>() {
+            2 => wgpu::IndexFormat::Uint16,
+            4 => wgpu::IndexFormat::Uint32,
+            size => panic!("Unsupported index size: {size} bytes"),
+        }
+    }
+
+    /// Bind the vertex (slot 0), instance (slot 1) and index buffers then draw every instance
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instances.buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), self.index_format());
+        render_pass.draw_indexed(0..self.indices_count(), 0, 0..self.instances.len());
+    }
+}
+
+/// Vertices and indices of `Model::cube`, a unit cube with 4 vertices per face so each face has
+/// its own normal. `inward_facing` flips the normals and the winding, to be seen from inside.
+pub fn cube_mesh(inward_facing: bool) -> (Vec<Vertex>, [u8; 36]) {
+    let positions = [
+        // Front face
+        ([-0.5, -0.5, 0.5], [0.0, 0.0]),
+        ([0.5, -0.5, 0.5], [1.0, 0.0]),
+        ([0.5, 0.5, 0.5], [1.0, 1.0]),
+        ([-0.5, 0.5, 0.5], [0.0, 1.0]),
+        // Back face
+        ([0.5, -0.5, -0.5], [0.0, 0.0]),
+        ([-0.5, -0.5, -0.5], [1.0, 0.0]),
+        ([-0.5, 0.5, -0.5], [1.0, 1.0]),
+        ([0.5, 0.5, -0.5], [0.0, 1.0]),
+        // Left face
+        ([-0.5, -0.5, -0.5], [0.0, 0.0]),
+        ([-0.5, -0.5, 0.5], [1.0, 0.0]),
+        ([-0.5, 0.5, 0.5], [1.0, 1.0]),
+        ([-0.5, 0.5, -0.5], [0.0, 1.0]),
+        // Right face
+        ([0.5, -0.5, 0.5], [0.0, 0.0]),
+        ([0.5, -0.5, -0.5], [1.0, 0.0]),
+        ([0.5, 0.5, -0.5], [1.0, 1.0]),
+        ([0.5, 0.5, 0.5], [0.0, 1.0]),
+        // Top face
+        ([-0.5, 0.5, 0.5], [0.0, 0.0]),
+        ([0.5, 0.5, 0.5], [1.0, 0.0]),
+        ([0.5, 0.5, -0.5], [1.0, 1.0]),
+        ([-0.5, 0.5, -0.5], [0.0, 1.0]),
+        // Bottom face
+        ([-0.5, -0.5, -0.5], [0.0, 0.0]),
+        ([0.5, -0.5, -0.5], [1.0, 0.0]),
+        ([0.5, -0.5, 0.5], [1.0, 1.0]),
+        ([-0.5, -0.5, 0.5], [0.0, 1.0]),
+    ];
+
+    // Faces are listed in the order of these normals, 4 vertices each
+    let normals = [
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, -1.0],
+        [-1.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, -1.0, 0.0],
+    ];
+    let vertices: Vec<Vertex> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, (pos, uv))| Vertex {
+            position: *pos,
+            uv: *uv,
+            normal: if inward_facing {
+                normals[i / 4].map(|n: f32| -n)
+            } else {
+                normals[i / 4]
+            },
+        })
+        .collect();
+
+    #[rustfmt::skip]
+    let mut indices: [u8; 36] = [
+        0,  1,  2,  0,  2,  3,  // Front
+        4,  5,  6,  4,  6,  7,  // Back
+        8,  9,  10, 8,  10, 11, // Left
+        12, 13, 14, 12, 14, 15, // Right
+        16, 17, 18, 16, 18, 19, // Top
+        20, 21, 22, 20, 22, 23, // Bottom
+    ];
+
+    // Reverse winding order if inward facing
+    if inward_facing {
+        for tri in indices.chunks_mut(3) {
+            tri.swap(1, 2);
+        }
+    }
+
+    (vertices, indices)
+}
+
+fn write_buffer(
+    ctx: &Graphics,
+    buffer: &wgpu::Buffer,
+    data: &[u8],
+) -> Result<(), ModelUpdateError> {
+    if !buffer.usage().contains(wgpu::BufferUsages::COPY_DST) {
+        return Err(ModelUpdateError::NotCopyDst);
+    }
+    if data.len() as u64 > buffer.size() {
+        return Err(ModelUpdateError::CapacityExceeded {
+            capacity: buffer.size(),
+            requested: data.len() as u64,
+        });
+    }
+    // Writes must be multiples of 4 bytes, as is the buffer size, e.g. an odd number of u16 indices
+    let padding = data
+        .len()
+        .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize)
+        - data.len();
+    if padding == 0 {
+        ctx.queue.write_buffer(buffer, 0, data);
+    } else {
+        let padded = [data, &[0; 4][..padding]].concat();
+        ctx.queue.write_buffer(buffer, 0, &padded);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::maths::Vec3f;
+
+    /// Normal of a counter clockwise triangle
+    fn winding_normal(vertices: &[Vertex], triangle: &[u8]) -> Vec3f {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3f::from(vertices[triangle[i] as usize].position));
+        (b - a).cross(&(c - a)).normalize()
+    }
+
+    #[test]
+    fn cube_front_face_points_towards_z() {
+        let (vertices, indices) = cube_mesh(false);
+        assert_eq!(vertices.len(), 24);
+        for &i in &indices[..6] {
+            assert_eq!(vertices[i as usize].normal, [0.0, 0.0, 1.0]);
+        }
+        // Every triangle winds counter clockwise around its normal
+        for triangle in indices.chunks(3) {
+            let normal = Vec3f::from(vertices[triangle[0] as usize].normal);
+            assert!((winding_normal(&vertices, triangle) - normal).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn inward_cube_flips_normals_and_winding() {
+        let (vertices, indices) = cube_mesh(true);
+        assert_eq!(vertices[indices[0] as usize].normal, [0.0, 0.0, -1.0]);
+        for triangle in indices.chunks(3) {
+            let normal = Vec3f::from(vertices[triangle[0] as usize].normal);
+            assert!((winding_normal(&vertices, triangle) - normal).norm() < 1e-6);
+        }
+    }
+
+    fn quad(size: f32) -> [Vertex; 4] {
+        [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]].map(|[x, y]| Vertex {
+            position: [x * size, y * size, 0.0],
+            uv: [x, y],
+            normal: [0.0, 0.0, 1.0],
+        })
+    }
+
+    #[test]
+    fn updated_quad_vertices_are_in_the_buffer() {
+        let Some(ctx) = Graphics::headless(8, 8) else {
+            return;
+        };
+        let usage = wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+        let mut model = Model::<u16>::new_with_usage(&ctx, &quad(1.0), &[0, 1, 2, 0, 2, 3], usage);
+
+        let vertices = quad(2.0);
+        model.update_vertices(&ctx, &vertices).unwrap();
+        assert_eq!(
+            ctx.read_buffer(&model.vertex_buffer),
+            bytemuck::cast_slice::<_, u8>(&vertices)
+        );
+        assert_eq!(model.bounds().unwrap().max, Vec3f::new(2.0, 2.0, 0.0));
+
+        // An odd number of u16 indices is padded to the copy alignment
+        model.update_indices(&ctx, &[3, 2, 1]).unwrap();
+        let indices = ctx.read_buffer(&model.index_buffer);
+        assert_eq!(bytemuck::cast_slice::<_, u16>(&indices)[..3], [3, 2, 1]);
+
+        assert_eq!(
+            model.update_vertices(&ctx, &[vertices[0]; 5]),
+            Err(ModelUpdateError::CapacityExceeded {
+                capacity: size_of_val(&vertices) as u64,
+                requested: (size_of::<Vertex>() * 5) as u64,
+            })
+        );
+        let mut fixed = Model::<u16>::new(&ctx, &quad(1.0), &[0, 1, 2, 0, 2, 3]);
+        assert_eq!(
+            fixed.update_vertices(&ctx, &vertices),
+            Err(ModelUpdateError::NotCopyDst)
+        );
     }
 }
@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use bytemuck::Pod;
+
+use crate::engine::graphics::{
+    Graphics,
+    model::{Model, Vertex},
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    Parse(tobj::LoadError),
+    /// The file parsed but contains no triangle
+    Empty,
+    /// The vertex count does not fit the index type of the model, use `Model<u32>`
+    TooManyVertices(usize),
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read OBJ file: {e}"),
+            Self::Parse(e) => write!(f, "malformed OBJ file: {e}"),
+            Self::Empty => write!(f, "OBJ file contains no triangle"),
+            Self::TooManyVertices(count) => {
+                write!(f, "{count} vertices do not fit the index type")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<std::io::Error> for ObjError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<tobj::LoadError> for ObjError {
+    fn from(e: tobj::LoadError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl<I: Pod + TryFrom<u32>> Model<I> {
+    /// Load every object of a Wavefront OBJ file as a single model, materials are ignored
+    pub fn from_obj(ctx: &Graphics, path: impl AsRef<Path>) -> Result<Self, ObjError> {
+        Self::from_obj_bytes(ctx, &std::fs::read(path)?)
+    }
+
+    /// Same as `from_obj`, from the content of the file, e.g. with `include_bytes!`
+    pub fn from_obj_bytes(ctx: &Graphics, bytes: &[u8]) -> Result<Self, ObjError> {
+        let (vertices, indices) = parse_obj(bytes)?;
+        let indices = indices
+            .into_iter()
+            .map(I::try_from)
+            .collect::<Result<Vec<I>, _>>()
+            .map_err(|_| ObjError::TooManyVertices(vertices.len()))?;
+        Ok(Self::new(ctx, &vertices, &indices))
+    }
+}
+
+/// Polygons are triangulated and identical position/uv/normal tuples share a vertex
+fn parse_obj(bytes: &[u8]) -> Result<(Vec<Vertex>, Vec<u32>), ObjError> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ignore_points: true,
+        ignore_lines: true,
+    };
+    let (models, _) = tobj::load_obj_buf(&mut &bytes[..], &options, |_| Ok(Default::default()))?;
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for mesh in models.into_iter().map(|model| model.mesh) {
+        let count = mesh.positions.len() / 3;
+        let has_uvs = mesh.texcoords.len() / 2 == count;
+        let has_normals = mesh.normals.len() / 3 == count;
+
+        let mut mesh_vertices: Vec<_> = (0..count)
+            .map(|i| Vertex {
+                position: [0, 1, 2].map(|j| mesh.positions[i * 3 + j]),
+                // OBJ texture coordinates start at the bottom left
+                uv: if has_uvs {
+                    [mesh.texcoords[i * 2], 1.0 - mesh.texcoords[i * 2 + 1]]
+                } else {
+                    [0.0; 2]
+                },
+                normal: if has_normals {
+                    [0, 1, 2].map(|j| mesh.normals[i * 3 + j])
+                } else {
+                    [0.0; 3]
+                },
+            })
+            .collect();
+        if !has_normals {
+            Model::compute_flat_normals(&mut mesh_vertices, &mesh.indices);
+        }
+
+        let offset = vertices.len() as u32;
+        vertices.extend(mesh_vertices);
+        indices.extend(mesh.indices.iter().map(|i| i + offset));
+    }
+
+    if indices.is_empty() {
+        return Err(ObjError::Empty);
+    }
+    Ok((vertices, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE: &str = "\
+v -1 -1  1
+v  1 -1  1
+v  1  1  1
+v -1  1  1
+v -1 -1 -1
+v  1 -1 -1
+v  1  1 -1
+v -1  1 -1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+vn 0 0 -1
+vn 1 0 0
+vn -1 0 0
+vn 0 1 0
+vn 0 -1 0
+f 1/1/1 2/2/1 3/3/1 4/4/1
+f 6/1/2 5/2/2 8/3/2 7/4/2
+f 2/1/3 6/2/3 7/3/3 3/4/3
+f 5/1/4 1/2/4 4/3/4 8/4/4
+f 4/1/5 3/2/5 7/3/5 8/4/5
+f 5/1/6 6/2/6 2/3/6 1/4/6
+";
+
+    #[test]
+    fn cube_has_four_vertices_per_face() {
+        let (vertices, indices) = parse_obj(CUBE.as_bytes()).unwrap();
+        // Corners are split by their normal, the quads are triangulated
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(indices.len(), 36);
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+        // The uv origin moves to the top left
+        assert_eq!(vertices[indices[0] as usize].uv, [0.0, 1.0]);
+        assert_eq!(vertices[indices[0] as usize].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn lines_only_is_empty() {
+        let obj = "v 0 0 0\nv 1 0 0\nl 1 2\n";
+        assert!(matches!(parse_obj(obj.as_bytes()), Err(ObjError::Empty)));
+    }
+}
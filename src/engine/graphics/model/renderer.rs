@@ -1,7 +1,25 @@
-use slotmap::{SecondaryMap, SlotMap, basic::Values};
+use std::collections::HashMap;
+
+use slotmap::{SecondaryMap, SlotMap};
 use wgpu::RenderPass;
 
-use crate::engine::graphics::{Frame, Graphics, camera::CameraUniform, model::Model};
+#[cfg(debug_assertions)]
+use crate::engine::graphics::gizmos::{GizmoRenderer, Gizmos};
+use crate::engine::{
+    graphics::{
+        Frame, Graphics,
+        camera::CameraUniform,
+        color::Color3f,
+        line::Grid,
+        model::{
+            Model,
+            texture::{ModelTexture, TextureUniform},
+        },
+        picking::Picker,
+        skybox::Skybox,
+    },
+    maths::{Aabb, Frustum, Mat4f, Vec2f, Vec2u, Vec3f},
+};
 
 slotmap::new_key_type! { pub struct MaterialId; }
 slotmap::new_key_type! { pub struct PerMaterialModelId; }
@@ -12,9 +30,221 @@ pub struct ModelId {
     pub material_id: MaterialId,
 }
 
-pub type ModelsIter<'a> = Values<'a, PerMaterialModelId, Model>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneGraphError {
+    ModelNotFound(ModelId),
+    /// The parent is the model itself or one of its descendants
+    Cycle {
+        child: ModelId,
+        parent: ModelId,
+    },
+}
+
+impl std::fmt::Display for SceneGraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ModelNotFound(id) => write!(f, "model {id:?} not found"),
+            Self::Cycle { child, parent } => write!(
+                f,
+                "parenting {child:?} to {parent:?} would make it its own ancestor"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneGraphError {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Opaque, overwrites the target
+    #[default]
+    Replace,
+    /// Classic transparency using the source alpha
+    Alpha,
+    /// Adds to the target, for glows and particles
+    Additive,
+    /// Multiplies the target, for darkening decals
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn blend_state(&self) -> wgpu::BlendState {
+        use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState};
+
+        match self {
+            Self::Replace => BlendState::REPLACE,
+            Self::Alpha => BlendState::ALPHA_BLENDING,
+            Self::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+            Self::Multiply => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::Dst,
+                    dst_factor: BlendFactor::Zero,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+        }
+    }
+
+    /// Only opaque materials should write depth, blended ones would hide what is behind them
+    pub fn writes_depth(&self) -> bool {
+        *self == Self::Replace
+    }
+}
+
+/// Order in which a material receives its models
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DrawOrder {
+    /// Order of insertion in the material, free as nothing is sorted
+    #[default]
+    Insertion,
+    /// Ascending `ModelLod::sort_key`, for explicit layering
+    SortKey,
+    /// Farthest from the camera first, for blended materials
+    BackToFront,
+}
+
+impl DrawOrder {
+    /// Key of a model `distance` away from the camera, models are drawn by ascending key
+    fn key(self, sort_key: f32, distance: f32) -> f32 {
+        match self {
+            DrawOrder::Insertion => 0.0,
+            DrawOrder::SortKey => sort_key,
+            DrawOrder::BackToFront => -distance,
+        }
+    }
+}
+
+/// Sort the keyed models from `DrawOrder::key`. Stable, equal keys keep the insertion order
+fn sort_draws<T>(order: DrawOrder, models: &mut [(f32, T)]) {
+    if order != DrawOrder::Insertion {
+        models.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    }
+}
+
+/// Meshes to draw this frame, with the level of detail already selected
+pub type ModelsIter<'a> = std::vec::IntoIter<&'a Model>;
+
+pub struct LodLevel {
+    /// The level is used while the camera is closer than this distance
+    pub max_distance: f32,
+    pub model: Model,
+}
+
+/// Several versions of a model, from the most to the least detailed
+pub struct ModelLod {
+    levels: Vec<LodLevel>,
+    /// Culling state of the last render
+    visible: bool,
+    /// Position in the draw order of materials using `DrawOrder::SortKey`
+    pub sort_key: f32,
+}
+
+impl ModelLod {
+    pub fn new(mut levels: Vec<LodLevel>) -> Self {
+        assert!(!levels.is_empty(), "A ModelLod needs at least one level");
+        levels.sort_by(|a, b| a.max_distance.total_cmp(&b.max_distance));
+        Self {
+            levels,
+            visible: false,
+            sort_key: 0.0,
+        }
+    }
+
+    pub fn single(model: Model) -> Self {
+        Self::new(vec![LodLevel {
+            max_distance: f32::INFINITY,
+            model,
+        }])
+    }
+
+    pub fn levels(&self) -> &[LodLevel] {
+        &self.levels
+    }
+
+    /// Reference point for the camera distance, the translation of the model transform
+    pub fn position(&self) -> Vec3f {
+        self.levels[0]
+            .model
+            .transform()
+            .fixed_view::<3, 1>(0, 3)
+            .into()
+    }
+
+    pub fn set_transform(&mut self, transform: Mat4f) {
+        for level in &mut self.levels {
+            level.model.set_transform(transform);
+        }
+    }
+
+    /// Index of the level to use at the given distance, the last level is used past every threshold
+    pub fn select(&self, distance: f32) -> usize {
+        select_lod(self.levels.iter().map(|l| l.max_distance), distance)
+    }
+}
+
+/// Index of the first ascending `max_distances` above `distance`, the last one past every threshold
+fn select_lod(mut max_distances: impl ExactSizeIterator<Item = f32>, distance: f32) -> usize {
+    let last = max_distances.len() - 1;
+    max_distances.position(|d| distance < d).unwrap_or(last)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RenderStats {
+    pub models_drawn: u32,
+    /// Models skipped by frustum culling
+    pub models_culled: u32,
+    /// Models that appeared or disappeared because of culling this frame
+    pub visibility_changes: u32,
+    /// Number of models drawn at each level of detail
+    pub lod_histogram: Vec<u32>,
+}
 
 pub trait MaterialRenderer {
+    fn name(&self) -> &str {
+        "Material"
+    }
+
+    /// Called before the scene render pass begins with the models about to be drawn,
+    /// to record uploads or other passes in the frame encoder
+    fn prepare(
+        &mut self,
+        _ctx: &Graphics,
+        _frame: &mut Frame,
+        _camera_uniform: &CameraUniform,
+        _models: ModelsIter,
+    ) {
+    }
+
+    /// Blended materials are drawn after the opaque ones, so they blend over the whole scene.
+    /// Their pipeline should not write depth, see `BlendMode::writes_depth`.
+    fn is_transparent(&self) -> bool {
+        false
+    }
+
+    /// Back to front for transparent materials so the farthest models are blended first
+    fn draw_order(&self) -> DrawOrder {
+        if self.is_transparent() {
+            DrawOrder::BackToFront
+        } else {
+            DrawOrder::Insertion
+        }
+    }
+
+    /// Pipelines must be recreated with the new `Graphics::sample_count`
+    fn on_sample_count_changed(&mut self, _ctx: &Graphics) {}
+
+    /// Settings shown in the editor materials panel
+    #[cfg(debug_assertions)]
+    fn ui(&mut self, _ui: &mut egui::Ui) {}
+
     fn render(
         &mut self,
         ctx: &Graphics,
@@ -26,19 +256,51 @@ pub trait MaterialRenderer {
 
 pub struct ModelRenderer {
     materials: SlotMap<MaterialId, Box<dyn MaterialRenderer>>,
-    meshes: SecondaryMap<MaterialId, SlotMap<PerMaterialModelId, Model>>,
+    meshes: SecondaryMap<MaterialId, SlotMap<PerMaterialModelId, ModelLod>>,
+    /// Parent and local transform of the models with a parent, see `set_parent`
+    parents: HashMap<ModelId, (ModelId, Mat4f)>,
+    stats: RenderStats,
+    /// Skip models whose bounds are outside the camera frustum
+    pub culling_enabled: bool,
+    /// Distance in world units past the frustum before a visible model is culled, a model
+    /// becomes visible again as soon as it enters the frustum. Prevents flickering at the edges.
+    pub culling_margin: f32,
+    skybox: Option<Skybox>,
+    grid: Option<Grid>,
+    /// Created by the first `pick`
+    picker: Option<Picker>,
+    /// Debug lines drawn by the next render, in the same pass as the scene
+    #[cfg(debug_assertions)]
+    pub gizmos: Gizmos,
+    #[cfg(debug_assertions)]
+    gizmo_renderer: GizmoRenderer,
 
     depth_texture: wgpu::Texture,
     depth_texture_view: wgpu::TextureView,
+    /// Multisampled color attachment resolved into the frame, None without MSAA
+    msaa_view: Option<wgpu::TextureView>,
 }
 
 impl ModelRenderer {
-    pub fn new(ctx: &Graphics, _camera_uniform: &CameraUniform) -> Self {
-        let (depth_texture, depth_texture_view) = create_depth_texture(ctx);
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    pub fn new(ctx: &Graphics, camera_uniform: &CameraUniform) -> Self {
+        let (depth_texture, depth_texture_view) = create_depth_texture(ctx, ctx.viewport_size);
 
         Self {
+            msaa_view: create_msaa_view(ctx, ctx.viewport_size),
             materials: SlotMap::default(),
             meshes: SecondaryMap::default(),
+            parents: HashMap::new(),
+            stats: RenderStats::default(),
+            culling_enabled: true,
+            culling_margin: 0.5,
+            skybox: None,
+            grid: None,
+            picker: None,
+            #[cfg(debug_assertions)]
+            gizmos: Gizmos::default(),
+            #[cfg(debug_assertions)]
+            gizmo_renderer: GizmoRenderer::new(ctx, camera_uniform),
 
             depth_texture,
             depth_texture_view,
@@ -52,50 +314,468 @@ impl ModelRenderer {
     }
 
     pub fn add_model(&mut self, mesh: Model, material_id: MaterialId) -> ModelId {
+        self.add_model_lod(ModelLod::single(mesh), material_id)
+    }
+
+    pub fn add_model_lod(&mut self, lod: ModelLod, material_id: MaterialId) -> ModelId {
         ModelId {
             per_material_id: self
                 .meshes
                 .get_mut(material_id)
                 .expect("Material not found")
-                .insert(mesh),
+                .insert(lod),
             material_id,
         }
     }
 
+    /// Drop the model and its GPU buffers, false when it was already removed.
+    /// Its children stay where they are, without a parent.
+    pub fn remove_model(&mut self, model_id: ModelId) -> bool {
+        let removed = self
+            .meshes
+            .get_mut(model_id.material_id)
+            .and_then(|models| models.remove(model_id.per_material_id))
+            .is_some();
+        self.parents.remove(&model_id);
+        self.parents.retain(|_, (parent, _)| *parent != model_id);
+        removed
+    }
+
+    /// Drop the material along with every model using it, false when it was already removed
+    pub fn remove_material(&mut self, material_id: MaterialId) -> bool {
+        self.meshes.remove(material_id);
+        self.parents.retain(|child, (parent, _)| {
+            child.material_id != material_id && parent.material_id != material_id
+        });
+        self.materials.remove(material_id).is_some()
+    }
+
+    /// Most detailed level of the model, None once removed
+    pub fn get_model(&self, model_id: ModelId) -> Option<&Model> {
+        self.get_model_lod(model_id).map(|lod| &lod.levels[0].model)
+    }
+
+    /// Most detailed level of the model, None once removed. Changes to its transform or
+    /// instances are uploaded on the next render.
+    pub fn get_model_mut(&mut self, model_id: ModelId) -> Option<&mut Model> {
+        self.get_model_lod_mut(model_id)
+            .map(|lod| &mut lod.levels[0].model)
+    }
+
+    pub fn get_model_lod(&self, model_id: ModelId) -> Option<&ModelLod> {
+        self.meshes
+            .get(model_id.material_id)
+            .and_then(|models| models.get(model_id.per_material_id))
+    }
+
+    pub fn get_model_lod_mut(&mut self, model_id: ModelId) -> Option<&mut ModelLod> {
+        self.meshes
+            .get_mut(model_id.material_id)
+            .and_then(|models| models.get_mut(model_id.per_material_id))
+    }
+
+    /// Move a model relative to its parent, or to the world without one, along with its
+    /// children. Applied on the next render.
+    pub fn set_transform(&mut self, model_id: ModelId, transform: impl Into<Mat4f>) {
+        let transform = transform.into();
+        let world = match self.parents.get_mut(&model_id) {
+            Some((parent, local)) => {
+                *local = transform;
+                let parent = *parent;
+                self.world_transform(parent).expect("Parent not found") * transform
+            }
+            None => transform,
+        };
+        self.set_world_transform(model_id, world);
+    }
+
+    /// Attach the model to `parent`, its transform becoming relative to the parent, or detach it
+    /// with None. The model stays in place either way. Fails when the parent is the model or one
+    /// of its descendants.
+    pub fn set_parent(
+        &mut self,
+        model_id: ModelId,
+        parent: Option<ModelId>,
+    ) -> Result<(), SceneGraphError> {
+        let world = self
+            .world_transform(model_id)
+            .ok_or(SceneGraphError::ModelNotFound(model_id))?;
+        let Some(parent) = parent else {
+            self.parents.remove(&model_id);
+            return Ok(());
+        };
+        if self.get_model_lod(parent).is_none() {
+            return Err(SceneGraphError::ModelNotFound(parent));
+        }
+        let mut ancestor = Some(parent);
+        while let Some(id) = ancestor {
+            if id == model_id {
+                return Err(SceneGraphError::Cycle {
+                    child: model_id,
+                    parent,
+                });
+            }
+            ancestor = self.parent(id);
+        }
+
+        let parent_world = self.world_transform(parent).expect("Parent not found");
+        self.parents
+            .insert(model_id, (parent, local_transform(&parent_world, &world)));
+        Ok(())
+    }
+
+    pub fn parent(&self, model_id: ModelId) -> Option<ModelId> {
+        self.parents.get(&model_id).map(|(parent, _)| *parent)
+    }
+
+    /// Transform of the model with its parents applied, None once removed
+    pub fn world_transform(&self, model_id: ModelId) -> Option<Mat4f> {
+        self.get_model(model_id).map(|model| *model.transform())
+    }
+
+    fn set_world_transform(&mut self, model_id: ModelId, world: Mat4f) {
+        self.get_model_lod_mut(model_id)
+            .expect("Model not found")
+            .set_transform(world);
+        let children: Vec<(ModelId, Mat4f)> = self
+            .parents
+            .iter()
+            .filter(|(_, (parent, _))| *parent == model_id)
+            .map(|(child, (_, local))| (*child, *local))
+            .collect();
+        for (child, local) in children {
+            self.set_world_transform(child, world * local);
+        }
+    }
+
+    /// Order of the model within its material, see `DrawOrder::SortKey`
+    pub fn set_sort_key(&mut self, model_id: ModelId, sort_key: f32) {
+        self.get_model_lod_mut(model_id)
+            .expect("Model not found")
+            .sort_key = sort_key;
+    }
+
+    /// World bounds of every model with a known extent, e.g. to pick a camera focus
+    pub fn world_bounds(&self) -> impl Iterator<Item = &Aabb> {
+        self.meshes
+            .values()
+            .flat_map(|lods| lods.values())
+            .filter_map(|lod| lod.levels[0].model.world_bounds())
+    }
+
+    pub fn stats(&self) -> &RenderStats {
+        &self.stats
+    }
+
     pub fn render(&mut self, ctx: &Graphics, frame: &mut Frame, camera_uniform: &CameraUniform) {
-        let mut render_pass = create_render_pass(frame, &self.depth_texture_view);
+        let attachments = Attachments {
+            view: frame.view.clone(),
+            msaa_view: self.msaa_view.clone(),
+            depth_view: self.depth_texture_view.clone(),
+        };
+        self.render_into(ctx, frame, camera_uniform, attachments, false);
+    }
 
-        for (material_id, material) in &mut self.materials {
-            material.render(
+    /// Draw the scene into the target instead of the frame, for minimaps or reflections.
+    ///
+    /// The uniform buffers are written before the frame is submitted, so the target needs its
+    /// own `CameraUniform` to be seen from another camera than the frame. The culling state,
+    /// `stats` and gizmos are left to the frame render.
+    pub fn render_to(
+        &mut self,
+        ctx: &Graphics,
+        frame: &mut Frame,
+        camera_uniform: &CameraUniform,
+        target: &mut RenderTarget,
+    ) {
+        target.sync_sample_count(ctx);
+        let attachments = Attachments {
+            view: target.color.view.clone(),
+            msaa_view: target.msaa_view.clone(),
+            depth_view: target.depth_view.clone(),
+        };
+        self.render_into(ctx, frame, camera_uniform, attachments, true);
+    }
+
+    /// `offscreen` renders see the scene from another camera than the frame, so they cull
+    /// without touching the hysteresis of the frame and keep their statistics to themselves
+    fn render_into(
+        &mut self,
+        ctx: &Graphics,
+        frame: &mut Frame,
+        camera_uniform: &CameraUniform,
+        attachments: Attachments,
+        offscreen: bool,
+    ) {
+        let frustum = Frustum::from_view_proj(camera_uniform.view_proj());
+        let (culling, margin) = (self.culling_enabled, self.culling_margin);
+        let in_frustum = |lod: &ModelLod| match lod.levels[0].model.world_bounds() {
+            Some(bounds) if culling => frustum.intersects_aabb(bounds, 0.0),
+            _ => true,
+        };
+        let mut offscreen_stats = RenderStats::default();
+        let stats = if offscreen {
+            &mut offscreen_stats
+        } else {
+            &mut self.stats
+        };
+        stats.models_drawn = 0;
+        stats.models_culled = 0;
+        stats.visibility_changes = 0;
+        stats.lod_histogram.iter_mut().for_each(|c| *c = 0);
+
+        for lods in self.meshes.values_mut() {
+            for lod in lods.values_mut() {
+                for level in &mut lod.levels {
+                    level.model.sync(ctx);
+                }
+                if offscreen {
+                    continue;
+                }
+
+                let visible = match lod.levels[0].model.world_bounds() {
+                    _ if !culling => true,
+                    Some(bounds) if lod.visible => frustum.intersects_aabb(bounds, margin),
+                    Some(bounds) => frustum.intersects_aabb(bounds, 0.0),
+                    None => true,
+                };
+                if visible != lod.visible {
+                    stats.visibility_changes += 1;
+                    lod.visible = visible;
+                }
+                if !visible {
+                    stats.models_culled += 1;
+                }
+            }
+        }
+
+        let mut draws: Vec<(MaterialId, Vec<&Model>)> = self
+            .meshes
+            .iter()
+            .map(|(material_id, lods)| {
+                let order = self.materials[material_id].draw_order();
+                let mut models: Vec<_> = lods
+                    .values()
+                    .filter(|lod| {
+                        if offscreen {
+                            in_frustum(lod)
+                        } else {
+                            lod.visible
+                        }
+                    })
+                    .map(|lod| {
+                        let distance = (lod.position() - camera_uniform.position).norm();
+                        let level = lod.select(distance);
+                        if stats.lod_histogram.len() <= level {
+                            stats.lod_histogram.resize(level + 1, 0);
+                        }
+                        stats.lod_histogram[level] += 1;
+                        stats.models_drawn += 1;
+                        let key = order.key(lod.sort_key, distance);
+                        (key, &lod.levels[level].model)
+                    })
+                    .collect();
+                sort_draws(order, &mut models);
+                (material_id, models.into_iter().map(|(_, m)| m).collect())
+            })
+            .collect();
+        // Stable, opaque materials keep their registration order
+        draws.sort_by_key(|(material_id, _)| self.materials[*material_id].is_transparent());
+
+        for (material_id, models) in &draws {
+            self.materials[*material_id].prepare(
+                ctx,
+                frame,
+                camera_uniform,
+                models.clone().into_iter(),
+            );
+        }
+        #[cfg(debug_assertions)]
+        if !offscreen {
+            self.gizmo_renderer.prepare(ctx, frame, &mut self.gizmos);
+        }
+
+        let mut render_pass = create_render_pass(&mut frame.encoder, &attachments, ctx.clear_color);
+        if let Some(skybox) = &self.skybox {
+            skybox.render(&mut render_pass, camera_uniform);
+        }
+        for (material_id, models) in draws {
+            self.materials[material_id].render(
                 ctx,
                 &mut render_pass,
                 camera_uniform,
-                self.meshes.get(material_id).unwrap().values(),
+                models.into_iter(),
             );
         }
+        // After the opaque models so they hide it
+        if let Some(grid) = &mut self.grid {
+            grid.render(ctx, &mut render_pass, camera_uniform);
+        }
+        #[cfg(debug_assertions)]
+        if !offscreen {
+            self.gizmo_renderer.render(&mut render_pass, camera_uniform);
+        }
+    }
+
+    /// Model drawn under the cursor, in pixels from the top left, among the models visible in
+    /// the last render. Pixel perfect but blocks until the GPU is done, so only call it on clicks.
+    pub fn pick(
+        &mut self,
+        ctx: &Graphics,
+        camera_uniform: &CameraUniform,
+        cursor: Vec2f,
+    ) -> Option<ModelId> {
+        let picker = self
+            .picker
+            .get_or_insert_with(|| Picker::new(ctx, camera_uniform));
+        let (ids, models): (Vec<ModelId>, Vec<&Model>) = self
+            .meshes
+            .iter()
+            .flat_map(|(material_id, lods)| {
+                lods.iter()
+                    .filter(|(_, lod)| lod.visible)
+                    .map(move |(per_material_id, lod)| {
+                        let distance = (lod.position() - camera_uniform.position).norm();
+                        let id = ModelId {
+                            per_material_id,
+                            material_id,
+                        };
+                        (id, &lod.levels[lod.select(distance)].model)
+                    })
+            })
+            .unzip();
+        picker
+            .pick(ctx, camera_uniform, cursor, &models)
+            .map(|index| ids[index])
+    }
+
+    /// Drawn before every material, replacing the clear color
+    pub fn set_skybox(&mut self, skybox: Option<Skybox>) {
+        self.skybox = skybox;
+    }
+
+    /// Drawn after every material, see `Renderer::set_grid_visible`
+    pub fn set_grid(&mut self, grid: Option<Grid>) {
+        self.grid = grid;
+    }
+
+    pub fn grid(&self) -> Option<&Grid> {
+        self.grid.as_ref()
+    }
+
+    /// Recreate the attachments and pipelines after `Graphics::sample_count` changed
+    pub fn on_sample_count_changed(&mut self, ctx: &Graphics, camera_uniform: &CameraUniform) {
+        self.on_resize(ctx);
+        if let Some(skybox) = &mut self.skybox {
+            skybox.on_sample_count_changed(ctx, camera_uniform);
+        }
+        if let Some(grid) = &mut self.grid {
+            grid.on_sample_count_changed(ctx);
+        }
+        #[cfg(debug_assertions)]
+        self.gizmo_renderer.on_sample_count_changed(ctx);
+        for material in self.materials.values_mut() {
+            material.on_sample_count_changed(ctx);
+        }
+    }
+
+    /// Registered materials, to tweak them after registration such as from the editor
+    pub fn materials_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (MaterialId, &mut Box<dyn MaterialRenderer>)> {
+        self.materials.iter_mut()
+    }
+
+    /// Scene depth of the last render, sampleable by later passes such as soft particles.
+    /// Multisampled when `Graphics::sample_count` is above 1.
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_texture_view
+    }
+
+    pub fn depth_sample_count(&self) -> u32 {
+        self.depth_texture.sample_count()
     }
 
     pub fn on_resize(&mut self, ctx: &Graphics) {
-        let (depth_texture, depth_texture_view) = create_depth_texture(ctx);
+        let (depth_texture, depth_texture_view) = create_depth_texture(ctx, ctx.viewport_size);
         self.depth_texture = depth_texture;
         self.depth_texture_view = depth_texture_view;
+        self.msaa_view = create_msaa_view(ctx, ctx.viewport_size);
     }
 }
 
-fn create_depth_texture(ctx: &Graphics) -> (wgpu::Texture, wgpu::TextureView) {
+/// Offscreen color texture and its depth that `ModelRenderer::render_to` draws the scene into,
+/// sized independently of the window.
+pub struct RenderTarget {
+    pub color: ModelTexture,
+    texture_uniform: TextureUniform,
+    /// Multisampled color attachment resolved into `color`, None without MSAA
+    msaa_view: Option<wgpu::TextureView>,
+    depth_view: wgpu::TextureView,
+    sample_count: u32,
+}
+
+impl RenderTarget {
+    pub fn new(ctx: &Graphics, width: u32, height: u32) -> Self {
+        let color = ModelTexture::render_target(ctx, width, height, "Render Target");
+        let size = Vec2u::new(color.texture.width(), color.texture.height());
+        Self {
+            texture_uniform: TextureUniform::new(ctx, &color),
+            msaa_view: create_msaa_view(ctx, size),
+            depth_view: create_depth_texture(ctx, size).1,
+            sample_count: ctx.sample_count,
+            color,
+        }
+    }
+
+    /// The rendered image, to sample from another material like any model texture
+    pub fn texture_uniform(&self) -> &TextureUniform {
+        &self.texture_uniform
+    }
+
+    pub fn size(&self) -> Vec2u {
+        Vec2u::new(self.color.texture.width(), self.color.texture.height())
+    }
+
+    /// Aspect ratio to give the camera rendering into the target
+    pub fn aspect_ratio(&self) -> f32 {
+        let size = self.size();
+        size.x as f32 / size.y as f32
+    }
+
+    /// The pipelines follow `Graphics::sample_count`, so must the attachments
+    fn sync_sample_count(&mut self, ctx: &Graphics) {
+        if self.sample_count != ctx.sample_count {
+            let size = self.size();
+            self.msaa_view = create_msaa_view(ctx, size);
+            self.depth_view = create_depth_texture(ctx, size).1;
+            self.sample_count = ctx.sample_count;
+        }
+    }
+}
+
+/// Views the scene pass draws into
+struct Attachments {
+    view: wgpu::TextureView,
+    msaa_view: Option<wgpu::TextureView>,
+    depth_view: wgpu::TextureView,
+}
+
+/// Depth attachment matching `Graphics::sample_count`, the color attachment must use the same count
+fn create_depth_texture(ctx: &Graphics, size: Vec2u) -> (wgpu::Texture, wgpu::TextureView) {
     let size = wgpu::Extent3d {
-        width: ctx.viewport_size.x,
-        height: ctx.viewport_size.y,
+        width: size.x,
+        height: size.y,
         depth_or_array_layers: 1,
     };
     let desc = wgpu::TextureDescriptor {
         label: Some("Depth Texture"),
         size,
         mip_level_count: 1,
-        sample_count: 1,
+        sample_count: ctx.sample_count,
         dimension: wgpu::TextureDimension::D2,
         format: wgpu::TextureFormat::Depth32Float,
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
         view_formats: &[],
     };
     let texture = ctx.device.create_texture(&desc);
@@ -103,30 +783,336 @@ fn create_depth_texture(ctx: &Graphics) -> (wgpu::Texture, wgpu::TextureView) {
     (texture, view)
 }
 
+fn create_msaa_view(ctx: &Graphics, size: Vec2u) -> Option<wgpu::TextureView> {
+    (ctx.sample_count > 1).then(|| {
+        ctx.device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("MSAA Color Texture"),
+                size: wgpu::Extent3d {
+                    width: size.x.max(1),
+                    height: size.y.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: ctx.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: ctx.surface_format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    })
+}
+
 fn create_render_pass<'a>(
-    frame: &'a mut Frame,
-    depth_texture_view: &'a wgpu::TextureView,
+    encoder: &'a mut wgpu::CommandEncoder,
+    attachments: &'a Attachments,
+    clear_color: Color3f,
 ) -> wgpu::RenderPass<'a> {
-    frame
-        .encoder
-        .begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Editor debug ui renderpass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &frame.view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    store: wgpu::StoreOp::Store,
-                    load: wgpu::LoadOp::Load,
+    // With MSAA the samples are drawn offscreen then resolved into the target
+    let (view, resolve_target) = match &attachments.msaa_view {
+        Some(msaa_view) => (msaa_view, Some(&attachments.view)),
+        None => (&attachments.view, None),
+    };
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Editor debug ui renderpass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations {
+                store: wgpu::StoreOp::Store,
+                // First pass of the frame, later passes load its output
+                load: wgpu::LoadOp::Clear(clear_color.into()),
+            },
+        })],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: &attachments.depth_view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }),
+        ..Default::default()
+    })
+}
+
+/// Transform relative to the parent keeping the child at `child_world`. Falls back to the world
+/// transform when the parent is not invertible, such as scaled to zero.
+fn local_transform(parent_world: &Mat4f, child_world: &Mat4f) -> Mat4f {
+    parent_world
+        .try_inverse()
+        .map_or(*child_world, |inverse| inverse * child_world)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::graphics::color::Color3f;
+
+    #[test]
+    fn lod_is_selected_by_camera_distance() {
+        let thresholds = [10.0, 30.0, f32::INFINITY];
+        let select = |distance| select_lod(thresholds.into_iter(), distance);
+        assert_eq!(select(0.0), 0);
+        assert_eq!(select(9.9), 0);
+        assert_eq!(select(10.0), 1);
+        assert_eq!(select(29.0), 1);
+        assert_eq!(select(1000.0), 2);
+
+        // Past the last finite threshold the least detailed level is kept
+        assert_eq!(select_lod([10.0, 30.0].into_iter(), 100.0), 1);
+    }
+
+    #[test]
+    fn attached_child_keeps_its_world_position() {
+        use crate::engine::maths::{Quatf, Transform};
+
+        let parent_world = Transform::new(
+            Vec3f::new(10.0, 0.0, 0.0),
+            Quatf::from_euler_angles(0.0, 1.0, 0.0),
+            Vec3f::repeat(2.0),
+        )
+        .to_matrix();
+        let child_world = Mat4f::new_translation(&Vec3f::new(1.0, 2.0, 3.0));
+
+        let local = local_transform(&parent_world, &child_world);
+        let position = |world: Mat4f| Transform::from_matrix(&world).translation;
+        assert!((position(parent_world * local) - Vec3f::new(1.0, 2.0, 3.0)).norm() < 1e-5);
+
+        // The child then follows the parent
+        let moved = Mat4f::new_translation(&Vec3f::new(0.0, 5.0, 0.0)) * parent_world;
+        assert!((position(moved * local) - Vec3f::new(1.0, 7.0, 3.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn blended_models_are_drawn_back_to_front() {
+        // (name, sort key, distance to the camera)
+        let models = [("near", 0.0, 1.0), ("far", 2.0, 9.0), ("middle", 1.0, 4.0)];
+        let sorted = |order: DrawOrder| {
+            let mut keyed: Vec<_> = models
+                .iter()
+                .map(|&(name, sort_key, distance)| (order.key(sort_key, distance), name))
+                .collect();
+            sort_draws(order, &mut keyed);
+            keyed.into_iter().map(|(_, name)| name).collect::<Vec<_>>()
+        };
+        assert_eq!(sorted(DrawOrder::BackToFront), ["far", "middle", "near"]);
+        assert_eq!(sorted(DrawOrder::SortKey), ["near", "middle", "far"]);
+        assert_eq!(sorted(DrawOrder::Insertion), ["near", "far", "middle"]);
+    }
+
+    struct NoopMaterial;
+
+    impl MaterialRenderer for NoopMaterial {
+        fn render(&mut self, _: &Graphics, _: &mut RenderPass, _: &CameraUniform, _: ModelsIter) {}
+    }
+
+    /// Skipped without a graphics adapter
+    fn headless_renderer() -> Option<(Graphics, ModelRenderer)> {
+        let ctx = Graphics::headless(64, 64)?;
+        let renderer = ModelRenderer::new(&ctx, &CameraUniform::new(&ctx));
+        Some((ctx, renderer))
+    }
+
+    #[test]
+    fn removed_models_and_materials_are_gone() {
+        let Some((ctx, mut renderer)) = headless_renderer() else {
+            return;
+        };
+        let material = renderer.add_material(Box::new(NoopMaterial));
+        let model = renderer.add_model(Model::cube(&ctx, false), material);
+        assert_eq!(renderer.world_bounds().count(), 1);
+
+        assert!(renderer.remove_model(model));
+        assert!(renderer.get_model(model).is_none());
+        assert_eq!(renderer.world_bounds().count(), 0);
+        assert!(!renderer.remove_model(model));
+
+        // Removing a material drops its models too
+        let model = renderer.add_model(Model::cube(&ctx, false), material);
+        assert!(renderer.remove_material(material));
+        assert!(renderer.get_model(model).is_none());
+        assert_eq!(renderer.world_bounds().count(), 0);
+        assert!(!renderer.remove_material(material));
+        assert!(!renderer.remove_model(model));
+    }
+
+    #[test]
+    fn mutating_a_retrieved_model_is_reflected() {
+        let Some((mut ctx, mut renderer)) = headless_renderer() else {
+            return;
+        };
+        let camera_uniform = CameraUniform::new(&ctx);
+        let material = renderer.add_material(Box::new(NoopMaterial));
+        let model = renderer.add_model(Model::cube(&ctx, false), material);
+        let other = renderer.add_model(Model::cube(&ctx, false), material);
+
+        let transform = Mat4f::new_translation(&Vec3f::new(1.0, 2.0, 3.0));
+        renderer
+            .get_model_mut(model)
+            .unwrap()
+            .set_transform(transform);
+        assert_eq!(*renderer.get_model(model).unwrap().transform(), transform);
+        assert_eq!(
+            *renderer.get_model(other).unwrap().transform(),
+            Mat4f::identity()
+        );
+
+        // The bounds follow once the transform is uploaded by a render
+        let mut frame = ctx.next_frame().unwrap();
+        renderer.render(&ctx, &mut frame, &camera_uniform);
+        ctx.present(frame);
+        let bounds = renderer.get_model(model).unwrap().world_bounds().unwrap();
+        assert!((bounds.center() - Vec3f::new(1.0, 2.0, 3.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn depth_texture_follows_the_sample_count() {
+        let Some((mut ctx, mut renderer)) = headless_renderer() else {
+            return;
+        };
+        assert_eq!(renderer.depth_sample_count(), ctx.sample_count);
+
+        for requested in [1, 4] {
+            ctx.sample_count = ctx.capabilities.clamp_sample_count(requested);
+            let (depth_texture, _) = create_depth_texture(&ctx, ctx.viewport_size);
+            assert_eq!(depth_texture.sample_count(), ctx.sample_count);
+
+            renderer.on_resize(&ctx);
+            assert_eq!(renderer.depth_sample_count(), ctx.sample_count);
+        }
+    }
+
+    #[test]
+    fn render_target_is_drawn_into() {
+        let Some((mut ctx, mut renderer)) = headless_renderer() else {
+            return;
+        };
+        let mut target = RenderTarget::new(&ctx, 64, 64);
+        assert_eq!(target.size(), Vec2u::new(64, 64));
+        assert_eq!(target.aspect_ratio(), 1.0);
+
+        ctx.clear_color = Color3f::new(1.0, 0.5, 0.0);
+        let camera_uniform = CameraUniform::new(&ctx);
+        let material = renderer.add_material(Box::new(NoopMaterial));
+        renderer.add_model(Model::cube(&ctx, false), material);
+        let mut frame = ctx.next_frame().unwrap();
+        renderer.render_to(&ctx, &mut frame, &camera_uniform, &mut target);
+        ctx.present(frame);
+
+        let pixels = ctx.read_texture(&target.color.texture);
+        assert_eq!(pixels.len(), 64 * 64 * 4);
+        assert!(
+            pixels
+                .chunks_exact(4)
+                .all(|pixel| pixel == ctx.clear_color.to_srgba_unorm())
+        );
+    }
+
+    /// Center pixel after drawing half transparent red over a dark blue clear color with `blend`
+    fn blended_pixel(ctx: &mut Graphics, blend: BlendMode) -> [u8; 4] {
+        const FULLSCREEN_WGSL: &str = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.5, 0.0, 0.0, 0.5);
+}
+"#;
+        let module = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("blend_test"),
+                source: wgpu::ShaderSource::Wgsl(FULLSCREEN_WGSL.into()),
+            });
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("blend_test"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
                 },
-            })],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: depth_texture_view,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: Some(blend.blend_state()),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
                 }),
-                stencil_ops: None,
-            }),
-            ..Default::default()
-        })
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        let mut frame = ctx.next_frame().unwrap();
+        {
+            let mut render_pass = frame
+                .encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("blend_test"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &frame.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(Color3f::new(0.0, 0.0, 0.5).into()),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+            render_pass.set_pipeline(&pipeline);
+            render_pass.draw(0..3, 0..1);
+        }
+        let texture = frame.texture.clone();
+        ctx.present(frame);
+
+        let pixels = ctx.read_texture(&texture);
+        let center = ((texture.height() / 2 * texture.width() + texture.width() / 2) * 4) as usize;
+        pixels[center..center + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn additive_blending_keeps_the_destination() {
+        let Some(mut ctx) = Graphics::headless(8, 8) else {
+            return;
+        };
+        let assert_rgb = |pixel: [u8; 4], expected: Color3f| {
+            let expected = expected.to_srgba_unorm();
+            for channel in 0..3 {
+                assert!(
+                    pixel[channel].abs_diff(expected[channel]) <= 1,
+                    "{pixel:?} != {expected:?}"
+                );
+            }
+        };
+        // Blending happens on linear values, before the sRGB encoding of the target
+        assert_rgb(
+            blended_pixel(&mut ctx, BlendMode::Alpha),
+            Color3f::new(0.25, 0.0, 0.25),
+        );
+        assert_rgb(
+            blended_pixel(&mut ctx, BlendMode::Additive),
+            Color3f::new(0.25, 0.0, 0.5),
+        );
+        assert_rgb(
+            blended_pixel(&mut ctx, BlendMode::Replace),
+            Color3f::new(0.5, 0.0, 0.0),
+        );
+    }
 }
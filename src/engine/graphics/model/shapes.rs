@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+};
+
+use bytemuck::Pod;
+
+use crate::engine::{
+    graphics::{
+        Graphics,
+        model::{Model, Vertex},
+    },
+    maths::Vec3f,
+};
+
+/// Generated primitives, centered on the origin with Y up and counter clockwise outward faces.
+/// Use `Model<u32>` when the vertex count exceeds 65535, the constructors panic otherwise.
+impl<I: Pod + TryFrom<u32>> Model<I> {
+    /// Unit sphere made of `rings` horizontal bands of `sectors` quads, the bands touching the
+    /// poles are made of triangles. `2 * sectors * (rings - 1)` triangles.
+    pub fn uv_sphere(ctx: &Graphics, rings: u32, sectors: u32) -> Self {
+        let (vertices, indices) = uv_sphere(rings, sectors);
+        Self::from_u32_indices(ctx, &vertices, &indices)
+    }
+
+    /// Unit sphere from an icosahedron whose triangles are split in 4 `subdivisions` times,
+    /// with vertices more evenly spread than `uv_sphere`. `20 * 4^subdivisions` triangles.
+    pub fn ico_sphere(ctx: &Graphics, subdivisions: u32) -> Self {
+        let (vertices, indices) = ico_sphere(subdivisions);
+        Self::from_u32_indices(ctx, &vertices, &indices)
+    }
+
+    /// Capped cylinder of `segments` sides around the Y axis. `4 * segments` triangles.
+    pub fn cylinder(ctx: &Graphics, segments: u32, height: f32, radius: f32) -> Self {
+        let (vertices, indices) = cylinder(segments, height, radius);
+        Self::from_u32_indices(ctx, &vertices, &indices)
+    }
+
+    /// Cone of `segments` sides with its apex up and a capped base. `2 * segments` triangles.
+    pub fn cone(ctx: &Graphics, segments: u32, height: f32, radius: f32) -> Self {
+        let (vertices, indices) = cone(segments, height, radius);
+        Self::from_u32_indices(ctx, &vertices, &indices)
+    }
+
+    /// Cylinder of `segments` sides closed by hemispheres of `rings` bands, like a collider
+    /// capsule. `height` is the distance between the hemisphere centers, so the total height
+    /// is `height + 2 * radius`. `4 * segments * rings` triangles.
+    pub fn capsule(ctx: &Graphics, segments: u32, rings: u32, height: f32, radius: f32) -> Self {
+        let (vertices, indices) = capsule(segments, rings, height, radius);
+        Self::from_u32_indices(ctx, &vertices, &indices)
+    }
+
+    /// Line geometry for `LineMaterial`, a square of `half_extent` around the origin on the XZ
+    /// plane with a line every `cell_size`. `2 * (2 * divisions + 1)` lines, where `divisions`
+    /// is the number of whole cells between the center and an edge.
+    pub fn grid(ctx: &Graphics, half_extent: f32, cell_size: f32) -> Self {
+        let (vertices, indices) = grid(half_extent, cell_size);
+        Self::from_u32_indices(ctx, &vertices, &indices)
+    }
+
+    fn from_u32_indices(ctx: &Graphics, vertices: &[Vertex], indices: &[u32]) -> Self {
+        let indices: Vec<I> = indices
+            .iter()
+            .map(|&i| {
+                I::try_from(i).unwrap_or_else(|_| {
+                    panic!(
+                        "{} vertices do not fit the index type, use Model<u32>",
+                        vertices.len()
+                    )
+                })
+            })
+            .collect();
+        Self::new(ctx, vertices, &indices)
+    }
+}
+
+/// Point of the outline revolved by `lathe`
+#[derive(Debug, Clone, Copy)]
+struct ProfilePoint {
+    /// Distance to the Y axis, 0 for a pole
+    radius: f32,
+    y: f32,
+    /// Outward normal as (away from the axis, y)
+    normal: [f32; 2],
+    v: f32,
+}
+
+/// Revolve the profile, ordered from top to bottom, around the Y axis. The seam is duplicated
+/// for the UVs. Poles get one vertex per segment, at the middle of the segment, so their UVs
+/// and normals are not degenerate.
+fn lathe(profile: &[ProfilePoint], segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let columns = segments + 1;
+    let mut vertices = Vec::with_capacity(profile.len() * columns as usize);
+    for point in profile {
+        let pole = point.radius == 0.0;
+        for column in 0..columns {
+            let u = (column as f32 + if pole { 0.5 } else { 0.0 }) / segments as f32;
+            let (sin, cos) = (u * TAU).sin_cos();
+            let normal = Vec3f::new(
+                point.normal[0] * cos,
+                point.normal[1],
+                point.normal[0] * sin,
+            )
+            .normalize();
+            vertices.push(Vertex {
+                position: [point.radius * cos, point.y, point.radius * sin],
+                uv: [u, point.v],
+                normal: normal.into(),
+            });
+        }
+    }
+
+    let mut indices = vec![];
+    for (row, band) in profile.windows(2).enumerate() {
+        let (top, bottom) = (row as u32 * columns, (row as u32 + 1) * columns);
+        for column in 0..segments {
+            let [a, b, c, d] = [
+                top + column,
+                bottom + column,
+                bottom + column + 1,
+                top + column + 1,
+            ];
+            if band[1].radius != 0.0 {
+                indices.extend([a, c, b]);
+            }
+            if band[0].radius != 0.0 {
+                indices.extend([a, d, c]);
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+/// Disc facing up or down, as a fan around its center
+fn cap(y: f32, radius: f32, segments: u32, up: bool) -> (Vec<Vertex>, Vec<u32>) {
+    let normal = [0.0, if up { 1.0 } else { -1.0 }, 0.0];
+    let center = Vertex {
+        position: [0.0, y, 0.0],
+        uv: [0.5, 0.5],
+        normal,
+    };
+    let rim = (0..=segments).map(|segment| {
+        let (sin, cos) = (segment as f32 / segments as f32 * TAU).sin_cos();
+        Vertex {
+            position: [radius * cos, y, radius * sin],
+            uv: [0.5 + cos * 0.5, 0.5 + sin * 0.5],
+            normal,
+        }
+    });
+    let vertices = std::iter::once(center).chain(rim).collect();
+    let indices = (1..=segments)
+        .flat_map(|i| if up { [0, i + 1, i] } else { [0, i, i + 1] })
+        .collect();
+    (vertices, indices)
+}
+
+fn append(mesh: &mut (Vec<Vertex>, Vec<u32>), other: (Vec<Vertex>, Vec<u32>)) {
+    let offset = mesh.0.len() as u32;
+    mesh.0.extend(other.0);
+    mesh.1.extend(other.1.into_iter().map(|i| i + offset));
+}
+
+/// Pairs of indices, one per line
+fn grid(half_extent: f32, cell_size: f32) -> (Vec<Vertex>, Vec<u32>) {
+    assert!(cell_size > 0.0, "A grid needs a positive cell size");
+    let divisions = (half_extent / cell_size).floor().max(0.0) as i32;
+    let extent = divisions as f32 * cell_size;
+    // The extent is zero when the cell is larger than the grid
+    let size = (2.0 * extent).max(cell_size);
+    let vertex = |x: f32, z: f32| Vertex {
+        position: [x, 0.0, z],
+        uv: [0.5 + x / size, 0.5 + z / size],
+        normal: [0.0, 1.0, 0.0],
+    };
+    let vertices: Vec<Vertex> = (-divisions..=divisions)
+        .flat_map(|i| {
+            let offset = i as f32 * cell_size;
+            [
+                // Along X, then along Z
+                vertex(-extent, offset),
+                vertex(extent, offset),
+                vertex(offset, -extent),
+                vertex(offset, extent),
+            ]
+        })
+        .collect();
+    let indices = (0..vertices.len() as u32).collect();
+    (vertices, indices)
+}
+
+fn cylinder(segments: u32, height: f32, radius: f32) -> (Vec<Vertex>, Vec<u32>) {
+    assert!(segments >= 3, "A cylinder needs at least 3 segments");
+    let half = height / 2.0;
+    let side = [(half, 0.0), (-half, 1.0)].map(|(y, v)| ProfilePoint {
+        radius,
+        y,
+        normal: [1.0, 0.0],
+        v,
+    });
+    let mut mesh = lathe(&side, segments);
+    append(&mut mesh, cap(half, radius, segments, true));
+    append(&mut mesh, cap(-half, radius, segments, false));
+    mesh
+}
+
+fn cone(segments: u32, height: f32, radius: f32) -> (Vec<Vertex>, Vec<u32>) {
+    assert!(segments >= 3, "A cone needs at least 3 segments");
+    let half = height / 2.0;
+    // Perpendicular to the slant
+    let normal = [height, radius];
+    let side = [(0.0, half, 0.0), (radius, -half, 1.0)].map(|(r, y, v)| ProfilePoint {
+        radius: r,
+        y,
+        normal,
+        v,
+    });
+    let mut mesh = lathe(&side, segments);
+    append(&mut mesh, cap(-half, radius, segments, false));
+    mesh
+}
+
+fn capsule(segments: u32, rings: u32, height: f32, radius: f32) -> (Vec<Vertex>, Vec<u32>) {
+    assert!(segments >= 3, "A capsule needs at least 3 segments");
+    let rings = rings.max(1);
+    let half = height / 2.0;
+    // v follows the length of the outline so the texture is not stretched on the cylinder
+    let length = PI * radius + height;
+    let hemisphere = |top: bool| {
+        (0..=rings).map(move |ring| {
+            let angle = ring as f32 / rings as f32 * PI / 2.0;
+            let angle = if top { angle } else { angle + PI / 2.0 };
+            let (sin, cos) = angle.sin_cos();
+            let arc = angle * radius + if top { 0.0 } else { height };
+            ProfilePoint {
+                radius: if angle == 0.0 || angle == PI {
+                    0.0
+                } else {
+                    sin * radius
+                },
+                y: cos * radius + if top { half } else { -half },
+                normal: [sin, cos],
+                v: arc / length,
+            }
+        })
+    };
+    let profile: Vec<ProfilePoint> = hemisphere(true).chain(hemisphere(false)).collect();
+    lathe(&profile, segments)
+}
+
+fn uv_sphere(rings: u32, sectors: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let rings = rings.max(2);
+    let profile: Vec<ProfilePoint> = (0..=rings)
+        .map(|ring| {
+            let v = ring as f32 / rings as f32;
+            let (sin, cos) = (v * PI).sin_cos();
+            ProfilePoint {
+                radius: if ring == 0 || ring == rings { 0.0 } else { sin },
+                y: cos,
+                normal: [sin, cos],
+                v,
+            }
+        })
+        .collect();
+    lathe(&profile, sectors.max(3))
+}
+
+fn ico_sphere(subdivisions: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3f> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .into_iter()
+    .map(|p| Vec3f::from(p).normalize())
+    .collect();
+    #[rustfmt::skip]
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // Edges are shared by two triangles, which must reuse the same midpoint
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let p = (positions[a as usize] + positions[b as usize]).normalize();
+                positions.push(p);
+                positions.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut vertices: Vec<Vertex> = positions
+        .iter()
+        .map(|p| Vertex {
+            position: (*p).into(),
+            uv: [0.5 + p.z.atan2(p.x) / TAU, p.y.clamp(-1.0, 1.0).acos() / PI],
+            normal: (*p).into(),
+        })
+        .collect();
+
+    // Triangles crossing the UV seam would interpolate across the whole texture, give them
+    // copies of their vertices on the low side with u past 1
+    let mut wrapped = HashMap::new();
+    for triangle in &mut triangles {
+        let u = triangle.map(|i| vertices[i as usize].uv[0]);
+        let max = u.iter().copied().fold(f32::MIN, f32::max);
+        if max - u.iter().copied().fold(f32::MAX, f32::min) < 0.5 {
+            continue;
+        }
+        for (index, u) in triangle.iter_mut().zip(u) {
+            if max - u > 0.5 {
+                *index = *wrapped.entry(*index).or_insert_with(|| {
+                    let mut vertex = vertices[*index as usize];
+                    vertex.uv[0] += 1.0;
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+    }
+
+    (vertices, triangles.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_on_unit_sphere(vertices: &[Vertex]) {
+        for vertex in vertices {
+            let position = Vec3f::from(vertex.position);
+            assert!((position.norm() - 1.0).abs() < 1e-5, "{position:?}");
+            assert!((Vec3f::from(vertex.normal) - position).norm() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn uv_sphere_vertices_are_on_the_unit_sphere() {
+        let (vertices, indices) = uv_sphere(8, 16);
+        assert_on_unit_sphere(&vertices);
+        assert_eq!(indices.len() / 3, 2 * 16 * (8 - 1));
+    }
+
+    #[test]
+    fn ico_sphere_vertices_are_on_the_unit_sphere() {
+        for subdivisions in 0..3 {
+            let (vertices, indices) = ico_sphere(subdivisions);
+            assert_on_unit_sphere(&vertices);
+            assert_eq!(indices.len() / 3, 20 * 4usize.pow(subdivisions));
+        }
+    }
+
+    #[test]
+    fn primitive_triangle_counts_match_their_formula() {
+        let triangles = |(_, indices): (Vec<Vertex>, Vec<u32>)| indices.len() / 3;
+        for segments in [3, 8, 32] {
+            let n = segments as usize;
+            assert_eq!(triangles(cylinder(segments, 2.0, 0.5)), 4 * n);
+            assert_eq!(triangles(cone(segments, 2.0, 0.5)), 2 * n);
+            for rings in [1, 4] {
+                assert_eq!(
+                    triangles(capsule(segments, rings, 2.0, 0.5)),
+                    4 * n * rings as usize
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 3 segments")]
+    fn primitives_need_three_segments() {
+        cylinder(2, 1.0, 1.0);
+    }
+
+    #[test]
+    fn grid_has_two_lines_per_division_and_axis() {
+        for (half_extent, cell_size, divisions) in [(5.0, 1.0, 5), (2.5, 1.0, 2), (0.5, 1.0, 0)] {
+            let (vertices, indices) = grid(half_extent, cell_size);
+            assert_eq!(indices.len() / 2, 2 * (2 * divisions + 1));
+            assert_eq!(vertices.len(), indices.len());
+        }
+    }
+}
@@ -1,14 +1,137 @@
-use crate::engine::graphics::{Graphics, color::Color3f};
+use crate::engine::graphics::{
+    Frame, Graphics, GraphicsCapabilities, color::Color3f, memory::TrackedMemory,
+};
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
     BindGroupLayoutEntry, BindingType, SamplerBindingType, ShaderStages, TextureSampleType,
     TextureViewDimension, util::DeviceExt, wgt::TextureDataOrder,
 };
 
+/// How a texture is sampled, the default clamps and filters linearly when magnified only
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerConfig {
+    /// Applied on every axis, `Repeat` for tiling textures
+    pub address_mode: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    /// Only matters for textures with mip levels
+    pub mipmap_filter: wgpu::FilterMode,
+    /// Anisotropic filtering level, 1 disables it. Clamped to `GraphicsCapabilities::max_anisotropy`,
+    /// only applies when every filter is `Linear`.
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            anisotropy_clamp: 1,
+        }
+    }
+}
+
+impl SamplerConfig {
+    /// Nearest filtering everywhere, keeps pixel art crisp
+    pub fn pixel_art() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+
+    /// Default filtering with repeat addressing, for tiling textures
+    pub fn repeating() -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::Repeat,
+            ..Default::default()
+        }
+    }
+
+    /// Linear filtering everywhere with `level` anisotropy, keeps textures seen at grazing
+    /// angles sharp, e.g. the ground
+    pub fn anisotropic(level: u16) -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: level,
+            ..Default::default()
+        }
+    }
+
+    pub fn descriptor<'a>(
+        &self,
+        capabilities: &GraphicsCapabilities,
+        label: Option<&'a str>,
+    ) -> wgpu::SamplerDescriptor<'a> {
+        let linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|f| *f == wgpu::FilterMode::Linear);
+        // wgpu rejects anisotropy with any other filter
+        let anisotropy_clamp = if linear {
+            capabilities.clamp_anisotropy(self.anisotropy_clamp)
+        } else {
+            1
+        };
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CubemapError {
+    Image(image::ImageError),
+    /// Every face must be square and have the size of the first one
+    FaceSize {
+        face: usize,
+        width: u32,
+        height: u32,
+        expected: u32,
+    },
+}
+
+impl std::fmt::Display for CubemapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Image(e) => write!(f, "failed to decode cubemap face: {e}"),
+            Self::FaceSize {
+                face,
+                width,
+                height,
+                expected,
+            } => write!(
+                f,
+                "cubemap face {face} is {width}x{height}, expected {expected}x{expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CubemapError {}
+
+impl From<image::ImageError> for CubemapError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
 pub struct ModelTexture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    _memory: TrackedMemory,
 }
 
 impl ModelTexture {
@@ -37,18 +160,13 @@ impl ModelTexture {
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some(&format!("{}_sampler", label)),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler_label = format!("{}_sampler", label);
+        let sampler = ctx.device.create_sampler(
+            &SamplerConfig::default().descriptor(&ctx.capabilities, Some(&sampler_label)),
+        );
 
         Self {
+            _memory: ctx.memory.track_texture(&texture),
             texture,
             view,
             sampler,
@@ -59,21 +177,90 @@ impl ModelTexture {
         ctx: &Graphics,
         bytes: &[u8],
         label: &str,
+    ) -> Result<Self, image::ImageError> {
+        Self::from_bytes_with_sampler(ctx, bytes, label, SamplerConfig::default())
+    }
+
+    pub fn from_bytes_with_sampler(
+        ctx: &Graphics,
+        bytes: &[u8],
+        label: &str,
+        sampler: SamplerConfig,
     ) -> Result<Self, image::ImageError> {
         let img = image::load_from_memory(bytes)?.to_rgba8();
-        let dimensions = img.dimensions();
+        let (width, height) = img.dimensions();
+        Ok(Self::from_mip_chain(
+            ctx, width, height, 1, &img, label, sampler,
+        ))
+    }
 
-        let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
+    /// Same as `from_bytes` with a full mip chain, downsampled on the CPU with a box filter.
+    /// Avoids the shimmering of minified textures at a distance.
+    pub fn from_bytes_mipmapped(
+        ctx: &Graphics,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self, image::ImageError> {
+        let mut level = image::load_from_memory(bytes)?.to_rgba8();
+        let (width, height) = level.dimensions();
+
+        let mip_level_count = mip_level_count(width, height);
+        let mut data = Vec::with_capacity(level.len() * 4 / 3 + 4);
+        for _ in 1..mip_level_count {
+            data.extend_from_slice(&level);
+            level = downsample(&level);
+        }
+        data.extend_from_slice(&level);
+
+        let sampler = SamplerConfig {
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
         };
+        Ok(Self::from_mip_chain(
+            ctx,
+            width,
+            height,
+            mip_level_count,
+            &data,
+            label,
+            sampler,
+        ))
+    }
+
+    /// Cube texture from six square images of the same size, in the +X, -X, +Y, -Y, +Z, -Z order.
+    /// Bind it with `TextureUniform::cubemap` and sample it with a direction.
+    pub fn cubemap_from_bytes(
+        ctx: &Graphics,
+        faces: [&[u8]; 6],
+        label: &str,
+    ) -> Result<Self, CubemapError> {
+        let mut size = None;
+        let mut data = vec![];
+        for (face, bytes) in faces.into_iter().enumerate() {
+            let img = image::load_from_memory(bytes)?.to_rgba8();
+            let (width, height) = img.dimensions();
+            let expected = *size.get_or_insert(width);
+            if width != expected || height != expected {
+                return Err(CubemapError::FaceSize {
+                    face,
+                    width,
+                    height,
+                    expected,
+                });
+            }
+            data.extend_from_slice(&img);
+        }
+        let size = size.unwrap_or_default();
 
         let texture = ctx.device.create_texture_with_data(
             &ctx.queue,
             &wgpu::TextureDescriptor {
                 label: Some(label),
-                size,
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 6,
+                },
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
@@ -81,27 +268,289 @@ impl ModelTexture {
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             },
-            TextureDataOrder::default(),
-            &img,
+            TextureDataOrder::LayerMajor,
+            &data,
         );
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
             ..Default::default()
         });
+        let sampler = ctx.device.create_sampler(
+            &SamplerConfig {
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }
+            .descriptor(&ctx.capabilities, None),
+        );
 
         Ok(Self {
+            _memory: ctx.memory.track_texture(&texture),
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// Texture from decoded sRGB pixels, 4 bytes per pixel
+    pub fn from_rgba8(ctx: &Graphics, width: u32, height: u32, pixels: &[u8], label: &str) -> Self {
+        Self::from_mip_chain(
+            ctx,
+            width,
+            height,
+            1,
+            pixels,
+            label,
+            SamplerConfig::default(),
+        )
+    }
+
+    /// Texture in the surface format that passes can draw into then sample or copy, see
+    /// `RenderTarget`
+    pub fn render_target(ctx: &Graphics, width: u32, height: u32, label: &str) -> Self {
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ctx.surface_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = ctx.device.create_sampler(
+            &SamplerConfig {
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }
+            .descriptor(&ctx.capabilities, Some(label)),
+        );
+
+        Self {
+            _memory: ctx.memory.track_texture(&texture),
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// `data` holds every mip level in order, from the full size image down
+    fn from_mip_chain(
+        ctx: &Graphics,
+        width: u32,
+        height: u32,
+        mip_level_count: u32,
+        data: &[u8],
+        label: &str,
+        sampler: SamplerConfig,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = ctx.device.create_texture_with_data(
+            &ctx.queue,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            data,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = ctx
+            .device
+            .create_sampler(&sampler.descriptor(&ctx.capabilities, None));
+
+        Self {
+            _memory: ctx.memory.track_texture(&texture),
             texture,
             view,
             sampler,
+        }
+    }
+}
+
+/// Levels of a full mip chain, down to a single pixel along the longest side
+fn mip_level_count(width: u32, height: u32) -> u32 {
+    width.max(height).max(1).ilog2() + 1
+}
+
+/// Next mip level, averaging 2x2 blocks. Odd sizes repeat the last row or column.
+fn downsample(img: &image::RgbaImage) -> image::RgbaImage {
+    let (width, height) = img.dimensions();
+    image::RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let mut sum = [0u32; 4];
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let pixel = img.get_pixel((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1));
+            for (s, c) in sum.iter_mut().zip(pixel.0) {
+                *s += c as u32;
+            }
+        }
+        image::Rgba(sum.map(|s| ((s + 2) / 4) as u8))
+    })
+}
+
+/// A texture whose data is copied to the GPU over several frames.
+///
+/// The decoded image is written once into a staging buffer, then `upload` records the copy of
+/// `rows_per_frame` rows into the frame's encoder. The texture content is complete for commands
+/// recorded after the `upload` call that made `is_ready` return true, so call `upload` before
+/// rendering with the texture. Until then, the missing rows are transparent black.
+/// Small images skip the staging buffer and are uploaded synchronously.
+pub struct StreamingTexture {
+    pub texture: ModelTexture,
+    pub rows_per_frame: u32,
+    staging: Option<wgpu::Buffer>,
+    padded_bytes_per_row: u32,
+    rows_uploaded: u32,
+}
+
+impl StreamingTexture {
+    /// Images up to this size are uploaded synchronously
+    pub const SYNC_UPLOAD_MAX_BYTES: usize = 256 * 1024;
+
+    pub fn from_bytes(
+        ctx: &Graphics,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self, image::ImageError> {
+        let img = image::load_from_memory(bytes)?.to_rgba8();
+        let (width, height) = img.dimensions();
+
+        if img.len() <= Self::SYNC_UPLOAD_MAX_BYTES {
+            return Ok(Self {
+                texture: ModelTexture::from_bytes(ctx, bytes, label)?,
+                rows_per_frame: height,
+                staging: None,
+                padded_bytes_per_row: 0,
+                rows_uploaded: height,
+            });
+        }
+
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        // Buffer to texture copies require rows aligned to COPY_BYTES_PER_ROW_ALIGNMENT
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{}_staging", label)),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: true,
+        });
+        {
+            let mut mapped = staging.slice(..).get_mapped_range_mut();
+            for (src, dst) in img
+                .chunks_exact(unpadded_bytes_per_row as usize)
+                .zip(mapped.chunks_exact_mut(padded_bytes_per_row as usize))
+            {
+                dst[..src.len()].copy_from_slice(src);
+            }
+        }
+        staging.unmap();
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler_label = format!("{}_sampler", label);
+        let sampler = ctx.device.create_sampler(
+            &SamplerConfig::default().descriptor(&ctx.capabilities, Some(&sampler_label)),
+        );
+
+        Ok(Self {
+            texture: ModelTexture {
+                _memory: ctx.memory.track_texture(&texture),
+                texture,
+                view,
+                sampler,
+            },
+            rows_per_frame: (4 * 1024 * 1024 / padded_bytes_per_row).max(1),
+            staging: Some(staging),
+            padded_bytes_per_row,
+            rows_uploaded: 0,
         })
     }
+
+    pub fn is_ready(&self) -> bool {
+        self.rows_uploaded >= self.texture.texture.height()
+    }
+
+    /// Fraction of the rows uploaded, between 0 and 1
+    pub fn progress(&self) -> f32 {
+        self.rows_uploaded as f32 / self.texture.texture.height().max(1) as f32
+    }
+
+    /// Record the copy of the next rows, returns true once the whole texture is uploaded
+    pub fn upload(&mut self, frame: &mut Frame) -> bool {
+        let Some(staging) = &self.staging else {
+            return true;
+        };
+
+        let height = self.texture.texture.height();
+        let rows = self.rows_per_frame.max(1).min(height - self.rows_uploaded);
+        frame.encoder.copy_buffer_to_texture(
+            wgpu::TexelCopyBufferInfo {
+                buffer: staging,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: self.rows_uploaded as u64 * self.padded_bytes_per_row as u64,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(rows),
+                },
+            },
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: self.rows_uploaded,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width: self.texture.texture.width(),
+                height: rows,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.rows_uploaded += rows;
+
+        if self.is_ready() {
+            // The buffer is kept alive by the recorded copy until it executes
+            self.staging = None;
+        }
+        self.is_ready()
+    }
 }
 
 pub struct TextureUniform {
@@ -111,6 +560,19 @@ pub struct TextureUniform {
 
 impl TextureUniform {
     pub fn new(ctx: &Graphics, texture: &ModelTexture) -> Self {
+        Self::with_view_dimension(ctx, texture, TextureViewDimension::D2)
+    }
+
+    /// For textures from `ModelTexture::cubemap_from_bytes`, declared as `texture_cube<f32>`
+    pub fn cubemap(ctx: &Graphics, texture: &ModelTexture) -> Self {
+        Self::with_view_dimension(ctx, texture, TextureViewDimension::Cube)
+    }
+
+    fn with_view_dimension(
+        ctx: &Graphics,
+        texture: &ModelTexture,
+        view_dimension: TextureViewDimension,
+    ) -> Self {
         let layout = ctx
             .device
             .create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -121,7 +583,7 @@ impl TextureUniform {
                         visibility: ShaderStages::FRAGMENT,
                         ty: BindingType::Texture {
                             multisampled: false,
-                            view_dimension: TextureViewDimension::D2,
+                            view_dimension,
                             sample_type: TextureSampleType::Float { filterable: true },
                         },
                         count: None,
@@ -135,8 +597,22 @@ impl TextureUniform {
                 ],
             });
 
-        let bind_group = ctx.device.create_bind_group(&BindGroupDescriptor {
-            layout: &layout,
+        let bind_group = Self::create_bind_group(ctx, &layout, texture);
+
+        Self {
+            bind_group_layout: layout,
+            bind_group,
+        }
+    }
+
+    /// Bind another texture with the same layout, usable in place of `bind_group`
+    pub fn create_bind_group(
+        ctx: &Graphics,
+        layout: &BindGroupLayout,
+        texture: &ModelTexture,
+    ) -> BindGroup {
+        ctx.device.create_bind_group(&BindGroupDescriptor {
+            layout,
             entries: &[
                 BindGroupEntry {
                     binding: 0,
@@ -148,11 +624,162 @@ impl TextureUniform {
                 },
             ],
             label: Some("texture_bind_group"),
-        });
+        })
+    }
+}
 
-        Self {
-            bind_group_layout: layout,
-            bind_group,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Residency {
+    /// Only the low resolution version is on the GPU
+    Low,
+    /// The high resolution version is being uploaded, with its progress between 0 and 1
+    Streaming(f32),
+    High,
+}
+
+/// A texture keeping a low resolution version resident and streaming a high resolution one
+/// while the camera is close enough. The high resolution image stays encoded in memory and is
+/// decoded again each time it streams in.
+pub struct ResidentTexture {
+    pub low: TextureUniform,
+    /// Start streaming the high resolution version closer than this distance
+    pub load_distance: f32,
+    /// Drop the high resolution version further than this distance, at least `load_distance`
+    pub unload_distance: f32,
+    high_bytes: Vec<u8>,
+    high: Option<(StreamingTexture, BindGroup)>,
+    failed: bool,
+    label: String,
+}
+
+impl ResidentTexture {
+    pub fn from_bytes(
+        ctx: &Graphics,
+        low_bytes: &[u8],
+        high_bytes: Vec<u8>,
+        label: &str,
+    ) -> Result<Self, image::ImageError> {
+        let low = TextureUniform::new(ctx, &ModelTexture::from_bytes(ctx, low_bytes, label)?);
+        Ok(Self {
+            low,
+            load_distance: 20.0,
+            unload_distance: 30.0,
+            high_bytes,
+            high: None,
+            failed: false,
+            label: label.to_owned(),
+        })
+    }
+
+    pub fn residency(&self) -> Residency {
+        match &self.high {
+            None => Residency::Low,
+            Some((texture, _)) if !texture.is_ready() => Residency::Streaming(texture.progress()),
+            Some(_) => Residency::High,
+        }
+    }
+
+    /// Load, stream or drop the high resolution version for a camera `distance` away,
+    /// call before the render pass using `bind_group`
+    pub fn update(&mut self, ctx: &Graphics, frame: &mut Frame, distance: f32) {
+        if distance > self.unload_distance.max(self.load_distance) {
+            self.high = None;
+            return;
+        }
+
+        if self.high.is_none() && distance < self.load_distance && !self.failed {
+            let label = format!("{}_high", self.label);
+            match StreamingTexture::from_bytes(ctx, &self.high_bytes, &label) {
+                Ok(texture) => {
+                    let bind_group = TextureUniform::create_bind_group(
+                        ctx,
+                        &self.low.bind_group_layout,
+                        &texture.texture,
+                    );
+                    self.high = Some((texture, bind_group));
+                }
+                Err(e) => {
+                    crate::log!("Failed to load high resolution texture {label}: {e}");
+                    self.failed = true;
+                }
+            }
+        }
+
+        if let Some((texture, _)) = &mut self.high
+            && !texture.is_ready()
+        {
+            texture.upload(frame);
         }
     }
+
+    /// The high resolution texture once fully uploaded, the low resolution one otherwise
+    pub fn bind_group(&self) -> &BindGroup {
+        match &self.high {
+            Some((texture, bind_group)) if texture.is_ready() => bind_group,
+            _ => &self.low.bind_group,
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("{}: {:?}", self.label, self.residency()));
+        ui.add(egui::Slider::new(&mut self.load_distance, 0.0..=500.0).text("Load distance"));
+        ui.add(egui::Slider::new(&mut self.unload_distance, 0.0..=500.0).text("Unload distance"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities() -> GraphicsCapabilities {
+        GraphicsCapabilities {
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            downlevel: wgpu::DownlevelCapabilities::default(),
+            sample_counts: vec![1],
+        }
+    }
+
+    #[test]
+    fn repeating_sampler_repeats_on_every_axis() {
+        let descriptor = SamplerConfig::repeating().descriptor(&capabilities(), None);
+        assert_eq!(descriptor.address_mode_u, wgpu::AddressMode::Repeat);
+        assert_eq!(descriptor.address_mode_v, wgpu::AddressMode::Repeat);
+        assert_eq!(descriptor.address_mode_w, wgpu::AddressMode::Repeat);
+    }
+
+    #[test]
+    fn anisotropy_is_clamped_and_needs_linear_filters() {
+        let capabilities = capabilities();
+        let descriptor = SamplerConfig::anisotropic(32).descriptor(&capabilities, None);
+        assert_eq!(descriptor.anisotropy_clamp, 16);
+
+        let nearest = SamplerConfig {
+            anisotropy_clamp: 8,
+            ..SamplerConfig::pixel_art()
+        };
+        assert_eq!(nearest.descriptor(&capabilities, None).anisotropy_clamp, 1);
+    }
+
+    #[test]
+    fn mip_chain_goes_down_to_one_pixel() {
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 17), 9);
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(0, 0), 1);
+    }
+
+    #[test]
+    fn mipmapped_texture_has_the_full_chain() {
+        let Some(ctx) = Graphics::headless(8, 8) else {
+            return;
+        };
+        let bytes = include_bytes!("../../../../assets/debug.png");
+        let texture = ModelTexture::from_bytes_mipmapped(&ctx, bytes, "mipmapped").unwrap();
+        // The image is 256x256
+        assert_eq!(texture.texture.mip_level_count(), 9);
+        let plain = ModelTexture::from_bytes(&ctx, bytes, "plain").unwrap();
+        assert_eq!(plain.texture.mip_level_count(), 1);
+    }
 }
@@ -0,0 +1,588 @@
+use std::time::Instant;
+
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, ShaderStages, TextureSampleType, TextureView,
+    TextureViewDimension,
+};
+
+use crate::engine::{
+    graphics::{
+        Frame, Graphics,
+        camera::{CAMERA_WGSL, CameraUniform},
+        color::Color4f,
+        memory::TrackedMemory,
+        model::renderer::{BlendMode, MaterialRenderer, ModelsIter},
+    },
+    maths::Vec3f,
+};
+
+/// Soft particle helpers, prepend to particle shaders after `CAMERA_WGSL`.
+///
+/// The scene depth is read with `textureLoad` so the depth texture must not be bound as a
+/// writable depth attachment in the same pass.
+pub const SOFT_PARTICLE_WGSL: &str = r#"
+struct SoftParticleParams {
+    // x: softness distance, y: circular falloff enabled
+    params: vec4<f32>,
+    // See `CameraUniform::depth_planes`
+    planes: vec4<f32>,
+};
+
+fn soft_particle_fade(scene_depth: f32, fragment_depth: f32, soft: SoftParticleParams) -> f32 {
+    if (soft.params.x <= 0.0) {
+        return 1.0;
+    }
+    let scene = linearize_depth(scene_depth, soft.planes);
+    let fragment = linearize_depth(fragment_depth, soft.planes);
+    return clamp((scene - fragment) / soft.params.x, 0.0, 1.0);
+}
+
+// uv in [0, 1], fades from the center of the quad to its inscribed circle
+fn circular_falloff(uv: vec2<f32>, soft: SoftParticleParams) -> f32 {
+    if (soft.params.y == 0.0) {
+        return 1.0;
+    }
+    let d = length(uv * 2.0 - vec2<f32>(1.0));
+    return 1.0 - smoothstep(0.5, 1.0, d);
+}
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoftParticleSettings {
+    /// Depth difference in world units over which particles fade into the geometry behind them, 0 disables it
+    pub softness: f32,
+    /// Round particles by fading alpha from the quad center
+    pub circular: bool,
+}
+
+impl Default for SoftParticleSettings {
+    fn default() -> Self {
+        Self {
+            softness: 0.5,
+            circular: true,
+        }
+    }
+}
+
+impl SoftParticleSettings {
+    /// Data matching the `SoftParticleParams` WGSL struct, upload it every frame so the depth
+    /// follows the camera projection
+    pub fn data(&self, camera_uniform: &CameraUniform) -> [[f32; 4]; 2] {
+        [
+            [
+                self.softness,
+                if self.circular { 1.0 } else { 0.0 },
+                0.0,
+                0.0,
+            ],
+            camera_uniform.depth_planes(),
+        ]
+    }
+}
+
+/// Bind group exposing the scene depth buffer to particle fragment shaders.
+///
+/// The depth buffer is multisampled along with the scene, declare it in WGSL with `wgsl_type`
+/// and read it with `textureLoad(t_depth, coords, 0)`, which loads the first sample.
+pub struct SceneDepthUniform {
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+    sample_count: u32,
+}
+
+impl SceneDepthUniform {
+    pub fn new(ctx: &Graphics, depth_view: &TextureView) -> Self {
+        let bind_group_layout = create_bind_group_layout(ctx, ctx.sample_count);
+        let bind_group = create_bind_group(ctx, &bind_group_layout, depth_view);
+
+        Self {
+            bind_group_layout,
+            bind_group,
+            sample_count: ctx.sample_count,
+        }
+    }
+
+    /// WGSL type of the depth texture binding
+    pub fn wgsl_type(&self) -> &'static str {
+        if self.sample_count > 1 {
+            "texture_depth_multisampled_2d"
+        } else {
+            "texture_depth_2d"
+        }
+    }
+
+    /// The depth texture is recreated on resize, the bind group must follow. Returns true when
+    /// the layout changed with `Graphics::sample_count`, pipelines using it must be recreated.
+    pub fn on_resize(&mut self, ctx: &Graphics, depth_view: &TextureView) -> bool {
+        let layout_changed = self.sample_count != ctx.sample_count;
+        if layout_changed {
+            self.sample_count = ctx.sample_count;
+            self.bind_group_layout = create_bind_group_layout(ctx, self.sample_count);
+        }
+        self.bind_group = create_bind_group(ctx, &self.bind_group_layout, depth_view);
+        layout_changed
+    }
+}
+
+fn create_bind_group_layout(ctx: &Graphics, sample_count: u32) -> BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("scene_depth_bind_group_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    multisampled: sample_count > 1,
+                    view_dimension: TextureViewDimension::D2,
+                    sample_type: TextureSampleType::Depth,
+                },
+                count: None,
+            }],
+        })
+}
+
+fn create_bind_group(
+    ctx: &Graphics,
+    layout: &BindGroupLayout,
+    depth_view: &TextureView,
+) -> BindGroup {
+    ctx.device.create_bind_group(&BindGroupDescriptor {
+        label: Some("scene_depth_bind_group"),
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(depth_view),
+        }],
+    })
+}
+
+const PARTICLE_WGSL: &str = r#"
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct ParticleInput {
+    // xyz: world position, w: size
+    @location(0) position_size: vec4<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct ParticleOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_particle(@builtin(vertex_index) index: u32, particle: ParticleInput) -> ParticleOutput {
+    // Two triangles of a quad centered on the particle
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5), vec2<f32>(0.5, -0.5), vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5), vec2<f32>(0.5, 0.5), vec2<f32>(-0.5, 0.5),
+    );
+    let corner = corners[index];
+    // Rows of the view rotation, the camera axes in world space
+    let right = vec3<f32>(camera.view[0].x, camera.view[1].x, camera.view[2].x);
+    let up = vec3<f32>(camera.view[0].y, camera.view[1].y, camera.view[2].y);
+    let size = particle.position_size.w;
+    let world_position = particle.position_size.xyz + (right * corner.x + up * corner.y) * size;
+
+    var out: ParticleOutput;
+    out.clip_position = log_depth(camera.proj * camera.view * vec4<f32>(world_position, 1.0), camera.depth);
+    out.uv = corner + vec2<f32>(0.5);
+    out.color = particle.color;
+    return out;
+}
+
+@fragment
+fn fs_particle(in: ParticleOutput) -> @location(0) vec4<f32> {
+    let d = length(in.uv * 2.0 - vec2<f32>(1.0));
+    return vec4<f32>(in.color.rgb, in.color.a * (1.0 - smoothstep(0.5, 1.0, d)));
+}
+"#;
+
+/// Emitter parameters, editable while the system runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleSettings {
+    /// Particles spawned per second
+    pub spawn_rate: f32,
+    /// Seconds a particle lives
+    pub lifetime: f32,
+    /// Color at birth, blended towards `end_color` over the lifetime
+    pub start_color: Color4f,
+    pub end_color: Color4f,
+    /// Width of the billboard at birth, blended towards `end_size` over the lifetime
+    pub start_size: f32,
+    pub end_size: f32,
+    /// Position of the emitter in world space
+    pub origin: Vec3f,
+    /// Average initial velocity
+    pub velocity: Vec3f,
+    /// Random velocity added in every direction, up to this speed
+    pub spread: f32,
+    pub gravity: Vec3f,
+}
+
+impl Default for ParticleSettings {
+    fn default() -> Self {
+        Self {
+            spawn_rate: 50.0,
+            lifetime: 2.0,
+            start_color: Color4f::new(1.0, 0.6, 0.2, 1.0),
+            end_color: Color4f::new(1.0, 0.1, 0.0, 0.0),
+            start_size: 0.2,
+            end_size: 0.05,
+            origin: Vec3f::zeros(),
+            velocity: Vec3f::new(0.0, 1.5, 0.0),
+            spread: 0.5,
+            gravity: Vec3f::new(0.0, -0.5, 0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    position: Vec3f,
+    velocity: Vec3f,
+    age: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleInstance {
+    position_size: [f32; 4],
+    color: [f32; 4],
+}
+
+impl ParticleInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ParticleInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// The CPU side of a `ParticleSystem`: ages, moves and spawns particles without a device.
+///
+/// At most `max_particles` are alive, the slots of dead particles are reused for new ones.
+#[derive(Debug, Clone)]
+pub struct ParticlePool {
+    particles: Vec<Particle>,
+    max_particles: usize,
+    /// Fraction of a particle left to spawn, carried to the next update
+    spawn_accumulator: f32,
+    rng: u32,
+}
+
+impl ParticlePool {
+    pub fn new(max_particles: usize) -> Self {
+        let max_particles = max_particles.max(1);
+        Self {
+            particles: Vec::with_capacity(max_particles),
+            max_particles,
+            spawn_accumulator: 0.0,
+            rng: 0x9e37_79b9,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn max_particles(&self) -> usize {
+        self.max_particles
+    }
+
+    /// Age, move and spawn the particles by `dt` seconds
+    pub fn update(&mut self, settings: &ParticleSettings, dt: f32) {
+        let lifetime = settings.lifetime.max(0.001);
+        self.particles.retain_mut(|p| {
+            p.age += dt;
+            p.velocity += settings.gravity * dt;
+            p.position += p.velocity * dt;
+            p.age < lifetime
+        });
+
+        self.spawn_accumulator += settings.spawn_rate.max(0.0) * dt;
+        // Spawning stops at the limit instead of growing, dead particles free their slots
+        let free = self.max_particles - self.particles.len();
+        let count = (self.spawn_accumulator as usize).min(free);
+        self.spawn_accumulator -= self.spawn_accumulator.floor();
+        for _ in 0..count {
+            let jitter = Vec3f::new(self.random(), self.random(), self.random()) * settings.spread;
+            self.particles.push(Particle {
+                position: settings.origin,
+                velocity: settings.velocity + jitter,
+                age: 0.0,
+            });
+        }
+    }
+
+    /// Uniform in [-1, 1], xorshift
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    fn instances(&self, settings: &ParticleSettings) -> Vec<ParticleInstance> {
+        let lifetime = settings.lifetime.max(0.001);
+        self.particles
+            .iter()
+            .map(|p| {
+                let t = (p.age / lifetime).clamp(0.0, 1.0);
+                let size = settings.start_size + (settings.end_size - settings.start_size) * t;
+                let color = settings.start_color + (settings.end_color - settings.start_color) * t;
+                ParticleInstance {
+                    position_size: [p.position.x, p.position.y, p.position.z, size],
+                    color: color.into(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// CPU simulated particles drawn as camera facing billboards, one instance per particle.
+///
+/// Register it with `ModelRenderer::add_material`, it draws its own particles and needs no
+/// model. The simulation advances by the time elapsed between renders, see `ParticlePool`.
+pub struct ParticleSystem {
+    pub settings: ParticleSettings,
+    /// Additive by default, glowing particles need no sorting
+    blend: BlendMode,
+    pool: ParticlePool,
+    last_update: Option<Instant>,
+    instance_buffer: wgpu::Buffer,
+    _memory: TrackedMemory,
+    pipeline: wgpu::RenderPipeline,
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        ctx: &Graphics,
+        camera_uniform: &CameraUniform,
+        settings: ParticleSettings,
+        max_particles: usize,
+    ) -> Self {
+        Self::with_blend(
+            ctx,
+            camera_uniform,
+            settings,
+            max_particles,
+            BlendMode::Additive,
+        )
+    }
+
+    /// `BlendMode::Alpha` draws the particles in spawn order, without sorting them
+    pub fn with_blend(
+        ctx: &Graphics,
+        camera_uniform: &CameraUniform,
+        settings: ParticleSettings,
+        max_particles: usize,
+        blend: BlendMode,
+    ) -> Self {
+        let pool = ParticlePool::new(max_particles);
+        let instance_buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Instance Buffer"),
+            size: (pool.max_particles() * size_of::<ParticleInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shader_module = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("particles"),
+                source: wgpu::ShaderSource::Wgsl(format!("{CAMERA_WGSL}{PARTICLE_WGSL}").into()),
+            });
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("particles"),
+                bind_group_layouts: &[&camera_uniform.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        Self {
+            settings,
+            blend,
+            pool,
+            last_update: None,
+            _memory: ctx.memory.track_buffer(&instance_buffer),
+            instance_buffer,
+            pipeline: create_pipeline(ctx, &shader_module, &pipeline_layout, blend),
+            shader_module,
+            pipeline_layout,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    pub fn max_particles(&self) -> usize {
+        self.pool.max_particles()
+    }
+
+    /// Age, move and spawn the particles, called by `prepare` with the time since the last
+    /// render
+    pub fn update(&mut self, dt: f32) {
+        self.pool.update(&self.settings, dt);
+    }
+}
+
+impl MaterialRenderer for ParticleSystem {
+    fn name(&self) -> &str {
+        "Particles"
+    }
+
+    fn is_transparent(&self) -> bool {
+        true
+    }
+
+    fn prepare(
+        &mut self,
+        ctx: &Graphics,
+        frame: &mut Frame,
+        _camera_uniform: &CameraUniform,
+        _models: ModelsIter,
+    ) {
+        let now = Instant::now();
+        // Clamped so a long stall does not release a burst of particles
+        let dt = self
+            .last_update
+            .map_or(0.0, |last| (now - last).as_secs_f32().min(0.1));
+        self.last_update = Some(now);
+        self.update(dt);
+
+        let instances = self.pool.instances(&self.settings);
+        frame.write_buffer(
+            ctx,
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&instances),
+        );
+    }
+
+    fn on_sample_count_changed(&mut self, ctx: &Graphics) {
+        self.pipeline =
+            create_pipeline(ctx, &self.shader_module, &self.pipeline_layout, self.blend);
+    }
+
+    #[cfg(debug_assertions)]
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.settings;
+        ui.label(format!(
+            "{} / {} particles",
+            self.pool.len(),
+            self.pool.max_particles()
+        ));
+        ui.add(egui::Slider::new(&mut settings.spawn_rate, 0.0..=1000.0).text("Spawn rate"));
+        ui.add(egui::Slider::new(&mut settings.lifetime, 0.05..=10.0).text("Lifetime"));
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            ui.color_edit_button_rgba_unmultiplied(settings.start_color.array_mut());
+            ui.color_edit_button_rgba_unmultiplied(settings.end_color.array_mut());
+        });
+        ui.add(egui::Slider::new(&mut settings.start_size, 0.0..=2.0).text("Start size"));
+        ui.add(egui::Slider::new(&mut settings.end_size, 0.0..=2.0).text("End size"));
+        ui.add(egui::Slider::new(&mut settings.spread, 0.0..=5.0).text("Spread"));
+    }
+
+    fn render(
+        &mut self,
+        _ctx: &Graphics,
+        render_pass: &mut wgpu::RenderPass,
+        camera_uniform: &CameraUniform,
+        _models: ModelsIter,
+    ) {
+        if self.pool.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &camera_uniform.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.pool.len() as u32);
+    }
+}
+
+fn create_pipeline(
+    ctx: &Graphics,
+    module: &wgpu::ShaderModule,
+    layout: &wgpu::PipelineLayout,
+    blend: BlendMode,
+) -> wgpu::RenderPipeline {
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("particles"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: Some("vs_particle"),
+                buffers: &[ParticleInstance::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: Some("fs_particle"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: Some(blend.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: ctx.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_particles_are_recycled() {
+        // Every particle dies within a single step, the pool must reuse their slots
+        let settings = ParticleSettings {
+            spawn_rate: 1000.0,
+            lifetime: 0.01,
+            ..Default::default()
+        };
+        let mut pool = ParticlePool::new(16);
+        for _ in 0..1000 {
+            pool.update(&settings, 0.1);
+            assert!(pool.len() <= pool.max_particles());
+        }
+        assert_eq!(pool.len(), pool.max_particles());
+        assert!(pool.particles.capacity() <= 16);
+        assert!(pool.particles.iter().all(|p| p.age == 0.0));
+    }
+}
@@ -0,0 +1,152 @@
+use std::marker::PhantomData;
+
+use bytemuck::Pod;
+use wgpu::{BindGroup, BindGroupLayout, PushConstantRange, RenderPass, ShaderStages};
+
+use crate::engine::graphics::Graphics;
+
+/// Small data changing between draws of a material, such as a color or an index.
+///
+/// Uses push constants when the device supports them, otherwise each draw writes its data to a
+/// slot of a uniform buffer bound with a dynamic offset. Declare the data in the shader with
+/// `wgsl_declaration` and add `push_constant_ranges` and `bind_group_layout` to the pipeline layout.
+pub struct PerDrawData<T: Pod> {
+    stages: ShaderStages,
+    fallback: Option<UniformFallback>,
+    _marker: PhantomData<T>,
+}
+
+struct UniformFallback {
+    layout: BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: BindGroup,
+    stride: u64,
+    next_slot: u64,
+}
+
+impl<T: Pod> PerDrawData<T> {
+    pub fn new(ctx: &Graphics, stages: ShaderStages) -> Self {
+        let size = size_of::<T>() as u32;
+        assert!(
+            size.is_multiple_of(4),
+            "Per draw data size must be a multiple of 4"
+        );
+
+        let fallback = (!ctx.capabilities.supports_push_constants(size)).then(|| {
+            let layout = ctx
+                .device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("per_draw_bind_group_layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: stages,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(size as u64),
+                        },
+                        count: None,
+                    }],
+                });
+            let alignment = ctx.capabilities.limits.min_uniform_buffer_offset_alignment as u64;
+            let stride = (size as u64).next_multiple_of(alignment);
+            let (buffer, bind_group) = create_slots(ctx, &layout, stride, 64);
+            UniformFallback {
+                layout,
+                buffer,
+                bind_group,
+                stride,
+                next_slot: 0,
+            }
+        });
+
+        Self {
+            stages,
+            fallback,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn uses_push_constants(&self) -> bool {
+        self.fallback.is_none()
+    }
+
+    /// Push constant ranges of the pipeline layout, empty when using the uniform fallback
+    pub fn push_constant_ranges(&self) -> Vec<PushConstantRange> {
+        match self.fallback {
+            Some(_) => vec![],
+            None => vec![PushConstantRange {
+                stages: self.stages,
+                range: 0..size_of::<T>() as u32,
+            }],
+        }
+    }
+
+    /// Layout to add to the pipeline at the group given to `wgsl_declaration`, only with the uniform fallback
+    pub fn bind_group_layout(&self) -> Option<&BindGroupLayout> {
+        self.fallback.as_ref().map(|f| &f.layout)
+    }
+
+    /// WGSL declaration of a `ty` variable named `name`, `group` is only used by the uniform fallback
+    pub fn wgsl_declaration(&self, name: &str, ty: &str, group: u32) -> String {
+        match self.fallback {
+            Some(_) => format!("@group({group}) @binding(0)\nvar<uniform> {name}: {ty};\n"),
+            None => format!("var<push_constant> {name}: {ty};\n"),
+        }
+    }
+
+    /// Call once per frame before the first `set`, reuses the uniform fallback slots
+    pub fn begin_frame(&mut self) {
+        if let Some(fallback) = &mut self.fallback {
+            fallback.next_slot = 0;
+        }
+    }
+
+    /// Set the data for the next draws of `render_pass`
+    pub fn set(&mut self, ctx: &Graphics, render_pass: &mut RenderPass, group: u32, data: &T) {
+        let Some(fallback) = &mut self.fallback else {
+            render_pass.set_push_constants(self.stages, 0, bytemuck::bytes_of(data));
+            return;
+        };
+
+        let capacity = fallback.buffer.size() / fallback.stride;
+        if fallback.next_slot >= capacity {
+            // Draws already recorded keep the previous buffer alive
+            (fallback.buffer, fallback.bind_group) =
+                create_slots(ctx, &fallback.layout, fallback.stride, capacity * 2);
+        }
+
+        let offset = fallback.next_slot * fallback.stride;
+        ctx.queue
+            .write_buffer(&fallback.buffer, offset, bytemuck::bytes_of(data));
+        render_pass.set_bind_group(group, &fallback.bind_group, &[offset as u32]);
+        fallback.next_slot += 1;
+    }
+}
+
+fn create_slots(
+    ctx: &Graphics,
+    layout: &BindGroupLayout,
+    stride: u64,
+    count: u64,
+) -> (wgpu::Buffer, BindGroup) {
+    let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("per_draw_buffer"),
+        size: stride * count,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("per_draw_bind_group"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(stride),
+            }),
+        }],
+    });
+    (buffer, bind_group)
+}
@@ -0,0 +1,271 @@
+use wgpu::ShaderStages;
+
+use crate::engine::{
+    graphics::{
+        Graphics,
+        camera::{CAMERA_WGSL, CameraUniform},
+        model::{
+            Model, Vertex,
+            instance::{INSTANCE_WGSL, InstanceData},
+        },
+        per_draw::PerDrawData,
+    },
+    maths::{Vec2f, Vec2u},
+};
+
+const PICKING_WGSL: &str = r#"
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@vertex
+fn vs_picking(@location(0) position: vec3<f32>, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let world_position = instance_matrix(instance) * vec4<f32>(position, 1.0);
+    return log_depth(camera.proj * camera.view * world_position, camera.depth);
+}
+
+@fragment
+fn fs_picking() -> @location(0) u32 {
+    return pick_id;
+}
+"#;
+
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Value written for the model at `index` of the drawn list, 0 is left for the background
+pub fn encode_pick_id(index: usize) -> u32 {
+    u32::try_from(index + 1).expect("Too many models to pick from")
+}
+
+/// Index of the model in the drawn list, None for the background
+pub fn decode_pick_id(id: u32) -> Option<usize> {
+    id.checked_sub(1).map(|index| index as usize)
+}
+
+/// Draws the id of each model into an integer texture and reads back the pixel under the
+/// cursor. Blocks until the GPU is done, meant for clicks rather than every frame.
+pub struct Picker {
+    pipeline: wgpu::RenderPipeline,
+    per_draw: PerDrawData<u32>,
+    /// Id and depth attachments, recreated when the viewport size changes
+    targets: Option<(Vec2u, wgpu::Texture, wgpu::TextureView)>,
+    readback: wgpu::Buffer,
+}
+
+impl Picker {
+    pub fn new(ctx: &Graphics, camera_uniform: &CameraUniform) -> Self {
+        let per_draw = PerDrawData::new(ctx, ShaderStages::FRAGMENT);
+        let module = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("picking"),
+                source: wgpu::ShaderSource::Wgsl(
+                    format!(
+                        "{CAMERA_WGSL}{INSTANCE_WGSL}{}{PICKING_WGSL}",
+                        per_draw.wgsl_declaration("pick_id", "u32", 1)
+                    )
+                    .into(),
+                ),
+            });
+        let mut bind_group_layouts = vec![&camera_uniform.bind_group_layout];
+        bind_group_layouts.extend(per_draw.bind_group_layout());
+        let layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("picking"),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &per_draw.push_constant_ranges(),
+            });
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("picking"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some("vs_picking"),
+                    buffers: &[Vertex::desc(), InstanceData::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some("fs_picking"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ID_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: Some(wgpu::Face::Back),
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                // Ids can't be resolved, always single sampled
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+        let readback = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Picking Readback Buffer"),
+            size: size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            per_draw,
+            targets: None,
+            readback,
+        }
+    }
+
+    /// Index in `models` of the model drawn under the cursor, in pixels from the top left
+    pub fn pick(
+        &mut self,
+        ctx: &Graphics,
+        camera_uniform: &CameraUniform,
+        cursor: Vec2f,
+        models: &[&Model],
+    ) -> Option<usize> {
+        let size = ctx.viewport_size;
+        if cursor.x < 0.0 || cursor.y < 0.0 {
+            return None;
+        }
+        let (x, y) = (cursor.x as u32, cursor.y as u32);
+        if x >= size.x || y >= size.y {
+            return None;
+        }
+
+        if self.targets.as_ref().is_none_or(|(s, ..)| *s != size) {
+            let (texture, depth_view) = create_targets(ctx, size);
+            self.targets = Some((size, texture, depth_view));
+        }
+        let Some((_, texture, depth_view)) = &self.targets else {
+            return None;
+        };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Picking Encoder"),
+            });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Picking renderpass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &camera_uniform.bind_group, &[]);
+            self.per_draw.begin_frame();
+            for (index, model) in models.iter().enumerate() {
+                self.per_draw
+                    .set(ctx, &mut render_pass, 1, &encode_pick_id(index));
+                model.draw(&mut render_pass);
+            }
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        ctx.queue.submit(Some(encoder.finish()));
+
+        let slice = self.readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| ());
+        ctx.device
+            .poll(wgpu::PollType::Wait)
+            .unwrap_or_else(|e| panic!("Failed to read back the picked id: {e}"));
+        let id = bytemuck::pod_read_unaligned::<u32>(&slice.get_mapped_range());
+        self.readback.unmap();
+        decode_pick_id(id)
+    }
+}
+
+fn create_targets(ctx: &Graphics, size: Vec2u) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = |label, format, usage| {
+        ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        })
+    };
+    let ids = texture(
+        "Picking Id Texture",
+        ID_FORMAT,
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    );
+    let depth = texture(
+        "Picking Depth Texture",
+        wgpu::TextureFormat::Depth32Float,
+        wgpu::TextureUsages::RENDER_ATTACHMENT,
+    );
+    (
+        ids,
+        depth.create_view(&wgpu::TextureViewDescriptor::default()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_ids_round_trip_and_leave_zero_for_the_background() {
+        for index in [0, 1, 41, u32::MAX as usize - 1] {
+            let id = encode_pick_id(index);
+            assert_ne!(id, 0);
+            assert_eq!(decode_pick_id(id), Some(index));
+        }
+        assert_eq!(decode_pick_id(0), None);
+    }
+}
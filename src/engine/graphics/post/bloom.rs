@@ -0,0 +1,430 @@
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, RenderPipeline, TextureView, util::DeviceExt,
+};
+
+use crate::engine::graphics::{
+    Graphics,
+    post::{
+        PostEffect, PostProcessor, create_input_bind_group, create_post_pipeline, fullscreen_pass,
+    },
+};
+
+/// Blurred images at half, quarter and eighth resolution
+const LEVELS: usize = 3;
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+const BLOOM_PARAMS_WGSL: &str = r#"
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+struct BloomParams {
+    // x: threshold, y: soft knee, z: intensity
+    params: vec4<f32>,
+};
+"#;
+
+const BRIGHT_PASS_WGSL: &str = r#"
+@group(1) @binding(0)
+var<uniform> bloom: BloomParams;
+
+// Keeps the part of the color above the threshold, with a quadratic ramp over the knee
+@fragment
+fn fs_bright_pass(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_input, s_input, in.uv, 0.0).rgb;
+    let threshold = bloom.params.x;
+    let knee = max(bloom.params.y, 0.0001);
+    let brightness = max(color.r, max(color.g, color.b));
+    let soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    let contribution = max(soft * soft / (4.0 * knee), brightness - threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+"#;
+
+const BLUR_WGSL: &str = r#"
+// 9 tap gaussian folded into 5 bilinear samples
+const OFFSETS: array<f32, 3> = array<f32, 3>(0.0, 1.3846153846, 3.2307692308);
+const WEIGHTS: array<f32, 3> = array<f32, 3>(0.2270270270, 0.3162162162, 0.0702702703);
+
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let step = direction / vec2<f32>(textureDimensions(t_input));
+    var sum = textureSampleLevel(t_input, s_input, uv, 0.0).rgb * WEIGHTS[0];
+    for (var i = 1; i < 3; i++) {
+        let offset = step * OFFSETS[i];
+        sum += textureSampleLevel(t_input, s_input, uv + offset, 0.0).rgb * WEIGHTS[i];
+        sum += textureSampleLevel(t_input, s_input, uv - offset, 0.0).rgb * WEIGHTS[i];
+    }
+    return vec4<f32>(sum, 1.0);
+}
+
+@fragment
+fn fs_blur_horizontal(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2<f32>(1.0, 0.0));
+}
+
+@fragment
+fn fs_blur_vertical(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2<f32>(0.0, 1.0));
+}
+
+// Linear filtering averages the 4 texels covered by each output texel
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSampleLevel(t_input, s_input, in.uv, 0.0);
+}
+"#;
+
+const COMPOSITE_WGSL: &str = r#"
+@group(1) @binding(0)
+var<uniform> bloom: BloomParams;
+
+@group(2) @binding(0)
+var t_bloom_0: texture_2d<f32>;
+@group(2) @binding(1)
+var t_bloom_1: texture_2d<f32>;
+@group(2) @binding(2)
+var t_bloom_2: texture_2d<f32>;
+
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_input, s_input, in.uv, 0.0);
+    let glow = textureSampleLevel(t_bloom_0, s_input, in.uv, 0.0).rgb
+        + textureSampleLevel(t_bloom_1, s_input, in.uv, 0.0).rgb
+        + textureSampleLevel(t_bloom_2, s_input, in.uv, 0.0).rgb;
+    return vec4<f32>(color.rgb + glow * bloom.params.z, color.a);
+}
+"#;
+
+/// Blurred image of one level, `a` holds the result and `b` the horizontal pass
+struct BloomLevel {
+    a: TextureView,
+    a_input: BindGroup,
+    b: TextureView,
+    b_input: BindGroup,
+}
+
+/// Glow around the bright areas of the scene.
+///
+/// The pixels above the threshold are extracted at half resolution, downsampled twice and each
+/// level is blurred with a separable gaussian, then the levels are added onto the scene. As
+/// the scene is rendered in the surface format, only colors close to white can bloom.
+pub struct Bloom {
+    pub enabled: bool,
+    /// Brightness from which pixels bloom, in [0, 1]
+    pub threshold: f32,
+    /// Width of the ramp below the threshold, softens the cut
+    pub knee: f32,
+    /// Strength of the glow added to the scene
+    pub intensity: f32,
+    bright_pass: RenderPipeline,
+    blur_horizontal: RenderPipeline,
+    blur_vertical: RenderPipeline,
+    downsample: RenderPipeline,
+    composite: RenderPipeline,
+    params_buffer: wgpu::Buffer,
+    params_bind_group: BindGroup,
+    input_layout: BindGroupLayout,
+    levels_layout: BindGroupLayout,
+    sampler: wgpu::Sampler,
+    levels: [BloomLevel; LEVELS],
+    levels_bind_group: BindGroup,
+}
+
+impl Bloom {
+    pub fn new(ctx: &Graphics, post: &PostProcessor) -> Self {
+        let params_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("bloom_params"),
+                contents: bytemuck::cast_slice(&[0.0f32; 4]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let params_layout = create_params_layout(ctx);
+        let params_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bloom_params_bind_group"),
+            layout: &params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+        let levels_layout = create_levels_layout(ctx);
+
+        let input_layout = &post.input_layout;
+        // Every pass but the composite writes to the level textures
+        let pipeline = |label: &str, source: &str, entry: &str, layouts: &[&BindGroupLayout]| {
+            create_post_pipeline(
+                ctx,
+                label,
+                &format!("{BLOOM_PARAMS_WGSL}{source}"),
+                entry,
+                layouts,
+                FORMAT,
+            )
+        };
+        let bright_pass = pipeline(
+            "bloom_bright_pass",
+            BRIGHT_PASS_WGSL,
+            "fs_bright_pass",
+            &[input_layout, &params_layout],
+        );
+        let blur_horizontal = pipeline(
+            "bloom_blur_horizontal",
+            BLUR_WGSL,
+            "fs_blur_horizontal",
+            &[input_layout],
+        );
+        let blur_vertical = pipeline(
+            "bloom_blur_vertical",
+            BLUR_WGSL,
+            "fs_blur_vertical",
+            &[input_layout],
+        );
+        let downsample = pipeline(
+            "bloom_downsample",
+            BLUR_WGSL,
+            "fs_downsample",
+            &[input_layout],
+        );
+        let composite = create_post_pipeline(
+            ctx,
+            "bloom_composite",
+            &format!("{BLOOM_PARAMS_WGSL}{COMPOSITE_WGSL}"),
+            "fs_composite",
+            &[input_layout, &params_layout, &levels_layout],
+            ctx.surface_format,
+        );
+
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("bloom_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let levels = create_levels(ctx, input_layout, &sampler);
+        let levels_bind_group = create_levels_bind_group(ctx, &levels_layout, &levels);
+
+        Self {
+            enabled: true,
+            threshold: 0.8,
+            knee: 0.1,
+            intensity: 0.6,
+            bright_pass,
+            blur_horizontal,
+            blur_vertical,
+            downsample,
+            composite,
+            params_buffer,
+            params_bind_group,
+            input_layout: input_layout.clone(),
+            levels_layout,
+            sampler,
+            levels,
+            levels_bind_group,
+        }
+    }
+}
+
+impl PostEffect for Bloom {
+    fn name(&self) -> &str {
+        "Bloom"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn render(
+        &mut self,
+        ctx: &Graphics,
+        encoder: &mut CommandEncoder,
+        input: &BindGroup,
+        output: &TextureView,
+    ) {
+        ctx.queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[
+                self.threshold,
+                self.knee.max(0.0),
+                self.intensity.max(0.0),
+                0.0,
+            ]),
+        );
+
+        fullscreen_pass(
+            encoder,
+            "bloom_bright_pass",
+            &self.bright_pass,
+            &[input, &self.params_bind_group],
+            &self.levels[0].a,
+        );
+        for i in 0..LEVELS {
+            if i > 0 {
+                fullscreen_pass(
+                    encoder,
+                    "bloom_downsample",
+                    &self.downsample,
+                    &[&self.levels[i - 1].a_input],
+                    &self.levels[i].a,
+                );
+            }
+            let level = &self.levels[i];
+            fullscreen_pass(
+                encoder,
+                "bloom_blur_horizontal",
+                &self.blur_horizontal,
+                &[&level.a_input],
+                &level.b,
+            );
+            fullscreen_pass(
+                encoder,
+                "bloom_blur_vertical",
+                &self.blur_vertical,
+                &[&level.b_input],
+                &level.a,
+            );
+        }
+        fullscreen_pass(
+            encoder,
+            "bloom_composite",
+            &self.composite,
+            &[input, &self.params_bind_group, &self.levels_bind_group],
+            output,
+        );
+    }
+
+    fn on_resize(&mut self, ctx: &Graphics, _depth_view: &TextureView) {
+        self.levels = create_levels(ctx, &self.input_layout, &self.sampler);
+        self.levels_bind_group = create_levels_bind_group(ctx, &self.levels_layout, &self.levels);
+    }
+
+    #[cfg(debug_assertions)]
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.add(egui::Slider::new(&mut self.threshold, 0.0..=1.0).text("Threshold"));
+        ui.add(egui::Slider::new(&mut self.knee, 0.0..=0.5).text("Knee"));
+        ui.add(egui::Slider::new(&mut self.intensity, 0.0..=4.0).text("Intensity"));
+    }
+}
+
+fn create_params_layout(ctx: &Graphics) -> BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloom_params_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+}
+
+/// The blurred level textures, sampled with the input sampler
+fn create_levels_layout(ctx: &Graphics) -> BindGroupLayout {
+    let entries: [wgpu::BindGroupLayoutEntry; LEVELS] =
+        std::array::from_fn(|i| wgpu::BindGroupLayoutEntry {
+            binding: i as u32,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        });
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloom_levels_bind_group_layout"),
+            entries: &entries,
+        })
+}
+
+fn create_levels_bind_group(
+    ctx: &Graphics,
+    layout: &BindGroupLayout,
+    levels: &[BloomLevel; LEVELS],
+) -> BindGroup {
+    let entries: [wgpu::BindGroupEntry; LEVELS] = std::array::from_fn(|i| wgpu::BindGroupEntry {
+        binding: i as u32,
+        resource: wgpu::BindingResource::TextureView(&levels[i].a),
+    });
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("bloom_levels_bind_group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+fn create_levels(
+    ctx: &Graphics,
+    input_layout: &BindGroupLayout,
+    sampler: &wgpu::Sampler,
+) -> [BloomLevel; LEVELS] {
+    std::array::from_fn(|i| {
+        // Half resolution for the first level, then halved again for each level
+        let divisor = 2 << i;
+        let view = |name: &str| {
+            ctx.device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(&format!("bloom_{i}_{name}")),
+                    size: wgpu::Extent3d {
+                        width: (ctx.viewport_size.x / divisor).max(1),
+                        height: (ctx.viewport_size.y / divisor).max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let (a, b) = (view("a"), view("b"));
+        BloomLevel {
+            a_input: create_input_bind_group(ctx, input_layout, &a, sampler),
+            b_input: create_input_bind_group(ctx, input_layout, &b, sampler),
+            a,
+            b,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::graphics::renderer::Renderer;
+
+    #[test]
+    fn bloom_pipelines_build_and_render() {
+        let Some(mut ctx) = Graphics::headless(64, 64) else {
+            return;
+        };
+        let mut renderer = Renderer::new(&ctx);
+        let bloom = Bloom::new(&ctx, &renderer.post);
+        renderer.post.add_effect(Box::new(bloom));
+
+        // The levels are recreated at the new size
+        for size in [(64, 64), (30, 17)] {
+            ctx.resize(size);
+            renderer.on_resize(&ctx);
+            let mut frame = ctx.next_frame().unwrap();
+            renderer.begin_frame(&mut ctx, &mut frame);
+            renderer
+                .model
+                .render(&ctx, &mut frame, &renderer.camera_uniform);
+            renderer.end_frame(&ctx, &mut frame);
+            ctx.present(frame);
+        }
+        ctx.device.poll(wgpu::PollType::Wait).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, RenderPipeline, TextureView, util::DeviceExt,
+    wgt::TextureDataOrder,
+};
+
+use crate::engine::graphics::{
+    Graphics,
+    post::{PostEffect, PostProcessor, create_post_pipeline, fullscreen_pass},
+};
+
+const COLOR_GRADE_WGSL: &str = r#"
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+struct ColorGradeParams {
+    // x: intensity, y: lut size
+    params: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var t_lut: texture_3d<f32>;
+@group(1) @binding(1)
+var s_lut: sampler;
+@group(1) @binding(2)
+var<uniform> grade: ColorGradeParams;
+
+fn to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return pow(max(c, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.2));
+}
+
+fn to_linear(c: vec3<f32>) -> vec3<f32> {
+    return pow(max(c, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+@fragment
+fn fs_color_grade(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    let size = grade.params.y;
+    // Sample texel centers so the edges of the LUT map to 0 and 1
+    let coords = clamp(to_srgb(color.rgb), vec3<f32>(0.0), vec3<f32>(1.0)) * (size - 1.0) / size + 0.5 / size;
+    let graded = to_linear(textureSample(t_lut, s_lut, coords).rgb);
+    return vec4<f32>(mix(color.rgb, graded, grade.params.x), color.a);
+}
+"#;
+
+#[derive(Debug)]
+pub enum LutError {
+    Image(image::ImageError),
+    /// A strip LUT of size N must be N*N pixels wide and N pixels high
+    Malformed {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl std::fmt::Display for LutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Image(e) => write!(f, "failed to decode LUT image: {e}"),
+            Self::Malformed { width, height } => write!(
+                f,
+                "malformed LUT strip {width}x{height}, expected a width equal to the height squared"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LutError {}
+
+impl From<image::ImageError> for LutError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+/// Remaps the final colors through a 3D lookup table
+pub struct ColorGrade {
+    pub enabled: bool,
+    /// Blend between the ungraded (0) and graded (1) image
+    pub intensity: f32,
+    lut_size: u32,
+    pipeline: RenderPipeline,
+    params_buffer: wgpu::Buffer,
+    bind_group: BindGroup,
+}
+
+impl ColorGrade {
+    /// Load a LUT from a horizontal strip image: N slices of NxN pixels side by side,
+    /// blue increasing with the slice index, red along x and green along y.
+    pub fn from_strip_bytes(
+        ctx: &Graphics,
+        post: &PostProcessor,
+        bytes: &[u8],
+    ) -> Result<Self, LutError> {
+        let img = image::load_from_memory(bytes)?.to_rgba8();
+        let (width, height) = img.dimensions();
+        let size = height;
+        if size < 2 || width != size * size {
+            return Err(LutError::Malformed { width, height });
+        }
+
+        // Reorder the strip into the depth slices of a 3D texture
+        let mut data = Vec::with_capacity(img.len());
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    data.extend_from_slice(&img.get_pixel(z * size + x, y).0);
+                }
+            }
+        }
+
+        let lut = ctx.device.create_texture_with_data(
+            &ctx.queue,
+            &wgpu::TextureDescriptor {
+                label: Some("color_grade_lut"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: size,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::default(),
+            &data,
+        );
+        let lut_view = lut.create_view(&wgpu::TextureViewDescriptor::default());
+        let lut_sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("color_grade_lut_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let intensity = 1.0;
+        let params_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("color_grade_params"),
+                contents: bytemuck::cast_slice(&[intensity, size as f32, 0.0, 0.0]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let layout = create_bind_group_layout(ctx);
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("color_grade_bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&lut_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&lut_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline = create_post_pipeline(
+            ctx,
+            "color_grade",
+            COLOR_GRADE_WGSL,
+            "fs_color_grade",
+            &[&post.input_layout, &layout],
+            ctx.surface_format,
+        );
+
+        Ok(Self {
+            enabled: true,
+            intensity,
+            lut_size: size,
+            pipeline,
+            params_buffer,
+            bind_group,
+        })
+    }
+
+    pub fn lut_size(&self) -> u32 {
+        self.lut_size
+    }
+}
+
+impl PostEffect for ColorGrade {
+    fn name(&self) -> &str {
+        "Color grade"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn render(
+        &mut self,
+        ctx: &Graphics,
+        encoder: &mut CommandEncoder,
+        input: &BindGroup,
+        output: &TextureView,
+    ) {
+        ctx.queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[self.intensity.clamp(0.0, 1.0), self.lut_size as f32]),
+        );
+        fullscreen_pass(
+            encoder,
+            "color_grade",
+            &self.pipeline,
+            &[input, &self.bind_group],
+            output,
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.add(egui::Slider::new(&mut self.intensity, 0.0..=1.0).text("Intensity"));
+    }
+}
+
+fn create_bind_group_layout(ctx: &Graphics) -> BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("color_grade_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        })
+}
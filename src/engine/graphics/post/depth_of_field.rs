@@ -0,0 +1,210 @@
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, RenderPipeline, TextureView, util::DeviceExt,
+};
+
+use crate::engine::graphics::{
+    Graphics,
+    camera::{CAMERA_WGSL, CameraUniform},
+    particles::SceneDepthUniform,
+    post::{PostEffect, PostProcessor, create_post_pipeline, fullscreen_pass},
+};
+
+const DEPTH_OF_FIELD_WGSL: &str = r#"
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+@group(1) @binding(0)
+var t_depth: SCENE_DEPTH_TYPE;
+
+struct DepthOfFieldParams {
+    // x: focus distance, y: focal range, z: blur radius in pixels
+    params: vec4<f32>,
+    // See `CameraUniform::depth_planes`
+    planes: vec4<f32>,
+};
+
+@group(2) @binding(0)
+var<uniform> dof: DepthOfFieldParams;
+
+const TAPS: i32 = 24;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+// Blur amount in [0, 1], 0 inside the focal range around the focus distance
+fn circle_of_confusion(distance: f32) -> f32 {
+    let range = max(dof.params.y, 0.0001);
+    return clamp((abs(distance - dof.params.x) - range * 0.5) / range, 0.0, 1.0);
+}
+
+@fragment
+fn fs_depth_of_field(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let depth = textureLoad(t_depth, vec2<i32>(in.position.xy), 0);
+    let coc = circle_of_confusion(linearize_depth(depth, dof.planes));
+    let center = textureSampleLevel(t_input, s_input, in.uv, 0.0);
+    let radius = coc * dof.params.z;
+    if (radius < 0.5) {
+        return center;
+    }
+
+    // Gather over a spiral disk of radius `radius` pixels
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_input));
+    var sum = center.rgb;
+    for (var i = 0; i < TAPS; i++) {
+        let r = sqrt((f32(i) + 0.5) / f32(TAPS)) * radius;
+        let a = f32(i) * GOLDEN_ANGLE;
+        let offset = vec2<f32>(cos(a), sin(a)) * r * texel;
+        sum += textureSampleLevel(t_input, s_input, in.uv + offset, 0.0).rgb;
+    }
+    return vec4<f32>(sum / f32(TAPS + 1), center.a);
+}
+"#;
+
+/// Blurs the scene away from a focus distance, read from the depth buffer
+pub struct DepthOfField {
+    pub enabled: bool,
+    /// Distance from the camera that stays sharp
+    pub focus_distance: f32,
+    /// Depth range around the focus distance that stays sharp, the blur ramps up over the same range
+    pub focal_range: f32,
+    /// Blur radius in pixels at full circle of confusion
+    pub blur_strength: f32,
+    pipeline: RenderPipeline,
+    depth: SceneDepthUniform,
+    /// `CameraUniform::depth_planes` of the frame
+    depth_planes: [f32; 4],
+    input_layout: BindGroupLayout,
+    params_layout: BindGroupLayout,
+    params_buffer: wgpu::Buffer,
+    params_bind_group: BindGroup,
+}
+
+impl DepthOfField {
+    pub fn new(ctx: &Graphics, post: &PostProcessor, depth_view: &TextureView) -> Self {
+        let depth = SceneDepthUniform::new(ctx, depth_view);
+        let params_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("depth_of_field_params"),
+                contents: bytemuck::cast_slice(&[0.0f32; 8]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let params_layout = create_bind_group_layout(ctx);
+        let params_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("depth_of_field_bind_group"),
+            layout: &params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline = create_pipeline(ctx, &post.input_layout, &depth, &params_layout);
+
+        Self {
+            enabled: true,
+            focus_distance: 5.0,
+            focal_range: 2.0,
+            blur_strength: 8.0,
+            pipeline,
+            depth,
+            depth_planes: [0.0; 4],
+            input_layout: post.input_layout.clone(),
+            params_layout,
+            params_buffer,
+            params_bind_group,
+        }
+    }
+}
+
+impl PostEffect for DepthOfField {
+    fn name(&self) -> &str {
+        "Depth of field"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn render(
+        &mut self,
+        ctx: &Graphics,
+        encoder: &mut CommandEncoder,
+        input: &BindGroup,
+        output: &TextureView,
+    ) {
+        ctx.queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[
+                [
+                    self.focus_distance,
+                    self.focal_range,
+                    self.blur_strength.max(0.0),
+                    0.0,
+                ],
+                self.depth_planes,
+            ]),
+        );
+        fullscreen_pass(
+            encoder,
+            "depth_of_field",
+            &self.pipeline,
+            &[input, &self.depth.bind_group, &self.params_bind_group],
+            output,
+        );
+    }
+
+    fn update_camera(&mut self, camera_uniform: &CameraUniform) {
+        self.depth_planes = camera_uniform.depth_planes();
+    }
+
+    fn on_resize(&mut self, ctx: &Graphics, depth_view: &TextureView) {
+        if self.depth.on_resize(ctx, depth_view) {
+            self.pipeline =
+                create_pipeline(ctx, &self.input_layout, &self.depth, &self.params_layout);
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.add(egui::Slider::new(&mut self.focus_distance, 0.1..=100.0).text("Focus distance"));
+        ui.add(egui::Slider::new(&mut self.focal_range, 0.01..=50.0).text("Focal range"));
+        ui.add(egui::Slider::new(&mut self.blur_strength, 0.0..=32.0).text("Blur strength"));
+    }
+}
+
+fn create_pipeline(
+    ctx: &Graphics,
+    input_layout: &BindGroupLayout,
+    depth: &SceneDepthUniform,
+    params_layout: &BindGroupLayout,
+) -> RenderPipeline {
+    let source = DEPTH_OF_FIELD_WGSL.replace("SCENE_DEPTH_TYPE", depth.wgsl_type());
+    create_post_pipeline(
+        ctx,
+        "depth_of_field",
+        &format!("{CAMERA_WGSL}{source}"),
+        "fs_depth_of_field",
+        &[input_layout, &depth.bind_group_layout, params_layout],
+        ctx.surface_format,
+    )
+}
+
+fn create_bind_group_layout(ctx: &Graphics) -> BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("depth_of_field_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+}
@@ -0,0 +1,406 @@
+use std::time::Instant;
+
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, RenderPipeline, TextureView, util::DeviceExt,
+};
+
+use crate::engine::graphics::{
+    Graphics,
+    post::{PostEffect, PostProcessor, create_post_pipeline, fullscreen_pass},
+};
+
+const EXPOSURE_STATE_WGSL: &str = r#"
+struct ExposureState {
+    exposure: f32,
+};
+"#;
+
+const EXPOSURE_WGSL: &str = r#"
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+@group(1) @binding(0)
+var<storage, read> state: ExposureState;
+
+struct TonemapParams {
+    // x: tonemap operator, 0 clamps, 1 is Reinhard
+    params: vec4<f32>,
+};
+
+@group(1) @binding(1)
+var<uniform> tonemap: TonemapParams;
+
+@fragment
+fn fs_exposure(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    var exposed = color.rgb * state.exposure;
+    if (tonemap.params.x > 0.5) {
+        exposed = exposed / (vec3<f32>(1.0) + exposed);
+    }
+    return vec4<f32>(clamp(exposed, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+}
+"#;
+
+const AUTO_EXPOSURE_WGSL: &str = r#"
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+struct AutoExposureParams {
+    // x: delta time, y: adaptation speed, z: key (target average luminance)
+    params: vec4<f32>,
+    // x: min exposure, y: max exposure
+    range: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<storage, read_write> state: ExposureState;
+@group(1) @binding(1)
+var<uniform> auto_exposure: AutoExposureParams;
+
+const GROUP_SIZE: u32 = 16u;
+// Samples per thread along each axis
+const SAMPLES: u32 = 4u;
+
+var<workgroup> log_luminance: array<f32, 256>;
+
+@compute @workgroup_size(16, 16)
+fn cs_auto_exposure(@builtin(local_invocation_id) id: vec3<u32>) {
+    let grid = GROUP_SIZE * SAMPLES;
+    var sum = 0.0;
+    for (var y = 0u; y < SAMPLES; y++) {
+        for (var x = 0u; x < SAMPLES; x++) {
+            let cell = id.xy * SAMPLES + vec2<u32>(x, y);
+            let uv = (vec2<f32>(cell) + 0.5) / f32(grid);
+            let color = textureSampleLevel(t_input, s_input, uv, 0.0).rgb;
+            let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+            sum += log(max(luminance, 0.0001));
+        }
+    }
+    let index = id.y * GROUP_SIZE + id.x;
+    log_luminance[index] = sum / f32(SAMPLES * SAMPLES);
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride >>= 1u) {
+        if (index < stride) {
+            log_luminance[index] += log_luminance[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        let average = exp(log_luminance[0] / 256.0);
+        let target_exposure = clamp(
+            auto_exposure.params.z / average,
+            auto_exposure.range.x,
+            auto_exposure.range.y,
+        );
+        let blend = 1.0 - exp(-auto_exposure.params.x * auto_exposure.params.y);
+        state.exposure = mix(state.exposure, target_exposure, blend);
+    }
+}
+"#;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExposureMode {
+    /// Fixed `Exposure::exposure` value
+    #[default]
+    Manual,
+    /// Adapts over time to the average luminance of the scene
+    Auto,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemap {
+    /// Exposed colors above 1 are clipped
+    #[default]
+    Clamp,
+    /// Compresses highlights, `c / (1 + c)`
+    Reinhard,
+}
+
+/// Scales the scene colors by an exposure then tonemaps them.
+///
+/// The auto mode measures the average log luminance of the input in a compute pass and keeps the
+/// adapted exposure on the GPU, nothing is read back. Note that the scene is rendered in the
+/// surface format, so values brighter than white are already clipped before this effect.
+pub struct Exposure {
+    pub enabled: bool,
+    pub mode: ExposureMode,
+    /// Exposure of the manual mode, also the starting point of the auto mode
+    pub exposure: f32,
+    /// Rate at which the auto mode reaches its target, higher is faster
+    pub adaptation_speed: f32,
+    /// Average luminance the auto mode aims for, middle grey by default
+    pub key: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    pub tonemap: Tonemap,
+    pipeline: RenderPipeline,
+    auto_pipeline: wgpu::ComputePipeline,
+    state_buffer: wgpu::Buffer,
+    tonemap_buffer: wgpu::Buffer,
+    auto_buffer: wgpu::Buffer,
+    bind_group: BindGroup,
+    auto_bind_group: BindGroup,
+    /// Manual value uploaded to the state buffer, None after the auto mode changed it
+    uploaded: Option<f32>,
+    last_render: Option<Instant>,
+}
+
+impl Exposure {
+    pub fn new(ctx: &Graphics, post: &PostProcessor) -> Self {
+        let exposure = 1.0;
+        let state_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("exposure_state"),
+                contents: bytemuck::cast_slice(&[exposure]),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            });
+        let tonemap_buffer = create_uniform_buffer(ctx, "exposure_tonemap", 16);
+        let auto_buffer = create_uniform_buffer(ctx, "auto_exposure_params", 32);
+
+        let layout = create_bind_group_layout(ctx, wgpu::ShaderStages::FRAGMENT, true);
+        let bind_group = create_bind_group(ctx, &layout, &state_buffer, &tonemap_buffer);
+        let pipeline = create_post_pipeline(
+            ctx,
+            "exposure",
+            &format!("{EXPOSURE_STATE_WGSL}{EXPOSURE_WGSL}"),
+            "fs_exposure",
+            &[&post.input_layout, &layout],
+            ctx.surface_format,
+        );
+
+        let auto_layout = create_bind_group_layout(ctx, wgpu::ShaderStages::COMPUTE, false);
+        let auto_bind_group = create_bind_group(ctx, &auto_layout, &state_buffer, &auto_buffer);
+        let auto_pipeline = create_auto_pipeline(ctx, &post.input_layout, &auto_layout);
+
+        Self {
+            enabled: true,
+            mode: ExposureMode::default(),
+            exposure,
+            adaptation_speed: 1.5,
+            key: 0.18,
+            min_exposure: 0.1,
+            max_exposure: 10.0,
+            tonemap: Tonemap::default(),
+            pipeline,
+            auto_pipeline,
+            state_buffer,
+            tonemap_buffer,
+            auto_buffer,
+            bind_group,
+            auto_bind_group,
+            uploaded: Some(exposure),
+            last_render: None,
+        }
+    }
+}
+
+impl PostEffect for Exposure {
+    fn name(&self) -> &str {
+        "Exposure"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn render(
+        &mut self,
+        ctx: &Graphics,
+        encoder: &mut CommandEncoder,
+        input: &BindGroup,
+        output: &TextureView,
+    ) {
+        let now = Instant::now();
+        let dt = self
+            .last_render
+            .map_or(0.0, |last| (now - last).as_secs_f32());
+        self.last_render = Some(now);
+
+        match self.mode {
+            ExposureMode::Manual if self.uploaded != Some(self.exposure) => {
+                ctx.queue.write_buffer(
+                    &self.state_buffer,
+                    0,
+                    bytemuck::cast_slice(&[self.exposure]),
+                );
+                self.uploaded = Some(self.exposure);
+            }
+            ExposureMode::Manual => {}
+            ExposureMode::Auto => {
+                let params = [
+                    dt,
+                    self.adaptation_speed,
+                    self.key,
+                    0.0,
+                    self.min_exposure,
+                    self.max_exposure.max(self.min_exposure),
+                    0.0,
+                    0.0,
+                ];
+                ctx.queue
+                    .write_buffer(&self.auto_buffer, 0, bytemuck::cast_slice(&params));
+                self.uploaded = None;
+
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("auto_exposure"),
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&self.auto_pipeline);
+                pass.set_bind_group(0, input, &[]);
+                pass.set_bind_group(1, &self.auto_bind_group, &[]);
+                pass.dispatch_workgroups(1, 1, 1);
+            }
+        }
+
+        let operator = match self.tonemap {
+            Tonemap::Clamp => 0.0f32,
+            Tonemap::Reinhard => 1.0,
+        };
+        ctx.queue.write_buffer(
+            &self.tonemap_buffer,
+            0,
+            bytemuck::cast_slice(&[operator, 0.0, 0.0, 0.0]),
+        );
+        fullscreen_pass(
+            encoder,
+            "exposure",
+            &self.pipeline,
+            &[input, &self.bind_group],
+            output,
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.mode, ExposureMode::Manual, "Manual");
+            ui.selectable_value(&mut self.mode, ExposureMode::Auto, "Auto");
+        });
+        match self.mode {
+            ExposureMode::Manual => {
+                ui.add(
+                    egui::Slider::new(&mut self.exposure, 0.01..=10.0)
+                        .logarithmic(true)
+                        .text("Exposure"),
+                );
+            }
+            ExposureMode::Auto => {
+                ui.add(
+                    egui::Slider::new(&mut self.adaptation_speed, 0.1..=10.0)
+                        .text("Adaptation speed"),
+                );
+                ui.add(egui::Slider::new(&mut self.key, 0.01..=1.0).text("Key"));
+                ui.add(
+                    egui::Slider::new(&mut self.min_exposure, 0.01..=10.0)
+                        .logarithmic(true)
+                        .text("Min exposure"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.max_exposure, 0.01..=100.0)
+                        .logarithmic(true)
+                        .text("Max exposure"),
+                );
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.tonemap, Tonemap::Clamp, "Clamp");
+            ui.selectable_value(&mut self.tonemap, Tonemap::Reinhard, "Reinhard");
+        });
+    }
+}
+
+fn create_uniform_buffer(ctx: &Graphics, label: &str, size: u64) -> wgpu::Buffer {
+    ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Exposure state storage buffer at binding 0, parameters uniform at binding 1
+fn create_bind_group_layout(
+    ctx: &Graphics,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> BindGroupLayout {
+    let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("exposure_bind_group_layout"),
+            entries: &[
+                buffer_entry(0, wgpu::BufferBindingType::Storage { read_only }),
+                buffer_entry(1, wgpu::BufferBindingType::Uniform),
+            ],
+        })
+}
+
+fn create_bind_group(
+    ctx: &Graphics,
+    layout: &BindGroupLayout,
+    state: &wgpu::Buffer,
+    params: &wgpu::Buffer,
+) -> BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("exposure_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: state.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: params.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+fn create_auto_pipeline(
+    ctx: &Graphics,
+    input_layout: &BindGroupLayout,
+    layout: &BindGroupLayout,
+) -> wgpu::ComputePipeline {
+    let module = ctx
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("auto_exposure"),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{EXPOSURE_STATE_WGSL}{AUTO_EXPOSURE_WGSL}").into(),
+            ),
+        });
+    let pipeline_layout = ctx
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("auto_exposure"),
+            bind_group_layouts: &[input_layout, layout],
+            push_constant_ranges: &[],
+        });
+    ctx.device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("auto_exposure"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: Some("cs_auto_exposure"),
+            compilation_options: Default::default(),
+            cache: None,
+        })
+}
@@ -0,0 +1,292 @@
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, CommandEncoder, RenderPipeline, SamplerBindingType,
+    ShaderStages, TextureSampleType, TextureView, TextureViewDimension,
+};
+
+use crate::engine::graphics::{Frame, Graphics, camera::CameraUniform};
+
+pub mod bloom;
+pub mod color_grade;
+pub mod depth_of_field;
+pub mod exposure;
+
+/// Fullscreen triangle vertex shader shared by post effects, prepend it to the effect shader.
+/// The fragment stage receives `in.uv` with (0, 0) at the top left.
+pub const FULLSCREEN_WGSL: &str = r#"
+struct FullscreenOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+"#;
+
+/// A fullscreen pass reading the previous image of the chain
+pub trait PostEffect {
+    fn name(&self) -> &str;
+    fn enabled(&self) -> bool {
+        true
+    }
+    fn render(
+        &mut self,
+        ctx: &Graphics,
+        encoder: &mut CommandEncoder,
+        input: &BindGroup,
+        output: &TextureView,
+    );
+    /// Called with the camera of the frame before `render`, for effects reading the scene depth
+    fn update_camera(&mut self, _camera_uniform: &CameraUniform) {}
+    /// The scene depth texture is recreated on resize, effects reading it must rebind it
+    fn on_resize(&mut self, _ctx: &Graphics, _depth_view: &TextureView) {}
+    /// Settings shown in the editor post processing panel
+    #[cfg(debug_assertions)]
+    fn ui(&mut self, _ui: &mut egui::Ui) {}
+}
+
+struct PostTarget {
+    view: TextureView,
+    bind_group: BindGroup,
+}
+
+/// Redirects the scene into an offscreen texture, then runs the enabled effects in order,
+/// the last one writing to the surface. Does nothing when no effect is enabled.
+pub struct PostProcessor {
+    pub effects: Vec<Box<dyn PostEffect>>,
+    /// Bind group layout of an effect input: the image at binding 0 and a linear sampler at binding 1,
+    /// visible to fragment and compute shaders
+    pub input_layout: BindGroupLayout,
+    sampler: wgpu::Sampler,
+    targets: [PostTarget; 2],
+    surface_view: Option<TextureView>,
+}
+
+impl PostProcessor {
+    pub fn new(ctx: &Graphics) -> Self {
+        let input_layout = ctx
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("post_input_bind_group_layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: TextureViewDimension::D2,
+                            sample_type: TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let targets = create_targets(ctx, &input_layout, &sampler);
+
+        Self {
+            effects: vec![],
+            input_layout,
+            sampler,
+            targets,
+            surface_view: None,
+        }
+    }
+
+    pub fn add_effect(&mut self, effect: Box<dyn PostEffect>) {
+        self.effects.push(effect);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.effects.iter().any(|e| e.enabled())
+    }
+
+    /// Redirect `frame.view` to the offscreen scene target, call before rendering the scene
+    pub fn begin_frame(&mut self, frame: &mut Frame) {
+        if self.is_active() {
+            let scene_view = self.targets[0].view.clone();
+            self.surface_view = Some(std::mem::replace(&mut frame.view, scene_view));
+        }
+    }
+
+    /// Run the effect chain and restore `frame.view` to the surface
+    pub fn end_frame(&mut self, ctx: &Graphics, frame: &mut Frame, camera_uniform: &CameraUniform) {
+        let Some(surface_view) = self.surface_view.take() else {
+            return;
+        };
+
+        let last = self.effects.iter().rposition(|e| e.enabled());
+        let mut input = 0;
+        for (i, effect) in self.effects.iter_mut().enumerate() {
+            if !effect.enabled() {
+                continue;
+            }
+            effect.update_camera(camera_uniform);
+            let output = if Some(i) == last {
+                &surface_view
+            } else {
+                &self.targets[1 - input].view
+            };
+            effect.render(
+                ctx,
+                &mut frame.encoder,
+                &self.targets[input].bind_group,
+                output,
+            );
+            input = 1 - input;
+        }
+
+        frame.view = surface_view;
+    }
+
+    pub fn on_resize(&mut self, ctx: &Graphics, depth_view: &TextureView) {
+        self.targets = create_targets(ctx, &self.input_layout, &self.sampler);
+        for effect in &mut self.effects {
+            effect.on_resize(ctx, depth_view);
+        }
+    }
+}
+
+/// Bind an effect input made of an image and a sampler, matching `PostProcessor::input_layout`
+pub fn create_input_bind_group(
+    ctx: &Graphics,
+    layout: &BindGroupLayout,
+    view: &TextureView,
+    sampler: &wgpu::Sampler,
+) -> BindGroup {
+    ctx.device.create_bind_group(&BindGroupDescriptor {
+        label: Some("post_input_bind_group"),
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+fn create_targets(
+    ctx: &Graphics,
+    layout: &BindGroupLayout,
+    sampler: &wgpu::Sampler,
+) -> [PostTarget; 2] {
+    [0, 1].map(|i| {
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("post_target_{i}")),
+            size: wgpu::Extent3d {
+                width: ctx.viewport_size.x.max(1),
+                height: ctx.viewport_size.y.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ctx.surface_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = create_input_bind_group(ctx, layout, &view, sampler);
+        PostTarget { view, bind_group }
+    })
+}
+
+/// Pipeline drawing a fullscreen triangle with `FULLSCREEN_WGSL` and the given fragment entry point
+pub fn create_post_pipeline(
+    ctx: &Graphics,
+    label: &str,
+    fragment_source: &str,
+    fragment_entry: &str,
+    bind_group_layouts: &[&BindGroupLayout],
+    format: wgpu::TextureFormat,
+) -> RenderPipeline {
+    let module = ctx
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(format!("{FULLSCREEN_WGSL}{fragment_source}").into()),
+        });
+    let layout = ctx
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some(fragment_entry),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+}
+
+/// Draw a fullscreen triangle into `output` with the given bind groups
+pub fn fullscreen_pass(
+    encoder: &mut CommandEncoder,
+    label: &str,
+    pipeline: &RenderPipeline,
+    bind_groups: &[&BindGroup],
+    output: &TextureView,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        ..Default::default()
+    });
+    pass.set_pipeline(pipeline);
+    for (i, bind_group) in bind_groups.iter().enumerate() {
+        pass.set_bind_group(i as u32, *bind_group, &[]);
+    }
+    pass.draw(0..3, 0..1);
+}
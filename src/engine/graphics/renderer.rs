@@ -1,52 +1,184 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
+#[cfg(debug_assertions)]
+use crate::engine::graphics::inspector::{UniformField, UniformInspector};
 use crate::engine::graphics::{
-    Graphics,
+    Frame, Graphics,
     camera::{Camera, CameraUniform},
-    model::renderer::ModelRenderer,
+    color::Color3f,
+    light::{Light, LightUniform},
+    line::Grid,
+    memory::{MemoryTracker, MemoryUsage},
+    model::{
+        renderer::{ModelId, ModelRenderer},
+        texture::ModelTexture,
+    },
+    post::PostProcessor,
+    skybox::Skybox,
+    sprite::SpriteBatch,
+    text::{BitmapFont, TextRenderer},
 };
+use crate::engine::maths::Vec2f;
 
 pub struct Renderer {
     pub camera_uniform: CameraUniform,
+    /// Scene light, uploaded by `update_light`
+    pub light: Light,
+    pub light_uniform: LightUniform,
 
     pub model: ModelRenderer,
+    pub post: PostProcessor,
+    /// Sprites drawn over the post processed scene, flushed by `end_frame`
+    pub sprites: SpriteBatch,
+    /// Game text drawn over the sprites, available once a font is set with `set_font`
+    pub text: Option<TextRenderer>,
+    memory: Arc<MemoryTracker>,
+    /// Sample count applied at the start of the next frame
+    requested_msaa: Option<u32>,
 
     #[cfg(debug_assertions)]
-    pub editor: egui_wgpu::Renderer,
+    pub inspector: UniformInspector,
 }
 
 impl Renderer {
     pub fn new(ctx: &Graphics) -> Self {
         let camera_uniform = CameraUniform::new(ctx);
+        let light = Light::default();
+        let light_uniform = LightUniform::new(ctx, &light);
 
         #[cfg(debug_assertions)]
-        let editor = egui_wgpu::Renderer::new(
-            &ctx.device,
-            ctx.surface_format,
-            None, // Some(TextureWrapper::DEPTH_FORMAT)
-            1,
-            false,
-        );
+        let inspector = {
+            let mut inspector = UniformInspector::default();
+            inspector.register(
+                "Camera",
+                &camera_uniform.uniform_buffer,
+                vec![
+                    ("view", UniformField::Mat4),
+                    ("proj", UniformField::Mat4),
+                    ("depth", UniformField::Vec4),
+                ],
+            );
+            inspector.register(
+                "Light",
+                &light_uniform.uniform_buffer,
+                vec![
+                    ("direction", UniformField::Vec4),
+                    ("color", UniformField::Vec4),
+                ],
+            );
+            inspector
+        };
 
         let model = ModelRenderer::new(ctx, &camera_uniform);
+        let post = PostProcessor::new(ctx);
+        let sprites = SpriteBatch::new(ctx);
 
         Self {
             #[cfg(debug_assertions)]
-            editor,
+            inspector,
 
             model,
+            post,
+            sprites,
+            text: None,
+            memory: ctx.memory.clone(),
+            requested_msaa: None,
 
             camera_uniform,
+            light,
+            light_uniform,
         }
     }
 
     pub fn on_resize(&mut self, ctx: &Graphics) {
         self.model.on_resize(ctx);
+        self.post.on_resize(ctx, self.model.depth_view());
+    }
+
+    /// Call before the game renders the scene
+    pub fn begin_frame(&mut self, ctx: &mut Graphics, frame: &mut Frame) {
+        if let Some(requested) = self.requested_msaa.take() {
+            let count = ctx.capabilities.clamp_sample_count(requested);
+            if count != ctx.sample_count {
+                ctx.sample_count = count;
+                self.model
+                    .on_sample_count_changed(ctx, &self.camera_uniform);
+                // The depth texture was recreated with the new count, effects reading it rebind it
+                self.post.on_resize(ctx, self.model.depth_view());
+            }
+        }
+        self.post.begin_frame(frame);
+    }
+
+    /// Multisample the scene with `count` samples per pixel from the next frame, 1 disables it.
+    /// Falls back to the highest supported count below, see `Graphics::sample_count`.
+    pub fn set_msaa(&mut self, count: u32) {
+        self.requested_msaa = Some(count);
+    }
+
+    /// Call after the game rendered the scene, before the editor
+    pub fn end_frame(&mut self, ctx: &Graphics, frame: &mut Frame) {
+        self.post.end_frame(ctx, frame, &self.camera_uniform);
+        self.sprites.render(ctx, frame);
+        if let Some(text) = &mut self.text {
+            text.render(ctx, frame, self.camera_uniform.view_proj());
+        }
+    }
+
+    /// Toggle logarithmic depth for every material using `CAMERA_WGSL`, off by default
+    pub fn set_log_depth(&mut self, enabled: bool) {
+        self.camera_uniform.log_depth = enabled.then_some(1.0);
+    }
+
+    /// Model under the cursor, see `ModelRenderer::pick`
+    pub fn pick(&mut self, ctx: &Graphics, cursor: Vec2f) -> Option<ModelId> {
+        self.model.pick(ctx, &self.camera_uniform, cursor)
     }
 
     pub fn update_camera(&mut self, ctx: &Graphics, camera: &Camera) {
         self.camera_uniform.update(ctx, camera);
     }
+
+    /// Draw a cubemap from `ModelTexture::cubemap_from_bytes` behind the scene, None removes it
+    pub fn set_skybox(&mut self, ctx: &Graphics, cubemap: Option<ModelTexture>) {
+        let skybox = cubemap.map(|cubemap| Skybox::new(ctx, &self.camera_uniform, cubemap));
+        self.model.set_skybox(skybox);
+    }
+
+    /// Font of `text`, None removes the text renderer
+    pub fn set_font(&mut self, ctx: &Graphics, font: Option<BitmapFont>) {
+        self.text = font.map(|font| TextRenderer::new(ctx, font));
+    }
+
+    /// Show a reference grid on the XZ plane, with a line every world unit
+    pub fn set_grid_visible(&mut self, ctx: &Graphics, visible: bool) {
+        if visible == self.grid_visible() {
+            return;
+        }
+        let grid = visible.then(|| {
+            Grid::new(
+                ctx,
+                &self.camera_uniform,
+                Grid::DEFAULT_HALF_EXTENT,
+                Grid::DEFAULT_CELL_SIZE,
+                Color3f::splat(0.5).with_alpha(0.5),
+            )
+        });
+        self.model.set_grid(grid);
+    }
+
+    pub fn grid_visible(&self) -> bool {
+        self.model.grid().is_some()
+    }
+
+    /// Estimated GPU memory of the models, textures and uniforms currently alive
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory.usage()
+    }
+
+    pub fn update_light(&mut self, ctx: &Graphics) {
+        self.light_uniform.update(ctx, &self.light);
+    }
 }
 
 impl Debug for Renderer {
@@ -54,3 +186,69 @@ impl Debug for Renderer {
         f.debug_struct("Renderer").finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn msaa_resources_follow_the_requested_sample_count() {
+        let Some(mut ctx) = Graphics::headless(64, 64) else {
+            return;
+        };
+        let mut renderer = Renderer::new(&ctx);
+        let expected = if ctx.capabilities.sample_counts.contains(&4) {
+            4
+        } else {
+            ctx.capabilities.clamp_sample_count(4)
+        };
+
+        renderer.set_msaa(4);
+        for _ in 0..2 {
+            let mut frame = ctx.next_frame().unwrap();
+            renderer.begin_frame(&mut ctx, &mut frame);
+            assert_eq!(ctx.sample_count, expected);
+            assert_eq!(renderer.model.depth_sample_count(), expected);
+            renderer
+                .model
+                .render(&ctx, &mut frame, &renderer.camera_uniform);
+            renderer.end_frame(&ctx, &mut frame);
+            ctx.present(frame);
+        }
+
+        // Disabling it goes back to single sampled attachments
+        renderer.set_msaa(1);
+        let mut frame = ctx.next_frame().unwrap();
+        renderer.begin_frame(&mut ctx, &mut frame);
+        assert_eq!(ctx.sample_count, 1);
+        assert_eq!(renderer.model.depth_sample_count(), 1);
+        renderer
+            .model
+            .render(&ctx, &mut frame, &renderer.camera_uniform);
+        renderer.end_frame(&ctx, &mut frame);
+        ctx.present(frame);
+        ctx.device.poll(wgpu::PollType::Wait).unwrap();
+    }
+
+    #[test]
+    fn empty_scene_renders_the_clear_color() {
+        let Some(mut ctx) = Graphics::headless(16, 16) else {
+            return;
+        };
+        let mut renderer = Renderer::new(&ctx);
+        ctx.clear_color = Color3f::new(0.2, 0.4, 0.8);
+
+        let mut frame = ctx.next_frame().unwrap();
+        renderer.begin_frame(&mut ctx, &mut frame);
+        renderer
+            .model
+            .render(&ctx, &mut frame, &renderer.camera_uniform);
+        renderer.end_frame(&ctx, &mut frame);
+        let texture = frame.texture.clone();
+        ctx.present(frame);
+
+        let expected = ctx.clear_color.to_srgba_unorm();
+        let pixels = ctx.read_texture(&texture);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == expected));
+    }
+}
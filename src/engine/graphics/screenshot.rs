@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use wgpu::TextureFormat;
+
+use crate::engine::graphics::{Frame, Graphics};
+
+/// Copy of the surface recorded in a frame, readable once the frame is presented
+pub struct Screenshot {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    bgra: bool,
+}
+
+impl Screenshot {
+    /// Record a copy of the surface as rendered so far this frame, the surface must support
+    /// `COPY_SRC` and use an 8 bit RGBA or BGRA format
+    pub fn capture(ctx: &Graphics, frame: &mut Frame) -> Option<Self> {
+        let texture = &frame.texture;
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            crate::log!("Screenshots are not supported by this surface");
+            return None;
+        }
+        let bgra = match texture.format() {
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+            format => {
+                crate::log!("Screenshots are not supported for the {format:?} surface format");
+                return None;
+            }
+        };
+
+        let (width, height) = (texture.width(), texture.height());
+        let padded_bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        frame.encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+
+        Some(Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            bgra,
+        })
+    }
+
+    /// Write the capture to a PNG, blocking until the frame it was recorded in is done
+    pub fn save(self, ctx: &Graphics, path: &Path) -> Result<(), image::ImageError> {
+        let slice = self.buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| ());
+        ctx.device
+            .poll(wgpu::PollType::Wait)
+            .unwrap_or_else(|e| panic!("Failed to read back screenshot: {e}"));
+
+        let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(self.padded_bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..(self.width * 4) as usize]);
+        }
+        self.buffer.unmap();
+
+        if self.bgra {
+            pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+        }
+        // The surface alpha is not meaningful once presented
+        pixels.chunks_exact_mut(4).for_each(|p| p[3] = 255);
+
+        image::save_buffer(
+            path,
+            &pixels,
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )
+    }
+}
@@ -0,0 +1,189 @@
+use wgpu::RenderPass;
+
+use crate::engine::graphics::{
+    Graphics,
+    camera::{CAMERA_WGSL, CameraUniform},
+    model::{
+        Model, Vertex,
+        texture::{ModelTexture, TextureUniform},
+    },
+};
+
+const SKYBOX_WGSL: &str = r#"
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var t_sky: texture_cube<f32>;
+@group(1) @binding(1)
+var s_sky: sampler;
+
+struct SkyboxOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+};
+
+@vertex
+fn vs_skybox(@location(0) position: vec3<f32>) -> SkyboxOutput {
+    // Rotation only, the sky stays centered on the camera
+    let view = mat4x4<f32>(
+        vec4<f32>(camera.view[0].xyz, 0.0),
+        vec4<f32>(camera.view[1].xyz, 0.0),
+        vec4<f32>(camera.view[2].xyz, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    let clip = camera.proj * view * vec4<f32>(position, 1.0);
+    var out: SkyboxOutput;
+    // On the far plane, behind everything drawn afterwards
+    out.clip_position = clip.xyww;
+    out.direction = position;
+    return out;
+}
+
+@fragment
+fn fs_skybox(in: SkyboxOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_sky, s_sky, in.direction);
+}
+"#;
+
+/// Cubemap drawn around the camera before the scene, see `Renderer::set_skybox`
+pub struct Skybox {
+    pipeline: wgpu::RenderPipeline,
+    texture_uniform: TextureUniform,
+    cube: Model,
+    _cubemap: ModelTexture,
+}
+
+impl Skybox {
+    /// `cubemap` must come from `ModelTexture::cubemap_from_bytes`
+    pub fn new(ctx: &Graphics, camera_uniform: &CameraUniform, cubemap: ModelTexture) -> Self {
+        let texture_uniform = TextureUniform::cubemap(ctx, &cubemap);
+        let pipeline = create_pipeline(ctx, camera_uniform, &texture_uniform);
+
+        Self {
+            pipeline,
+            texture_uniform,
+            cube: Model::cube(ctx, true),
+            _cubemap: cubemap,
+        }
+    }
+
+    pub fn on_sample_count_changed(&mut self, ctx: &Graphics, camera_uniform: &CameraUniform) {
+        self.pipeline = create_pipeline(ctx, camera_uniform, &self.texture_uniform);
+    }
+
+    pub fn render(&self, render_pass: &mut RenderPass, camera_uniform: &CameraUniform) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &camera_uniform.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.texture_uniform.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.cube.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.cube.index_buffer.slice(..), self.cube.index_format());
+        render_pass.draw_indexed(0..self.cube.indices_count(), 0, 0..1);
+    }
+}
+
+fn create_pipeline(
+    ctx: &Graphics,
+    camera_uniform: &CameraUniform,
+    texture_uniform: &TextureUniform,
+) -> wgpu::RenderPipeline {
+    let module = ctx
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("skybox"),
+            source: wgpu::ShaderSource::Wgsl(format!("{CAMERA_WGSL}{SKYBOX_WGSL}").into()),
+        });
+    let layout = ctx
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("skybox"),
+            bind_group_layouts: &[
+                &camera_uniform.bind_group_layout,
+                &texture_uniform.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("skybox"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_skybox"),
+                buffers: &[Vertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_skybox"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ctx.surface_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            // Drawn first without writing depth, the scene covers it
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: ctx.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::graphics::model::renderer::ModelRenderer;
+
+    fn face_png(color: [u8; 4]) -> Vec<u8> {
+        let mut png = std::io::Cursor::new(vec![]);
+        image::RgbaImage::from_pixel(4, 4, image::Rgba(color))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+
+    #[test]
+    fn skybox_pipeline_builds_and_renders() {
+        let Some(mut ctx) = Graphics::headless(64, 64) else {
+            return;
+        };
+        let camera_uniform = CameraUniform::new(&ctx);
+        let faces = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+            .into_iter()
+            .cycle()
+            .take(6)
+            .map(face_png)
+            .collect::<Vec<_>>();
+        let faces = std::array::from_fn(|i| faces[i].as_slice());
+        let cubemap = ModelTexture::cubemap_from_bytes(&ctx, faces, "Sky").unwrap();
+
+        let mut renderer = ModelRenderer::new(&ctx, &camera_uniform);
+        renderer.set_skybox(Some(Skybox::new(&ctx, &camera_uniform, cubemap)));
+        let mut frame = ctx.next_frame().unwrap();
+        renderer.render(&ctx, &mut frame, &camera_uniform);
+        ctx.present(frame);
+
+        // The pipeline is rebuilt for the new sample count
+        ctx.sample_count = ctx.capabilities.clamp_sample_count(4);
+        renderer.on_sample_count_changed(&ctx, &camera_uniform);
+        let mut frame = ctx.next_frame().unwrap();
+        renderer.render(&ctx, &mut frame, &camera_uniform);
+        ctx.present(frame);
+        ctx.device.poll(wgpu::PollType::Wait).unwrap();
+    }
+}
@@ -0,0 +1,468 @@
+use std::ops::Range;
+
+use slotmap::SlotMap;
+use wgpu::util::DeviceExt;
+
+use crate::engine::{
+    graphics::{
+        Frame, Graphics,
+        color::{Color3f, Color4f},
+        memory::TrackedMemory,
+        model::{
+            renderer::BlendMode,
+            texture::{ModelTexture, TextureUniform},
+        },
+    },
+    maths::{Mat4f, Vec2f},
+};
+
+const SPRITE_WGSL: &str = r#"
+@group(0) @binding(0)
+var<uniform> projection: mat4x4<f32>;
+
+@group(1) @binding(0)
+var t_sprite: texture_2d<f32>;
+@group(1) @binding(1)
+var s_sprite: sampler;
+
+struct SpriteOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_sprite(
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> SpriteOutput {
+    var out: SpriteOutput;
+    out.clip_position = projection * vec4<f32>(position, 0.0, 1.0);
+    out.uv = uv;
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_sprite(in: SpriteOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_sprite, s_sprite, in.uv) * in.color;
+}
+"#;
+
+slotmap::new_key_type! { pub struct SpriteTextureId; }
+
+/// Coordinate system of the sprite positions and sizes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpriteView {
+    /// Pixels from the top left of the window, y down, for HUDs
+    Screen,
+    /// World units with y up, `height` units visible vertically around `center`
+    World { center: Vec2f, height: f32 },
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+impl SpriteVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sprite {
+    texture: SpriteTextureId,
+    position: Vec2f,
+    size: Vec2f,
+    uv_min: Vec2f,
+    uv_max: Vec2f,
+    color: Color4f,
+}
+
+/// Textured quads drawn over the frame with alpha blending, without depth, see `Renderer::sprites`.
+///
+/// Sprites are queued with `draw` during the frame and drawn in order by `render`, then cleared.
+/// Consecutive sprites sharing a texture are merged into a single draw call, so group them by
+/// texture when the layering allows it.
+pub struct SpriteBatch {
+    pub view: SpriteView,
+    sprites: Vec<Sprite>,
+    textures: SlotMap<SpriteTextureId, wgpu::BindGroup>,
+    white: SpriteTextureId,
+    _white_texture: ModelTexture,
+    texture_layout: wgpu::BindGroupLayout,
+    projection_buffer: wgpu::Buffer,
+    projection_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    buffers: Option<SpriteBuffers>,
+    draw_calls: u32,
+}
+
+struct SpriteBuffers {
+    vertices: wgpu::Buffer,
+    indices: wgpu::Buffer,
+    /// Sprites that fit in the buffers
+    capacity: usize,
+    _memory: [TrackedMemory; 2],
+}
+
+impl SpriteBatch {
+    pub fn new(ctx: &Graphics) -> Self {
+        let white_texture = ModelTexture::from_color(ctx, Color3f::WHITE, "sprite_white");
+        let white_uniform = TextureUniform::new(ctx, &white_texture);
+        let mut textures = SlotMap::default();
+        let white = textures.insert(white_uniform.bind_group.clone());
+
+        let projection_buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Sprite Projection Buffer"),
+                contents: bytemuck::cast_slice(&[[[0.0f32; 4]; 4]]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let projection_layout =
+            ctx.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("sprite_projection_bind_group_layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+        let projection_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sprite_projection_bind_group"),
+            layout: &projection_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: projection_buffer.as_entire_binding(),
+            }],
+        });
+
+        let module = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("sprites"),
+                source: wgpu::ShaderSource::Wgsl(SPRITE_WGSL.into()),
+            });
+        let layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("sprites"),
+                bind_group_layouts: &[&projection_layout, &white_uniform.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("sprites"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some("vs_sprite"),
+                    buffers: &[SpriteVertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some("fs_sprite"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: Some(BlendMode::Alpha.blend_state()),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // Drawn in their own pass straight into the frame, after the scene is resolved
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            view: SpriteView::Screen,
+            sprites: vec![],
+            textures,
+            white,
+            _white_texture: white_texture,
+            texture_layout: white_uniform.bind_group_layout,
+            projection_buffer,
+            projection_bind_group,
+            pipeline,
+            buffers: None,
+            draw_calls: 0,
+        }
+    }
+
+    /// Make the texture drawable as sprites, sampled with its own sampler
+    pub fn add_texture(&mut self, ctx: &Graphics, texture: &ModelTexture) -> SpriteTextureId {
+        self.textures.insert(TextureUniform::create_bind_group(
+            ctx,
+            &self.texture_layout,
+            texture,
+        ))
+    }
+
+    pub fn remove_texture(&mut self, texture: SpriteTextureId) -> bool {
+        texture != self.white && self.textures.remove(texture).is_some()
+    }
+
+    /// Plain white texture, tinted by the sprite color to draw rectangles
+    pub fn white(&self) -> SpriteTextureId {
+        self.white
+    }
+
+    /// Queue the whole texture over the rectangle starting at `position`, its top left corner on
+    /// screen or its bottom left corner in the world
+    pub fn draw(
+        &mut self,
+        texture: SpriteTextureId,
+        position: Vec2f,
+        size: Vec2f,
+        color: impl Into<Color4f>,
+    ) {
+        self.draw_region(
+            texture,
+            position,
+            size,
+            Vec2f::zeros(),
+            Vec2f::new(1.0, 1.0),
+            color,
+        );
+    }
+
+    /// Queue part of the texture, from `uv_min` at its top left to `uv_max`, e.g. a cell of an atlas
+    pub fn draw_region(
+        &mut self,
+        texture: SpriteTextureId,
+        position: Vec2f,
+        size: Vec2f,
+        uv_min: Vec2f,
+        uv_max: Vec2f,
+        color: impl Into<Color4f>,
+    ) {
+        self.sprites.push(Sprite {
+            texture,
+            position,
+            size,
+            uv_min,
+            uv_max,
+            color: color.into(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// Draw calls issued by the last `render`
+    pub fn draw_calls(&self) -> u32 {
+        self.draw_calls
+    }
+
+    /// Draw the queued sprites over `frame.view` and clear them
+    pub fn render(&mut self, ctx: &Graphics, frame: &mut Frame) {
+        self.draw_calls = 0;
+        if self.sprites.is_empty() {
+            return;
+        }
+
+        ctx.queue.write_buffer(
+            &self.projection_buffer,
+            0,
+            bytemuck::cast_slice(&[self.projection(ctx)]),
+        );
+        let flip = matches!(self.view, SpriteView::World { .. });
+        let vertices: Vec<SpriteVertex> = self
+            .sprites
+            .iter()
+            .flat_map(|s| {
+                let (min, max) = (s.position, s.position + s.size);
+                // The top of the image is at the smallest y on screen and the largest in the world
+                let (top, bottom) = if flip { (max.y, min.y) } else { (min.y, max.y) };
+                let color = s.color.into();
+                [
+                    ([min.x, top], [s.uv_min.x, s.uv_min.y]),
+                    ([max.x, top], [s.uv_max.x, s.uv_min.y]),
+                    ([max.x, bottom], [s.uv_max.x, s.uv_max.y]),
+                    ([min.x, bottom], [s.uv_min.x, s.uv_max.y]),
+                ]
+                .map(|(position, uv)| SpriteVertex {
+                    position,
+                    uv,
+                    color,
+                })
+            })
+            .collect();
+        let indices: Vec<u32> = (0..self.sprites.len() as u32)
+            .flat_map(|i| [0, 1, 2, 0, 2, 3].map(|v| i * 4 + v))
+            .collect();
+
+        if self
+            .buffers
+            .as_ref()
+            .is_none_or(|b| b.capacity < self.sprites.len())
+        {
+            self.buffers = Some(SpriteBuffers::new(
+                ctx,
+                self.sprites.len().next_power_of_two(),
+            ));
+        }
+        let Some(buffers) = &self.buffers else {
+            return;
+        };
+        frame.write_buffer(ctx, &buffers.vertices, 0, bytemuck::cast_slice(&vertices));
+        frame.write_buffer(ctx, &buffers.indices, 0, bytemuck::cast_slice(&indices));
+
+        let mut render_pass = frame
+            .encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Sprite renderpass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.projection_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffers.vertices.slice(..));
+        render_pass.set_index_buffer(buffers.indices.slice(..), wgpu::IndexFormat::Uint32);
+
+        for (texture, indices) in draw_runs(&self.sprites) {
+            // Sprites of removed textures are skipped
+            if let Some(bind_group) = self.textures.get(texture) {
+                render_pass.set_bind_group(1, bind_group, &[]);
+                render_pass.draw_indexed(indices, 0, 0..1);
+                self.draw_calls += 1;
+            }
+        }
+        drop(render_pass);
+        self.sprites.clear();
+    }
+
+    fn projection(&self, ctx: &Graphics) -> [[f32; 4]; 4] {
+        let (width, height) = (
+            ctx.viewport_size.x.max(1) as f32,
+            ctx.viewport_size.y.max(1) as f32,
+        );
+        let matrix = match self.view {
+            SpriteView::Screen => Mat4f::new_orthographic(0.0, width, height, 0.0, -1.0, 1.0),
+            SpriteView::World { center, height: h } => {
+                let (half_w, half_h) = (h * width / height / 2.0, h / 2.0);
+                Mat4f::new_orthographic(
+                    center.x - half_w,
+                    center.x + half_w,
+                    center.y - half_h,
+                    center.y + half_h,
+                    -1.0,
+                    1.0,
+                )
+            }
+        };
+        matrix.into()
+    }
+}
+
+/// Index ranges of the draw calls, one per run of consecutive sprites sharing a texture
+fn draw_runs(sprites: &[Sprite]) -> Vec<(SpriteTextureId, Range<u32>)> {
+    let mut start = 0;
+    sprites
+        .chunk_by(|a, b| a.texture == b.texture)
+        .map(|run| {
+            let end = start + run.len() as u32 * 6;
+            let indices = start..end;
+            start = end;
+            (run[0].texture, indices)
+        })
+        .collect()
+}
+
+impl SpriteBuffers {
+    fn new(ctx: &Graphics, capacity: usize) -> Self {
+        let buffer = |label, size, usage| {
+            ctx.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as u64,
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let vertices = buffer(
+            "Sprite Vertex Buffer",
+            capacity * 4 * size_of::<SpriteVertex>(),
+            wgpu::BufferUsages::VERTEX,
+        );
+        let indices = buffer(
+            "Sprite Index Buffer",
+            capacity * 6 * size_of::<u32>(),
+            wgpu::BufferUsages::INDEX,
+        );
+        Self {
+            _memory: [
+                ctx.memory.track_buffer(&vertices),
+                ctx.memory.track_buffer(&indices),
+            ],
+            vertices,
+            indices,
+            capacity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite(texture: SpriteTextureId) -> Sprite {
+        Sprite {
+            texture,
+            position: Vec2f::zeros(),
+            size: Vec2f::new(1.0, 1.0),
+            uv_min: Vec2f::zeros(),
+            uv_max: Vec2f::new(1.0, 1.0),
+            color: Color4f::new(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+
+    #[test]
+    fn sprites_sharing_a_texture_are_one_draw_call() {
+        let mut textures = SlotMap::<SpriteTextureId, ()>::with_key();
+        let (a, b) = (textures.insert(()), textures.insert(()));
+
+        assert_eq!(draw_runs(&[sprite(a), sprite(a)]), vec![(a, 0..12)]);
+        assert_eq!(
+            draw_runs(&[sprite(a), sprite(b), sprite(a)]),
+            vec![(a, 0..6), (b, 6..12), (a, 12..18)]
+        );
+        assert!(draw_runs(&[]).is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use crate::engine::{
+    graphics::{
+        Frame, Graphics,
+        color::Color4f,
+        model::texture::ModelTexture,
+        sprite::{SpriteBatch, SpriteTextureId, SpriteView},
+    },
+    maths::{Mat4f, Vec2f, Vec3f},
+};
+
+#[derive(Debug)]
+pub enum FontError {
+    Image(image::ImageError),
+    /// A line of the font description could not be read, numbered from 1
+    Malformed {
+        line: usize,
+    },
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Image(e) => write!(f, "failed to decode font atlas: {e}"),
+            Self::Malformed { line } => write!(f, "malformed font description at line {line}"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+impl From<image::ImageError> for FontError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+/// Placement of a character in the atlas, in pixels of the font
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    pub uv_min: Vec2f,
+    pub uv_max: Vec2f,
+    pub size: Vec2f,
+    /// From the pen position to the top left of the glyph
+    pub offset: Vec2f,
+    /// Horizontal move of the pen after the glyph
+    pub advance: f32,
+}
+
+/// Glyph metrics and kerning of a font, without its atlas texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontMetrics {
+    /// Size the font was rasterized at, in pixels
+    pub size: f32,
+    /// Distance between two baselines, in pixels of the font
+    pub line_height: f32,
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), f32>,
+}
+
+impl FontMetrics {
+    /// Read a font description in the AngelCode BMFont text format, `atlas_size` in pixels maps
+    /// the glyph rectangles to texture coordinates
+    pub fn from_bmfont(description: &str, atlas_size: Vec2f) -> Result<Self, FontError> {
+        let mut metrics = Self {
+            size: 0.0,
+            line_height: 0.0,
+            glyphs: HashMap::new(),
+            kerning: HashMap::new(),
+        };
+        for (index, line) in description.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let Some(tag) = words.next() else {
+                continue;
+            };
+            let fields: HashMap<&str, &str> = words.filter_map(|w| w.split_once('=')).collect();
+            let number = |key: &str| -> Result<f32, FontError> {
+                fields
+                    .get(key)
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| malformed(index))
+            };
+            let character = |key: &str| {
+                number(key).and_then(|id| char::from_u32(id as u32).ok_or_else(|| malformed(index)))
+            };
+            match tag {
+                "info" => metrics.size = number("size")?.abs(),
+                "common" => metrics.line_height = number("lineHeight")?,
+                "char" => {
+                    let position = Vec2f::new(number("x")?, number("y")?);
+                    let size = Vec2f::new(number("width")?, number("height")?);
+                    let glyph = Glyph {
+                        uv_min: position.component_div(&atlas_size),
+                        uv_max: (position + size).component_div(&atlas_size),
+                        size,
+                        offset: Vec2f::new(number("xoffset")?, number("yoffset")?),
+                        advance: number("xadvance")?,
+                    };
+                    metrics.glyphs.insert(character("id")?, glyph);
+                }
+                "kerning" => {
+                    let pair = (character("first")?, character("second")?);
+                    metrics.kerning.insert(pair, number("amount")?);
+                }
+                _ => {}
+            }
+        }
+        if metrics.size == 0.0 {
+            metrics.size = metrics.line_height;
+        }
+        Ok(metrics)
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    /// Extra horizontal move between two consecutive characters, in pixels of the font
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.kerning.get(&(first, second)).copied().unwrap_or(0.0)
+    }
+
+    /// Width of the widest line and total height of `text` drawn at `size` pixels
+    pub fn measure(&self, text: &str, size: f32) -> Vec2f {
+        let scale = self.scale(size);
+        let width = text
+            .lines()
+            .map(|line| self.advance_width(line))
+            .fold(0.0, f32::max);
+        let lines = text.split('\n').count();
+        Vec2f::new(width, lines as f32 * self.line_height) * scale
+    }
+
+    /// Pen travel over a single line, kerning included, in pixels of the font
+    pub fn advance_width(&self, line: &str) -> f32 {
+        let mut width = 0.0;
+        let mut previous = None;
+        for c in line.chars() {
+            if let Some(previous) = previous {
+                width += self.kerning(previous, c);
+            }
+            width += self.glyph(c).map_or(0.0, |g| g.advance);
+            previous = Some(c);
+        }
+        width
+    }
+
+    fn scale(&self, size: f32) -> f32 {
+        size / self.size.max(1.0)
+    }
+}
+
+/// A font rasterized into an atlas texture with its metrics
+pub struct BitmapFont {
+    pub texture: ModelTexture,
+    pub metrics: FontMetrics,
+}
+
+impl BitmapFont {
+    /// Load a font in the AngelCode BMFont text format with its single page atlas image,
+    /// as exported by BMFont, Hiero or msdf-bmfont
+    pub fn from_bmfont(ctx: &Graphics, description: &str, atlas: &[u8]) -> Result<Self, FontError> {
+        let texture = ModelTexture::from_bytes(ctx, atlas, "font_atlas")?;
+        let atlas_size = Vec2f::new(
+            texture.texture.width() as f32,
+            texture.texture.height() as f32,
+        );
+        let metrics = FontMetrics::from_bmfont(description, atlas_size)?;
+        Ok(Self { texture, metrics })
+    }
+}
+
+fn malformed(index: usize) -> FontError {
+    FontError::Malformed { line: index + 1 }
+}
+
+/// Where the top left corner of the first line of a text is placed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAnchor {
+    /// Pixels from the top left of the window
+    Screen(Vec2f),
+    /// A point of the scene projected on screen, e.g. a label above an entity
+    World(Vec3f),
+}
+
+impl From<Vec2f> for TextAnchor {
+    fn from(position: Vec2f) -> Self {
+        Self::Screen(position)
+    }
+}
+
+impl From<Vec3f> for TextAnchor {
+    fn from(position: Vec3f) -> Self {
+        Self::World(position)
+    }
+}
+
+struct QueuedText {
+    text: String,
+    anchor: TextAnchor,
+    size: f32,
+    color: Color4f,
+}
+
+/// Game text drawn with a `BitmapFont` over the frame, see `Renderer::set_font`.
+///
+/// Texts are queued with `draw` during the frame and drawn by `render` in a single sprite batch,
+/// then cleared. Text stays the same size on screen whatever its anchor.
+pub struct TextRenderer {
+    pub font: BitmapFont,
+    batch: SpriteBatch,
+    atlas: SpriteTextureId,
+    texts: Vec<QueuedText>,
+}
+
+impl TextRenderer {
+    pub fn new(ctx: &Graphics, font: BitmapFont) -> Self {
+        let mut batch = SpriteBatch::new(ctx);
+        batch.view = SpriteView::Screen;
+        let atlas = batch.add_texture(ctx, &font.texture);
+        Self {
+            font,
+            batch,
+            atlas,
+            texts: vec![],
+        }
+    }
+
+    /// Queue `text` with the font scaled to `size` pixels, `\n` starts a new line
+    pub fn draw(
+        &mut self,
+        text: &str,
+        anchor: impl Into<TextAnchor>,
+        size: f32,
+        color: impl Into<Color4f>,
+    ) {
+        self.texts.push(QueuedText {
+            text: text.to_owned(),
+            anchor: anchor.into(),
+            size,
+            color: color.into(),
+        });
+    }
+
+    /// Draw the queued texts over `frame.view` and clear them, world anchors are projected
+    /// with `view_proj`
+    pub fn render(&mut self, ctx: &Graphics, frame: &mut Frame, view_proj: &Mat4f) {
+        let viewport = Vec2f::new(ctx.viewport_size.x as f32, ctx.viewport_size.y as f32);
+        for text in self.texts.drain(..) {
+            let origin = match text.anchor {
+                TextAnchor::Screen(position) => position,
+                TextAnchor::World(position) => {
+                    let clip = view_proj * position.push(1.0);
+                    // Behind the camera
+                    if clip.w <= 0.0 {
+                        continue;
+                    }
+                    let ndc = clip.xy() / clip.w;
+                    Vec2f::new(ndc.x + 1.0, 1.0 - ndc.y).component_mul(&viewport) / 2.0
+                }
+            };
+
+            let scale = self.font.metrics.scale(text.size);
+            let mut pen = origin;
+            let mut previous = None;
+            for c in text.text.chars() {
+                if c == '\n' {
+                    pen = Vec2f::new(origin.x, pen.y + self.font.metrics.line_height * scale);
+                    previous = None;
+                    continue;
+                }
+                if let Some(previous) = previous {
+                    pen.x += self.font.metrics.kerning(previous, c) * scale;
+                }
+                previous = Some(c);
+                let Some(glyph) = self.font.metrics.glyph(c) else {
+                    continue;
+                };
+                if glyph.size.x > 0.0 && glyph.size.y > 0.0 {
+                    self.batch.draw_region(
+                        self.atlas,
+                        pen + glyph.offset * scale,
+                        glyph.size * scale,
+                        glyph.uv_min,
+                        glyph.uv_max,
+                        text.color,
+                    );
+                }
+                pen.x += glyph.advance * scale;
+            }
+        }
+        self.batch.render(ctx, frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT: &str = "\
+info face=\"Test\" size=-16
+common lineHeight=20 base=16 scaleW=64 scaleH=64 pages=1
+char id=65 x=0 y=0 width=10 height=12 xoffset=0 yoffset=4 xadvance=10 page=0
+char id=86 x=10 y=0 width=10 height=12 xoffset=0 yoffset=4 xadvance=9 page=0
+char id=32 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4 page=0
+kernings count=1
+kerning first=65 second=86 amount=-2
+";
+
+    #[test]
+    fn advance_width_includes_kerning() {
+        let metrics = FontMetrics::from_bmfont(FONT, Vec2f::new(64.0, 64.0)).unwrap();
+        assert_eq!(metrics.size, 16.0);
+        assert_eq!(metrics.line_height, 20.0);
+        // A + (A, V) kerning + V + space + V + A, V then A has no kerning pair
+        assert_eq!(
+            metrics.advance_width("AV VA"),
+            10.0 - 2.0 + 9.0 + 4.0 + 9.0 + 10.0
+        );
+        assert_eq!(
+            metrics.measure("AV\nA", 32.0),
+            Vec2f::new((10.0 - 2.0 + 9.0) * 2.0, 40.0 * 2.0)
+        );
+        let glyph = metrics.glyph('V').unwrap();
+        assert_eq!(glyph.uv_min, Vec2f::new(10.0 / 64.0, 0.0));
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        let error =
+            FontMetrics::from_bmfont("common lineHeight=20\nchar id=65 x=0", Vec2f::zeros());
+        assert!(matches!(error, Err(FontError::Malformed { line: 2 })));
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use super::inputs::GamepadButton;
+
+pub const MOVE_FORWARD: &str = "move_forward";
+pub const MOVE_BACKWARD: &str = "move_backward";
+pub const MOVE_LEFT: &str = "move_left";
+pub const MOVE_RIGHT: &str = "move_right";
+pub const MOVE_UP: &str = "move_up";
+pub const MOVE_DOWN: &str = "move_down";
+pub const ROLL_LEFT: &str = "roll_left";
+pub const ROLL_RIGHT: &str = "roll_right";
+
+/// Physical input an action can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl From<KeyCode> for Binding {
+    fn from(key: KeyCode) -> Self {
+        Self::Key(key)
+    }
+}
+
+impl From<MouseButton> for Binding {
+    fn from(button: MouseButton) -> Self {
+        Self::Mouse(button)
+    }
+}
+
+impl From<GamepadButton> for Binding {
+    fn from(button: GamepadButton) -> Self {
+        Self::Gamepad(button)
+    }
+}
+
+/// Named actions and the bindings triggering them, queried with `Inputs::action_held` and co.
+///
+/// An action fires when any of its bindings does. Serializes as a map from action names to
+/// binding lists, so it can be stored in a settings file and edited by hand.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputMap {
+    bindings: BTreeMap<String, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    /// Bindings used by the camera controllers
+    fn default() -> Self {
+        let mut map = Self::empty();
+        map.bind(MOVE_FORWARD, KeyCode::KeyW)
+            .bind(MOVE_BACKWARD, KeyCode::KeyS)
+            .bind(MOVE_LEFT, KeyCode::KeyA)
+            .bind(MOVE_RIGHT, KeyCode::KeyD)
+            .bind(MOVE_UP, KeyCode::Space)
+            .bind(MOVE_UP, GamepadButton::South)
+            .bind(MOVE_DOWN, KeyCode::ShiftLeft)
+            .bind(MOVE_DOWN, GamepadButton::East)
+            .bind(ROLL_LEFT, KeyCode::KeyQ)
+            .bind(ROLL_LEFT, GamepadButton::LeftBumper)
+            .bind(ROLL_RIGHT, KeyCode::KeyE)
+            .bind(ROLL_RIGHT, GamepadButton::RightBumper);
+        map
+    }
+}
+
+impl InputMap {
+    /// Map without any action
+    pub fn empty() -> Self {
+        Self {
+            bindings: BTreeMap::new(),
+        }
+    }
+
+    /// Add a binding to the action, keeping the existing ones
+    pub fn bind(&mut self, action: &str, binding: impl Into<Binding>) -> &mut Self {
+        let binding = binding.into();
+        let bindings = self.bindings.entry(action.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self
+    }
+
+    /// Replace every binding of the action
+    pub fn rebind(
+        &mut self,
+        action: &str,
+        bindings: impl IntoIterator<Item = Binding>,
+    ) -> &mut Self {
+        self.bindings
+            .insert(action.to_owned(), bindings.into_iter().collect());
+        self
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: impl Into<Binding>) -> &mut Self {
+        let binding = binding.into();
+        if let Some(bindings) = self.bindings.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+        self
+    }
+
+    /// Bindings of the action, empty when the action is unknown
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(String::as_str)
+    }
+}
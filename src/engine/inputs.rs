@@ -3,13 +3,19 @@ use std::{
     time::{Duration, Instant},
 };
 
-use current::{CurrentInput, KeyAction, MouseAction, ScanCodeAction, mouse_button_to_int};
+use current::{
+    CurrentInput, KeyAction, MouseAction, ScanCodeAction, TouchPoint, mouse_button_to_int,
+};
+use serde::{Deserialize, Serialize};
 use winit::{
     dpi::PhysicalSize,
-    event::{DeviceEvent, MouseButton, WindowEvent},
-    keyboard::{Key, KeyCode, PhysicalKey},
+    event::{DeviceEvent, ElementState, MouseButton, WindowEvent},
+    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
 };
 
+use super::input_map::{Binding, InputMap};
+use super::maths::Vec2f;
+
 /// From `winit_input_helper` updated to 3.0
 ///
 /// Create with `WinitInputHelper::new`.
@@ -26,6 +32,8 @@ use winit::{
 #[derive(Clone, Debug)]
 pub struct Inputs {
     current: Option<CurrentInput>,
+    /// Window events are ignored while unfocused, injected inputs still apply
+    focused: bool,
     dropped_file: Option<PathBuf>,
     window_resized: Option<PhysicalSize<u32>>,
     window_size: Option<(u32, u32)>,
@@ -35,6 +43,22 @@ pub struct Inputs {
     close_requested: bool,
     step_start: Option<Instant>,
     step_duration: Option<Duration>,
+    dt_smoother: Option<DtSmoother>,
+    smoothed_step_duration: Option<Duration>,
+    gamepad: GamepadState,
+    gamepad_deadzone: f32,
+    input_map: InputMap,
+    /// Last position reported by `WindowEvent::CursorMoved`, kept when the window loses focus
+    cursor_position: Option<Vec2f>,
+    cursor_in_window: bool,
+    /// Time each held key has been held for, summed from the step durations
+    key_held_durations: Vec<(PhysicalKey, Duration)>,
+    /// Latest click sequence of each mouse button
+    clicks: Vec<Click>,
+    /// Longest delay between two presses of a multi click
+    pub double_click_interval: Duration,
+    /// Furthest the cursor can move between two presses of a multi click, in pixels
+    pub double_click_distance: f32,
 }
 
 impl Default for Inputs {
@@ -48,6 +72,7 @@ impl Inputs {
     pub fn new() -> Inputs {
         Inputs {
             current: Some(CurrentInput::new()),
+            focused: true,
             dropped_file: None,
             window_resized: None,
             window_size: None,
@@ -57,6 +82,17 @@ impl Inputs {
             close_requested: false,
             step_start: None,
             step_duration: None,
+            dt_smoother: None,
+            smoothed_step_duration: None,
+            gamepad: GamepadState::default(),
+            gamepad_deadzone: 0.15,
+            input_map: InputMap::default(),
+            cursor_position: None,
+            cursor_in_window: false,
+            key_held_durations: vec![],
+            clicks: vec![],
+            double_click_interval: Duration::from_millis(400),
+            double_click_distance: 4.0,
         }
     }
 
@@ -68,20 +104,34 @@ impl Inputs {
         // Set the start time on the first event to avoid the first step appearing too long
         self.step_start.get_or_insert(Instant::now());
         self.step_duration = None;
+        self.smoothed_step_duration = None;
         if let Some(current) = &mut self.current {
             current.step();
         }
+        self.gamepad.step();
+        // Durations of released keys are kept until here so they can be read on release
+        match &self.current {
+            Some(current) => self
+                .key_held_durations
+                .retain(|(key, _)| current.scancode_held.contains(key)),
+            None => self.key_held_durations.clear(),
+        }
+        for click in &mut self.clicks {
+            click.this_step = false;
+        }
     }
 
     pub fn process_window_event(&mut self, event: &WindowEvent) {
         match event {
             WindowEvent::CloseRequested => self.close_requested = true,
             WindowEvent::Destroyed => self.destroyed = true,
-            WindowEvent::Focused(false) => self.current = None,
+            WindowEvent::Focused(false) => {
+                self.current = None;
+                self.focused = false;
+            }
             WindowEvent::Focused(true) => {
-                if self.current.is_none() {
-                    self.current = Some(CurrentInput::new())
-                }
+                self.current.get_or_insert_with(CurrentInput::new);
+                self.focused = true;
             }
             WindowEvent::DroppedFile(path) => self.dropped_file = Some(path.clone()),
             WindowEvent::Resized(size) => {
@@ -92,22 +142,231 @@ impl Inputs {
                 self.scale_factor_changed = Some(*scale_factor);
                 self.scale_factor = Some(*scale_factor);
             }
+            WindowEvent::CursorEntered { .. } => self.cursor_in_window = true,
+            WindowEvent::CursorLeft { .. } => self.cursor_in_window = false,
+            WindowEvent::CursorMoved { position, .. } => {
+                self.move_cursor(Vec2f::new(position.x as f32, position.y as f32));
+            }
             _ => {}
         }
+        if !self.focused {
+            return;
+        }
         if let Some(current) = &mut self.current {
             current.handle_event(event);
         }
+        if let WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button,
+            ..
+        } = event
+            && self.current.is_some()
+        {
+            self.register_click(*button);
+        }
     }
 
     pub fn process_device_event(&mut self, event: &DeviceEvent) {
+        if !self.focused {
+            return;
+        }
         if let Some(ref mut current) = self.current {
             current.handle_device_event(event);
         }
     }
 
+    /// Simulate a physical key press or release, as if it came from the window. The logical key
+    /// is the one of a US layout without modifiers, see `us_logical_key`.
+    ///
+    /// Injected inputs are applied even when the window is not focused, which allows scripted replays and headless tests.
+    /// The window events stay ignored until it is focused again.
+    pub fn inject_key(&mut self, keycode: KeyCode, pressed: bool) {
+        let current = self.current.get_or_insert_with(CurrentInput::new);
+        let logical_key = us_logical_key(keycode);
+        if pressed {
+            current.press_key(PhysicalKey::Code(keycode), logical_key.as_ref());
+        } else {
+            current.release_key(PhysicalKey::Code(keycode), logical_key.as_ref());
+        }
+    }
+
+    /// Simulate a mouse button press or release.
+    pub fn inject_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        self.current
+            .get_or_insert_with(CurrentInput::new)
+            .mouse_button(button, pressed);
+        if pressed {
+            self.register_click(button);
+        }
+    }
+
+    /// Extend the click sequence of the button, or start a new one when the previous press was
+    /// too long ago or too far away
+    fn register_click(&mut self, button: MouseButton) {
+        let now = Instant::now();
+        let position = self.cursor();
+        let (interval, distance) = (self.double_click_interval, self.double_click_distance);
+        match self.clicks.iter_mut().find(|c| c.button == button) {
+            Some(click) => {
+                let close = match (click.position, position) {
+                    (Some(a), Some(b)) => (a.0 - b.0).hypot(a.1 - b.1) <= distance,
+                    _ => true,
+                };
+                click.count = if close && now.duration_since(click.time) <= interval {
+                    click.count + 1
+                } else {
+                    1
+                };
+                click.time = now;
+                click.position = position;
+                click.this_step = true;
+            }
+            None => self.clicks.push(Click {
+                button,
+                time: now,
+                position,
+                count: 1,
+                this_step: true,
+            }),
+        }
+    }
+
+    /// Simulate a raw mouse motion, as reported by `DeviceEvent::MouseMotion`.
+    pub fn inject_mouse_motion(&mut self, dx: f32, dy: f32) {
+        self.current
+            .get_or_insert_with(CurrentInput::new)
+            .mouse_motion(dx, dy);
+    }
+
+    /// Simulate a mouse wheel scroll, in lines.
+    pub fn inject_scroll(&mut self, dx: f32, dy: f32) {
+        self.current
+            .get_or_insert_with(CurrentInput::new)
+            .scroll(dx, dy);
+    }
+
+    /// Simulate the cursor moving to the given window coordinates, in pixels.
+    pub fn inject_cursor_position(&mut self, x: f32, y: f32) {
+        self.current
+            .get_or_insert_with(CurrentInput::new)
+            .cursor_point = Some((x, y));
+        self.move_cursor(Vec2f::new(x, y));
+    }
+
+    fn move_cursor(&mut self, position: Vec2f) {
+        self.cursor_position = Some(position);
+        // Some platforms skip `CursorEntered` when the window opens under the cursor. Moves
+        // outside of the window are still reported while a button is held, so check the bounds.
+        if let Some((w, h)) = self.window_size {
+            self.cursor_in_window =
+                (0.0..w as f32).contains(&position.x) && (0.0..h as f32).contains(&position.y);
+        } else {
+            self.cursor_in_window = true;
+        }
+    }
+
+    /// Apply an event polled from gilrs. Events of every connected gamepad are merged.
+    #[cfg(feature = "gamepad")]
+    pub fn process_gamepad_event(&mut self, event: &gilrs::EventType) {
+        use gilrs::{Axis, Button, EventType};
+
+        let button = |button: Button| {
+            Some(match button {
+                Button::South => GamepadButton::South,
+                Button::East => GamepadButton::East,
+                Button::North => GamepadButton::North,
+                Button::West => GamepadButton::West,
+                Button::LeftTrigger => GamepadButton::LeftBumper,
+                Button::RightTrigger => GamepadButton::RightBumper,
+                Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+                Button::RightTrigger2 => GamepadButton::RightTrigger,
+                Button::Select => GamepadButton::Select,
+                Button::Start => GamepadButton::Start,
+                Button::Mode => GamepadButton::Mode,
+                Button::LeftThumb => GamepadButton::LeftThumb,
+                Button::RightThumb => GamepadButton::RightThumb,
+                Button::DPadUp => GamepadButton::DPadUp,
+                Button::DPadDown => GamepadButton::DPadDown,
+                Button::DPadLeft => GamepadButton::DPadLeft,
+                Button::DPadRight => GamepadButton::DPadRight,
+                _ => return None,
+            })
+        };
+
+        match *event {
+            EventType::ButtonPressed(b, _) => {
+                if let Some(b) = button(b) {
+                    self.gamepad.button(b, true);
+                }
+            }
+            EventType::ButtonReleased(b, _) => {
+                if let Some(b) = button(b) {
+                    self.gamepad.button(b, false);
+                }
+            }
+            EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+                self.gamepad.axes[GamepadAxis::LeftTrigger as usize] = value;
+            }
+            EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+                self.gamepad.axes[GamepadAxis::RightTrigger as usize] = value;
+            }
+            EventType::AxisChanged(axis, value, _) => {
+                let axis = match axis {
+                    Axis::LeftStickX => GamepadAxis::LeftStickX,
+                    Axis::LeftStickY => GamepadAxis::LeftStickY,
+                    Axis::RightStickX => GamepadAxis::RightStickX,
+                    Axis::RightStickY => GamepadAxis::RightStickY,
+                    Axis::LeftZ => GamepadAxis::LeftTrigger,
+                    Axis::RightZ => GamepadAxis::RightTrigger,
+                    _ => return,
+                };
+                self.gamepad.axes[axis as usize] = value;
+            }
+            // Avoid a stick or button staying stuck when the gamepad is unplugged mid input
+            EventType::Disconnected => self.gamepad = GamepadState::default(),
+            _ => {}
+        }
+    }
+
+    /// Simulate a gamepad axis moving to the given raw value, before the deadzone is applied.
+    pub fn inject_gamepad_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.current.get_or_insert_with(CurrentInput::new);
+        self.gamepad.axes[axis as usize] = value;
+    }
+
+    /// Simulate a gamepad button press or release.
+    pub fn inject_gamepad_button(&mut self, button: GamepadButton, pressed: bool) {
+        self.current.get_or_insert_with(CurrentInput::new);
+        self.gamepad.button(button, pressed);
+    }
+
     pub fn end_step(&mut self) {
         self.step_duration = self.step_start.map(|start| start.elapsed());
+        self.smoothed_step_duration = match &mut self.dt_smoother {
+            Some(smoother) => self.step_duration.map(|dt| smoother.smooth(dt)),
+            None => self.step_duration,
+        };
         self.step_start = Some(Instant::now());
+
+        if let (Some(current), Some(dt)) = (&self.current, self.step_duration) {
+            // Keys tapped within the step were held during it too, and are read on this step only
+            let tapped = current
+                .scancode_tapped
+                .iter()
+                .filter(|key| !current.scancode_held.contains(key));
+            for key in current.scancode_held.iter().chain(tapped) {
+                match self.key_held_durations.iter_mut().find(|(k, _)| k == key) {
+                    Some((_, duration)) => *duration += dt,
+                    None => self.key_held_durations.push((*key, dt)),
+                }
+            }
+        }
+    }
+
+    /// Enable delta time smoothing with the given exponential moving average factor
+    /// (weight of the newest frame, in `(0, 1]`), or disable it with `None`.
+    pub fn set_dt_smoothing(&mut self, factor: Option<f32>) {
+        self.dt_smoother = factor.map(DtSmoother::new);
     }
 
     /// Returns true when the key with the specified keycode goes from "not pressed" to "pressed".
@@ -163,15 +422,44 @@ impl Inputs {
     /// Returns true when the key with the specified keycode remains "pressed".
     /// Otherwise returns false.
     ///
+    /// A key pressed and released within the same step counts as held for that step, so quick taps
+    /// are not missed at low framerates.
+    ///
     /// Uses physical keys in the US layout, so for example the `W` key will be in the same physical key on both US and french keyboards.
     pub fn key_held(&self, keycode: KeyCode) -> bool {
         let key = PhysicalKey::Code(keycode);
         if let Some(current) = &self.current {
-            return current.scancode_held.contains(&key);
+            return current.scancode_held.contains(&key) || current.scancode_tapped.contains(&key);
         }
         false
     }
 
+    /// Returns how long the key with the specified keycode has been held, summed from `delta_time`.
+    /// On the step the key is released, returns the total time it was held, which suits
+    /// charge-and-release mechanics, a key tapped within a step counts that whole step.
+    /// Otherwise returns `Duration::ZERO` when the key is not held.
+    ///
+    /// Uses physical keys in the US layout, so for example the `W` key will be in the same physical key on both US and french keyboards.
+    pub fn key_held_duration(&self, keycode: KeyCode) -> Duration {
+        let key = PhysicalKey::Code(keycode);
+        self.key_held_durations
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map_or(Duration::ZERO, |(_, duration)| *duration)
+    }
+
+    /// Returns true when the key with the specified keycode was pressed and released within the last step.
+    /// Otherwise returns false.
+    ///
+    /// `key_pressed` and `key_released` are also true for that step.
+    pub fn key_tapped(&self, keycode: KeyCode) -> bool {
+        let key = PhysicalKey::Code(keycode);
+        match &self.current {
+            Some(current) => current.scancode_tapped.contains(&key),
+            None => false,
+        }
+    }
+
     /// Returns true while any shift key is held on the keyboard.
     /// Otherwise returns false.
     ///
@@ -204,10 +492,10 @@ impl Inputs {
     pub fn key_pressed_logical(&self, check_key: Key<&str>) -> bool {
         if let Some(current) = &self.current {
             for action in &current.key_actions {
-                if let KeyAction::Pressed(key) = action {
-                    if key.as_ref() == check_key {
-                        return true;
-                    }
+                if let KeyAction::Pressed(key) = action
+                    && key.as_ref() == check_key
+                {
+                    return true;
                 }
             }
         }
@@ -224,10 +512,10 @@ impl Inputs {
     pub fn key_pressed_os_logical(&self, check_key: Key<&str>) -> bool {
         if let Some(current) = &self.current {
             for action in &current.key_actions {
-                if let KeyAction::PressedOs(key_code) = action {
-                    if key_code.as_ref() == check_key {
-                        return true;
-                    }
+                if let KeyAction::PressedOs(key_code) = action
+                    && key_code.as_ref() == check_key
+                {
+                    return true;
                 }
             }
         }
@@ -241,10 +529,10 @@ impl Inputs {
     pub fn key_released_logical(&self, check_key: Key<&str>) -> bool {
         if let Some(current) = &self.current {
             for action in &current.key_actions {
-                if let KeyAction::Released(key_code) = action {
-                    if key_code.as_ref() == check_key {
-                        return true;
-                    }
+                if let KeyAction::Released(key_code) = action
+                    && key_code.as_ref() == check_key
+                {
+                    return true;
                 }
             }
         }
@@ -267,10 +555,10 @@ impl Inputs {
     pub fn mouse_pressed(&self, mouse_button: MouseButton) -> bool {
         if let Some(current) = &self.current {
             for action in &current.mouse_actions {
-                if let MouseAction::Pressed(key_code) = *action {
-                    if key_code == mouse_button {
-                        return true;
-                    }
+                if let MouseAction::Pressed(key_code) = *action
+                    && key_code == mouse_button
+                {
+                    return true;
                 }
             }
         }
@@ -282,10 +570,10 @@ impl Inputs {
     pub fn mouse_released(&self, mouse_button: MouseButton) -> bool {
         if let Some(current) = &self.current {
             for action in &current.mouse_actions {
-                if let MouseAction::Released(key_code) = *action {
-                    if key_code == mouse_button {
-                        return true;
-                    }
+                if let MouseAction::Released(key_code) = *action
+                    && key_code == mouse_button
+                {
+                    return true;
                 }
             }
         }
@@ -294,13 +582,38 @@ impl Inputs {
 
     /// Returns true while the specified mouse button remains "pressed".
     /// Otherwise returns false.
+    ///
+    /// Like `key_held`, a click within a single step counts as held for that step.
     pub fn mouse_held(&self, mouse_button: MouseButton) -> bool {
         match &self.current {
-            Some(current) => current.mouse_held[mouse_button_to_int(&mouse_button)],
+            Some(current) => {
+                let i = mouse_button_to_int(&mouse_button);
+                current.mouse_held[i] || current.mouse_tapped[i]
+            }
             None => false,
         }
     }
 
+    /// Returns the number of clicks in the sequence the mouse button press of the last step
+    /// belongs to, 1 for a single click, 2 for a double click and so on.
+    /// Returns 0 when the button was not pressed during the last step.
+    ///
+    /// Presses belong to the same sequence when they are at most `double_click_interval` apart
+    /// and the cursor moved less than `double_click_distance` in between.
+    pub fn mouse_click_count(&self, mouse_button: MouseButton) -> u32 {
+        self.clicks
+            .iter()
+            .find(|c| c.button == mouse_button && c.this_step)
+            .map_or(0, |c| c.count)
+    }
+
+    /// Returns true when the mouse button was pressed for the second time in a row during the
+    /// last step, see `mouse_click_count`.
+    /// Otherwise returns false.
+    pub fn mouse_double_clicked(&self, mouse_button: MouseButton) -> bool {
+        self.mouse_click_count(mouse_button) == 2
+    }
+
     /// Returns `(0.0, 0.0)` when the window is not focused.
     /// Otherwise returns the amount scrolled by the mouse during the last step.
     /// Returns (horizontally, vertically)
@@ -320,15 +633,28 @@ impl Inputs {
         }
     }
 
+    /// Returns the cursor coordinates in pixels while the cursor is over the window, focused or not.
+    /// Otherwise returns `None`.
+    ///
+    /// Unlike `cursor`, the position is not reported while dragging outside of the window. The
+    /// position is not updated while the cursor is captured, use `mouse_diff` then.
+    pub fn cursor_position(&self) -> Option<Vec2f> {
+        self.cursor_position.filter(|_| self.cursor_in_window)
+    }
+
+    /// Returns true while the cursor is over the window.
+    pub fn cursor_in_window(&self) -> bool {
+        self.cursor_in_window
+    }
+
     /// Returns the change in cursor coordinates that occured during the last step, when window is focused AND (cursor is on window OR any mouse button remains held while cursor moved off window)
     /// Otherwise returns `(0.0, 0.0)`.
     pub fn cursor_diff(&self) -> (f32, f32) {
-        if let Some(current_input) = &self.current {
-            if let Some(cur) = current_input.cursor_point {
-                if let Some(prev) = current_input.cursor_point_prev {
-                    return (cur.0 - prev.0, cur.1 - prev.1);
-                }
-            }
+        if let Some(current_input) = &self.current
+            && let Some(cur) = current_input.cursor_point
+            && let Some(prev) = current_input.cursor_point_prev
+        {
+            return (cur.0 - prev.0, cur.1 - prev.1);
         }
         (0.0, 0.0)
     }
@@ -340,14 +666,157 @@ impl Inputs {
     /// Because this uses `DeviceEvent`s, the `step_with_windows_events`
     /// function won't update this as it is not a `WindowEvent`.
     pub fn mouse_diff(&self) -> (f32, f32) {
-        if let Some(current_input) = &self.current {
-            if let Some(diff) = current_input.mouse_diff {
-                return diff;
-            }
+        if let Some(current_input) = &self.current
+            && let Some(diff) = current_input.mouse_diff
+        {
+            return diff;
         }
         (0.0, 0.0)
     }
 
+    /// Set the gamepad deadzone, stick and trigger values below it read as zero.
+    ///
+    /// The deadzone is radial for sticks and the remaining range is rescaled to start at zero.
+    pub fn set_gamepad_deadzone(&mut self, deadzone: f32) {
+        self.gamepad_deadzone = deadzone.clamp(0.0, 0.99);
+    }
+
+    pub fn gamepad_deadzone(&self) -> f32 {
+        self.gamepad_deadzone
+    }
+
+    /// Returns the value of the gamepad axis with the deadzone applied, sticks are in [-1, 1] with
+    /// y pointing up and triggers in [0, 1].
+    ///
+    /// Returns 0 when the window is not focused or no gamepad is connected.
+    pub fn gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        use GamepadAxis::*;
+        let (x, y) = match axis {
+            LeftStickX | LeftStickY => self.gamepad_stick(LeftStickX, LeftStickY),
+            RightStickX | RightStickY => self.gamepad_stick(RightStickX, RightStickY),
+            LeftTrigger | RightTrigger => (self.apply_deadzone(self.gamepad_raw_axis(axis)), 0.0),
+        };
+        match axis {
+            LeftStickY | RightStickY => y,
+            _ => x,
+        }
+    }
+
+    /// Returns (x, y) of the left stick, see `gamepad_axis`.
+    pub fn gamepad_left_stick(&self) -> (f32, f32) {
+        self.gamepad_stick(GamepadAxis::LeftStickX, GamepadAxis::LeftStickY)
+    }
+
+    /// Returns (x, y) of the right stick, see `gamepad_axis`.
+    pub fn gamepad_right_stick(&self) -> (f32, f32) {
+        self.gamepad_stick(GamepadAxis::RightStickX, GamepadAxis::RightStickY)
+    }
+
+    /// Returns true when the gamepad button goes from "not pressed" to "pressed".
+    /// Otherwise returns false.
+    pub fn gamepad_button_pressed(&self, button: GamepadButton) -> bool {
+        self.current.is_some() && self.gamepad.pressed[button as usize]
+    }
+
+    /// Returns true when the gamepad button goes from "pressed" to "not pressed".
+    /// Otherwise returns false.
+    pub fn gamepad_button_released(&self, button: GamepadButton) -> bool {
+        self.current.is_some() && self.gamepad.released[button as usize]
+    }
+
+    /// Returns true while the gamepad button remains "pressed".
+    /// Otherwise returns false.
+    ///
+    /// Like `key_held`, a press within a single step counts as held for that step.
+    pub fn gamepad_button_held(&self, button: GamepadButton) -> bool {
+        let i = button as usize;
+        self.current.is_some() && (self.gamepad.held[i] || self.gamepad.pressed[i])
+    }
+
+    fn gamepad_raw_axis(&self, axis: GamepadAxis) -> f32 {
+        match self.current {
+            Some(_) => self.gamepad.axes[axis as usize],
+            None => 0.0,
+        }
+    }
+
+    fn gamepad_stick(&self, x: GamepadAxis, y: GamepadAxis) -> (f32, f32) {
+        let (x, y) = (self.gamepad_raw_axis(x), self.gamepad_raw_axis(y));
+        let length = x.hypot(y);
+        if length <= self.gamepad_deadzone {
+            return (0.0, 0.0);
+        }
+        let scale = self.apply_deadzone(length.min(1.0)) / length;
+        (x * scale, y * scale)
+    }
+
+    fn apply_deadzone(&self, value: f32) -> f32 {
+        let dz = self.gamepad_deadzone;
+        (value.abs() - dz).max(0.0) / (1.0 - dz) * value.signum()
+    }
+
+    pub fn input_map(&self) -> &InputMap {
+        &self.input_map
+    }
+
+    /// Replace the action bindings, from the game use `AppContext::set_input_map`
+    pub fn set_input_map(&mut self, input_map: InputMap) {
+        self.input_map = input_map;
+    }
+
+    /// Returns true when any binding of the action goes from "not pressed" to "pressed".
+    /// Otherwise returns false, including for unknown actions.
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.input_map
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => self.key_pressed(key),
+                Binding::Mouse(button) => self.mouse_pressed(button),
+                Binding::Gamepad(button) => self.gamepad_button_pressed(button),
+            })
+    }
+
+    /// Returns true when any binding of the action goes from "pressed" to "not pressed".
+    /// Otherwise returns false, including for unknown actions.
+    pub fn action_released(&self, action: &str) -> bool {
+        self.input_map
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => self.key_released(key),
+                Binding::Mouse(button) => self.mouse_released(button),
+                Binding::Gamepad(button) => self.gamepad_button_released(button),
+            })
+    }
+
+    /// Returns true while any binding of the action remains "pressed".
+    /// Otherwise returns false, including for unknown actions.
+    pub fn action_held(&self, action: &str) -> bool {
+        self.input_map
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => self.key_held(key),
+                Binding::Mouse(button) => self.mouse_held(button),
+                Binding::Gamepad(button) => self.gamepad_button_held(button),
+            })
+    }
+
+    /// Returns the touch points currently in contact with the screen.
+    /// Returns an empty slice when the window is not focused.
+    pub fn touches(&self) -> &[TouchPoint] {
+        match &self.current {
+            Some(current) => &current.touches,
+            None => &[],
+        }
+    }
+
+    /// Returns the touch point with the given id if it is still in contact with the screen.
+    pub fn touch(&self, id: u64) -> Option<&TouchPoint> {
+        self.touches().iter().find(|t| t.id == id)
+    }
+
     /// Returns the characters pressed during the last step.
     /// The characters are in the order they were pressed.
     pub fn text(&self) -> &[Key] {
@@ -404,10 +873,235 @@ impl Inputs {
     pub fn delta_time(&self) -> Option<Duration> {
         self.step_duration
     }
+
+    /// Returns the smoothed `std::time::Duration` elapsed since the last step, suitable for simulation.
+    /// Same as `delta_time` when smoothing is disabled.
+    /// Returns `None` if the step is still in progress.
+    pub fn smoothed_delta_time(&self) -> Option<Duration> {
+        self.smoothed_step_duration
+    }
+}
+
+/// Logical key produced by `keycode` on a US QWERTY layout without modifiers, for injected keys.
+/// None for keys without a layout independent meaning such as the numpad or media keys, only the
+/// physical key queries see those.
+fn us_logical_key(keycode: KeyCode) -> Option<Key> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA,
+        KeyCode::KeyB,
+        KeyCode::KeyC,
+        KeyCode::KeyD,
+        KeyCode::KeyE,
+        KeyCode::KeyF,
+        KeyCode::KeyG,
+        KeyCode::KeyH,
+        KeyCode::KeyI,
+        KeyCode::KeyJ,
+        KeyCode::KeyK,
+        KeyCode::KeyL,
+        KeyCode::KeyM,
+        KeyCode::KeyN,
+        KeyCode::KeyO,
+        KeyCode::KeyP,
+        KeyCode::KeyQ,
+        KeyCode::KeyR,
+        KeyCode::KeyS,
+        KeyCode::KeyT,
+        KeyCode::KeyU,
+        KeyCode::KeyV,
+        KeyCode::KeyW,
+        KeyCode::KeyX,
+        KeyCode::KeyY,
+        KeyCode::KeyZ,
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0,
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    const FUNCTION_KEYS: [(KeyCode, NamedKey); 12] = [
+        (KeyCode::F1, NamedKey::F1),
+        (KeyCode::F2, NamedKey::F2),
+        (KeyCode::F3, NamedKey::F3),
+        (KeyCode::F4, NamedKey::F4),
+        (KeyCode::F5, NamedKey::F5),
+        (KeyCode::F6, NamedKey::F6),
+        (KeyCode::F7, NamedKey::F7),
+        (KeyCode::F8, NamedKey::F8),
+        (KeyCode::F9, NamedKey::F9),
+        (KeyCode::F10, NamedKey::F10),
+        (KeyCode::F11, NamedKey::F11),
+        (KeyCode::F12, NamedKey::F12),
+    ];
+
+    let character = |c: char| Some(Key::Character(c.to_string().into()));
+    if let Some(i) = LETTERS.iter().position(|k| *k == keycode) {
+        return character((b'a' + i as u8).into());
+    }
+    if let Some(i) = DIGITS.iter().position(|k| *k == keycode) {
+        return character((b'0' + i as u8).into());
+    }
+    if let Some((_, named)) = FUNCTION_KEYS.iter().find(|(k, _)| *k == keycode) {
+        return Some(Key::Named(*named));
+    }
+    let named = match keycode {
+        KeyCode::Minus => return character('-'),
+        KeyCode::Equal => return character('='),
+        KeyCode::BracketLeft => return character('['),
+        KeyCode::BracketRight => return character(']'),
+        KeyCode::Backslash => return character('\\'),
+        KeyCode::Semicolon => return character(';'),
+        KeyCode::Quote => return character('\''),
+        KeyCode::Backquote => return character('`'),
+        KeyCode::Comma => return character(','),
+        KeyCode::Period => return character('.'),
+        KeyCode::Slash => return character('/'),
+        KeyCode::Space => NamedKey::Space,
+        KeyCode::Enter => NamedKey::Enter,
+        KeyCode::Escape => NamedKey::Escape,
+        KeyCode::Tab => NamedKey::Tab,
+        KeyCode::Backspace => NamedKey::Backspace,
+        KeyCode::Delete => NamedKey::Delete,
+        KeyCode::Insert => NamedKey::Insert,
+        KeyCode::Home => NamedKey::Home,
+        KeyCode::End => NamedKey::End,
+        KeyCode::PageUp => NamedKey::PageUp,
+        KeyCode::PageDown => NamedKey::PageDown,
+        KeyCode::ArrowUp => NamedKey::ArrowUp,
+        KeyCode::ArrowDown => NamedKey::ArrowDown,
+        KeyCode::ArrowLeft => NamedKey::ArrowLeft,
+        KeyCode::ArrowRight => NamedKey::ArrowRight,
+        KeyCode::ShiftLeft | KeyCode::ShiftRight => NamedKey::Shift,
+        KeyCode::ControlLeft | KeyCode::ControlRight => NamedKey::Control,
+        KeyCode::AltLeft | KeyCode::AltRight => NamedKey::Alt,
+        KeyCode::SuperLeft | KeyCode::SuperRight => NamedKey::Super,
+        KeyCode::CapsLock => NamedKey::CapsLock,
+        _ => return None,
+    };
+    Some(Key::Named(named))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+/// Gamepad buttons by position, `South` is A on Xbox layouts and Cross on PlayStation layouts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    const COUNT: usize = Self::DPadRight as usize + 1;
+}
+
+#[derive(Clone, Debug)]
+struct Click {
+    button: MouseButton,
+    /// Time of the latest press
+    time: Instant,
+    /// Cursor position at the latest press, `None` when the cursor was unknown
+    position: Option<(f32, f32)>,
+    count: u32,
+    /// The latest press happened during the current step
+    this_step: bool,
+}
+
+/// Raw state of the gamepads, before the deadzone is applied
+#[derive(Clone, Debug, Default)]
+struct GamepadState {
+    axes: [f32; 6],
+    held: [bool; GamepadButton::COUNT],
+    pressed: [bool; GamepadButton::COUNT],
+    released: [bool; GamepadButton::COUNT],
+}
+
+impl GamepadState {
+    fn step(&mut self) {
+        self.pressed = [false; GamepadButton::COUNT];
+        self.released = [false; GamepadButton::COUNT];
+    }
+
+    fn button(&mut self, button: GamepadButton, pressed: bool) {
+        let i = button as usize;
+        if pressed && !self.held[i] {
+            self.pressed[i] = true;
+        } else if !pressed && self.held[i] {
+            self.released[i] = true;
+        }
+        self.held[i] = pressed;
+    }
+}
+
+/// Exponential moving average of frame durations.
+///
+/// The difference between the raw and smoothed total time is fed back progressively,
+/// so the smoothed time does not drift away from the real time over long sessions.
+#[derive(Clone, Debug)]
+pub struct DtSmoother {
+    pub factor: f32,
+    average: Option<f64>,
+    drift: f64,
+}
+
+impl DtSmoother {
+    const DRIFT_CORRECTION: f64 = 0.1;
+
+    pub fn new(factor: f32) -> Self {
+        Self {
+            factor: factor.clamp(f32::EPSILON, 1.0),
+            average: None,
+            drift: 0.0,
+        }
+    }
+
+    pub fn smooth(&mut self, raw: Duration) -> Duration {
+        let raw = raw.as_secs_f64();
+        let average = match self.average {
+            Some(average) => average + self.factor as f64 * (raw - average),
+            None => raw,
+        };
+        self.average = Some(average);
+
+        let smoothed = (average + self.drift * Self::DRIFT_CORRECTION).max(0.0);
+        self.drift += raw - smoothed;
+        Duration::from_secs_f64(smoothed)
+    }
 }
 
 pub mod current {
-    use winit::event::{DeviceEvent, ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+    use winit::event::{
+        DeviceEvent, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent,
+    };
     use winit::keyboard::{Key, PhysicalKey};
 
     #[derive(Clone, Debug)]
@@ -417,13 +1111,34 @@ pub mod current {
         pub scancode_actions: Vec<ScanCodeAction>,
         pub key_held: Vec<Key>,
         pub scancode_held: Vec<PhysicalKey>, // some scan codes are higher than 255 so using an array may be dangerous
+        /// Keys pressed and released during the current step
+        pub scancode_tapped: Vec<PhysicalKey>,
         pub mouse_held: [bool; 255],
+        /// Buttons pressed and released during the current step
+        pub mouse_tapped: [bool; 255],
         pub cursor_point: Option<(f32, f32)>,
         pub cursor_point_prev: Option<(f32, f32)>,
         pub mouse_diff: Option<(f32, f32)>,
         pub y_scroll_diff: f32,
         pub x_scroll_diff: f32,
         pub text: Vec<Key>,
+        pub touches: Vec<TouchPoint>,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct TouchPoint {
+        pub id: u64,
+        /// Position where the touch started, in pixels
+        pub start: (f32, f32),
+        pub position: (f32, f32),
+        /// Position at the beginning of the current step
+        pub position_prev: (f32, f32),
+    }
+
+    impl Default for CurrentInput {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 
     impl CurrentInput {
@@ -434,13 +1149,16 @@ pub mod current {
                 scancode_actions: vec![],
                 key_held: vec![],
                 scancode_held: vec![],
+                scancode_tapped: vec![],
                 mouse_held: [false; 255],
+                mouse_tapped: [false; 255],
                 cursor_point: None,
                 cursor_point_prev: None,
                 mouse_diff: None,
                 y_scroll_diff: 0.0,
                 x_scroll_diff: 0.0,
                 text: vec![],
+                touches: vec![],
             }
         }
 
@@ -448,83 +1166,65 @@ pub mod current {
             self.mouse_actions.clear();
             self.key_actions.clear();
             self.scancode_actions.clear();
+            self.scancode_tapped.clear();
+            self.mouse_tapped = [false; 255];
             self.cursor_point_prev = self.cursor_point;
             self.mouse_diff = None;
             self.y_scroll_diff = 0.0;
             self.x_scroll_diff = 0.0;
             self.text.clear();
+            for touch in &mut self.touches {
+                touch.position_prev = touch.position;
+            }
         }
 
         pub fn handle_event(&mut self, event: &WindowEvent) {
             match event {
                 WindowEvent::KeyboardInput { event, .. } => match event.state {
                     ElementState::Pressed => {
-                        let logical_key = &event.logical_key;
-                        if !self.key_held.contains(logical_key) {
-                            self.key_actions
-                                .push(KeyAction::Pressed(logical_key.clone()));
-                        }
-
-                        self.key_held.push(logical_key.clone());
-                        self.key_actions
-                            .push(KeyAction::PressedOs(logical_key.clone()));
-                        self.text.push(logical_key.clone());
-
-                        let physical_key = &event.physical_key;
-                        if !self.scancode_held.contains(physical_key) {
-                            self.scancode_actions
-                                .push(ScanCodeAction::Pressed(*physical_key));
-                            self.scancode_held.push(*physical_key);
-                        }
-
-                        self.scancode_actions
-                            .push(ScanCodeAction::PressedOs(*physical_key));
+                        self.press_key(event.physical_key, Some(&event.logical_key))
                     }
                     ElementState::Released => {
-                        let logical_key = &event.logical_key;
-                        self.key_held.retain(|x| x != logical_key);
-                        self.key_actions
-                            .push(KeyAction::Released(logical_key.clone()));
-
-                        let physical_key = &event.physical_key;
-                        self.scancode_held.retain(|x| x != physical_key);
-                        self.scancode_actions
-                            .push(ScanCodeAction::Released(*physical_key));
+                        self.release_key(event.physical_key, Some(&event.logical_key))
                     }
                 },
                 WindowEvent::CursorMoved { position, .. } => {
                     self.cursor_point = Some((position.x as f32, position.y as f32));
                 }
-                WindowEvent::MouseInput {
-                    state: ElementState::Pressed,
-                    button,
-                    ..
-                } => {
-                    let button_usize = mouse_button_to_int(button);
-                    self.mouse_held[button_usize] = true;
-                    self.mouse_actions.push(MouseAction::Pressed(*button));
-                }
-                WindowEvent::MouseInput {
-                    state: ElementState::Released,
-                    button,
-                    ..
-                } => {
-                    let button_usize = mouse_button_to_int(button);
-                    self.mouse_held[button_usize] = false;
-                    self.mouse_actions.push(MouseAction::Released(*button));
+                WindowEvent::MouseInput { state, button, .. } => {
+                    self.mouse_button(*button, state.is_pressed());
                 }
                 WindowEvent::MouseWheel { delta, .. } => {
                     // I just took this from three-rs, no idea why this magic number was chosen ¯\_(ツ)_/¯
                     const PIXELS_PER_LINE: f64 = 38.0;
 
                     match delta {
-                        MouseScrollDelta::LineDelta(x, y) => {
-                            self.x_scroll_diff += x;
-                            self.y_scroll_diff += y;
+                        MouseScrollDelta::LineDelta(x, y) => self.scroll(*x, *y),
+                        MouseScrollDelta::PixelDelta(delta) => self.scroll(
+                            (delta.x / PIXELS_PER_LINE) as f32,
+                            (delta.y / PIXELS_PER_LINE) as f32,
+                        ),
+                    }
+                }
+                WindowEvent::Touch(touch) => {
+                    let position = (touch.location.x as f32, touch.location.y as f32);
+                    match touch.phase {
+                        TouchPhase::Started => {
+                            self.touches.retain(|t| t.id != touch.id);
+                            self.touches.push(TouchPoint {
+                                id: touch.id,
+                                start: position,
+                                position,
+                                position_prev: position,
+                            });
                         }
-                        MouseScrollDelta::PixelDelta(delta) => {
-                            self.y_scroll_diff += (delta.y / PIXELS_PER_LINE) as f32;
-                            self.x_scroll_diff += (delta.x / PIXELS_PER_LINE) as f32;
+                        TouchPhase::Moved => {
+                            if let Some(t) = self.touches.iter_mut().find(|t| t.id == touch.id) {
+                                t.position = position;
+                            }
+                        }
+                        TouchPhase::Ended | TouchPhase::Cancelled => {
+                            self.touches.retain(|t| t.id != touch.id);
                         }
                     }
                 }
@@ -534,13 +1234,78 @@ pub mod current {
 
         pub fn handle_device_event(&mut self, event: &DeviceEvent) {
             if let DeviceEvent::MouseMotion { delta, .. } = event {
-                match self.mouse_diff {
-                    Some((x, y)) => {
-                        self.mouse_diff = Some((x + delta.0 as f32, y + delta.1 as f32))
-                    }
-                    None => self.mouse_diff = Some((delta.0 as f32, delta.1 as f32)),
+                self.mouse_motion(delta.0 as f32, delta.1 as f32);
+            }
+        }
+
+        /// `logical_key` is `None` for synthetic presses that only know the physical key
+        pub fn press_key(&mut self, physical_key: PhysicalKey, logical_key: Option<&Key>) {
+            if let Some(logical_key) = logical_key {
+                if !self.key_held.contains(logical_key) {
+                    self.key_actions
+                        .push(KeyAction::Pressed(logical_key.clone()));
                 }
+
+                self.key_held.push(logical_key.clone());
+                self.key_actions
+                    .push(KeyAction::PressedOs(logical_key.clone()));
+                self.text.push(logical_key.clone());
+            }
+
+            if !self.scancode_held.contains(&physical_key) {
+                self.scancode_actions
+                    .push(ScanCodeAction::Pressed(physical_key));
+                self.scancode_held.push(physical_key);
+            }
+
+            self.scancode_actions
+                .push(ScanCodeAction::PressedOs(physical_key));
+        }
+
+        pub fn release_key(&mut self, physical_key: PhysicalKey, logical_key: Option<&Key>) {
+            if let Some(logical_key) = logical_key {
+                self.key_held.retain(|x| x != logical_key);
+                self.key_actions
+                    .push(KeyAction::Released(logical_key.clone()));
+            }
+
+            if self
+                .scancode_actions
+                .contains(&ScanCodeAction::Pressed(physical_key))
+            {
+                self.scancode_tapped.push(physical_key);
             }
+            self.scancode_held.retain(|x| *x != physical_key);
+            self.scancode_actions
+                .push(ScanCodeAction::Released(physical_key));
+        }
+
+        pub fn mouse_button(&mut self, button: MouseButton, pressed: bool) {
+            let i = mouse_button_to_int(&button);
+            if !pressed
+                && self
+                    .mouse_actions
+                    .iter()
+                    .any(|a| matches!(a, MouseAction::Pressed(b) if *b == button))
+            {
+                self.mouse_tapped[i] = true;
+            }
+            self.mouse_held[i] = pressed;
+            self.mouse_actions.push(if pressed {
+                MouseAction::Pressed(button)
+            } else {
+                MouseAction::Released(button)
+            });
+        }
+
+        pub fn mouse_motion(&mut self, dx: f32, dy: f32) {
+            let (x, y) = self.mouse_diff.unwrap_or((0.0, 0.0));
+            self.mouse_diff = Some((x + dx, y + dy));
+        }
+
+        pub fn scroll(&mut self, dx: f32, dy: f32) {
+            self.x_scroll_diff += dx;
+            self.y_scroll_diff += dy;
         }
     }
 
@@ -575,3 +1340,243 @@ pub mod current {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_pressed_and_released_within_a_step_is_a_tap() {
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.inject_key(KeyCode::Space, true);
+        inputs.inject_key(KeyCode::Space, false);
+        inputs.end_step();
+
+        assert!(inputs.key_tapped(KeyCode::Space));
+        assert!(inputs.key_pressed(KeyCode::Space));
+        assert!(inputs.key_released(KeyCode::Space));
+        assert!(inputs.key_held(KeyCode::Space));
+
+        inputs.step();
+        inputs.end_step();
+        assert!(!inputs.key_tapped(KeyCode::Space));
+        assert!(!inputs.key_held(KeyCode::Space));
+    }
+
+    #[test]
+    fn key_held_across_steps_is_not_a_tap() {
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.inject_key(KeyCode::Space, true);
+        inputs.end_step();
+        inputs.step();
+        inputs.inject_key(KeyCode::Space, false);
+        inputs.end_step();
+
+        assert!(inputs.key_released(KeyCode::Space));
+        assert!(!inputs.key_tapped(KeyCode::Space));
+    }
+
+    #[test]
+    fn two_rapid_presses_are_a_double_click() {
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.inject_cursor_position(10.0, 10.0);
+        inputs.inject_mouse_button(MouseButton::Left, true);
+        inputs.inject_mouse_button(MouseButton::Left, false);
+        inputs.end_step();
+        assert_eq!(inputs.mouse_click_count(MouseButton::Left), 1);
+        assert!(!inputs.mouse_double_clicked(MouseButton::Left));
+
+        inputs.step();
+        inputs.inject_mouse_button(MouseButton::Left, true);
+        inputs.end_step();
+        assert!(inputs.mouse_double_clicked(MouseButton::Left));
+        assert_eq!(inputs.mouse_click_count(MouseButton::Right), 0);
+    }
+
+    #[test]
+    fn presses_far_apart_start_a_new_click_sequence() {
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.inject_cursor_position(10.0, 10.0);
+        inputs.inject_mouse_button(MouseButton::Left, true);
+        inputs.inject_mouse_button(MouseButton::Left, false);
+        inputs.end_step();
+
+        inputs.step();
+        inputs.inject_cursor_position(100.0, 10.0);
+        inputs.inject_mouse_button(MouseButton::Left, true);
+        inputs.end_step();
+        assert_eq!(inputs.mouse_click_count(MouseButton::Left), 1);
+    }
+
+    #[test]
+    fn held_duration_accumulates_until_release() {
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.inject_key(KeyCode::KeyE, true);
+        std::thread::sleep(Duration::from_millis(5));
+        inputs.end_step();
+        assert!(inputs.key_pressed(KeyCode::KeyE));
+        let first = inputs.key_held_duration(KeyCode::KeyE);
+        assert!(first >= Duration::from_millis(5));
+
+        inputs.step();
+        std::thread::sleep(Duration::from_millis(5));
+        inputs.end_step();
+        assert!(inputs.key_held(KeyCode::KeyE));
+        assert!(!inputs.key_pressed(KeyCode::KeyE));
+        let held = inputs.key_held_duration(KeyCode::KeyE);
+        assert!(held >= first + Duration::from_millis(5));
+
+        inputs.step();
+        inputs.inject_key(KeyCode::KeyE, false);
+        inputs.end_step();
+        assert!(inputs.key_released(KeyCode::KeyE));
+        assert!(!inputs.key_held(KeyCode::KeyE));
+        // The total is still readable on the release step
+        assert_eq!(inputs.key_held_duration(KeyCode::KeyE), held);
+
+        inputs.step();
+        inputs.end_step();
+        assert_eq!(inputs.key_held_duration(KeyCode::KeyE), Duration::ZERO);
+    }
+
+    #[test]
+    fn tapped_key_reports_a_held_duration_on_its_step() {
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.inject_key(KeyCode::KeyE, true);
+        inputs.inject_key(KeyCode::KeyE, false);
+        std::thread::sleep(Duration::from_millis(5));
+        inputs.end_step();
+        assert!(inputs.key_tapped(KeyCode::KeyE));
+        assert!(inputs.key_held_duration(KeyCode::KeyE) >= Duration::from_millis(5));
+
+        inputs.step();
+        inputs.end_step();
+        assert_eq!(inputs.key_held_duration(KeyCode::KeyE), Duration::ZERO);
+    }
+
+    #[test]
+    fn rebound_action_fires_on_the_new_key_only() {
+        use crate::engine::input_map::MOVE_FORWARD;
+
+        let mut map = InputMap::default();
+        map.rebind(MOVE_FORWARD, [Binding::Key(KeyCode::ArrowUp)]);
+        let mut inputs = Inputs::new();
+        inputs.set_input_map(map);
+
+        inputs.step();
+        inputs.inject_key(KeyCode::KeyW, true);
+        inputs.end_step();
+        assert!(!inputs.action_pressed(MOVE_FORWARD));
+        assert!(!inputs.action_held(MOVE_FORWARD));
+
+        inputs.step();
+        inputs.inject_key(KeyCode::ArrowUp, true);
+        inputs.end_step();
+        assert!(inputs.action_pressed(MOVE_FORWARD));
+        assert!(inputs.action_held(MOVE_FORWARD));
+
+        inputs.step();
+        inputs.inject_key(KeyCode::ArrowUp, false);
+        inputs.end_step();
+        assert!(inputs.action_released(MOVE_FORWARD));
+        assert!(!inputs.action_held(MOVE_FORWARD));
+    }
+
+    #[test]
+    fn injected_cursor_moves_update_the_position() {
+        let mut inputs = Inputs::new();
+        assert_eq!(inputs.cursor_position(), None);
+
+        inputs.step();
+        inputs.inject_cursor_position(10.0, 20.0);
+        inputs.end_step();
+        assert_eq!(inputs.cursor_position(), Some(Vec2f::new(10.0, 20.0)));
+        assert!(inputs.cursor_in_window());
+
+        inputs.step();
+        inputs.inject_cursor_position(30.0, 5.0);
+        inputs.end_step();
+        assert_eq!(inputs.cursor_position(), Some(Vec2f::new(30.0, 5.0)));
+        assert_eq!(inputs.cursor_diff(), (20.0, -15.0));
+    }
+
+    #[test]
+    fn cursor_leaves_the_window_on_events_and_out_of_bounds_moves() {
+        let device_id = winit::event::DeviceId::dummy();
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.process_window_event(&WindowEvent::Resized(PhysicalSize::new(100, 50)));
+        inputs.process_window_event(&WindowEvent::CursorEntered { device_id });
+        assert!(inputs.cursor_in_window());
+
+        inputs.inject_cursor_position(40.0, 40.0);
+        assert_eq!(inputs.cursor_position(), Some(Vec2f::new(40.0, 40.0)));
+
+        // Dragging past the edge keeps reporting moves, but the cursor is no longer over the window
+        inputs.inject_cursor_position(120.0, 40.0);
+        assert!(!inputs.cursor_in_window());
+        assert_eq!(inputs.cursor_position(), None);
+
+        inputs.inject_cursor_position(99.0, 49.0);
+        assert!(inputs.cursor_in_window());
+        inputs.process_window_event(&WindowEvent::CursorLeft { device_id });
+        assert!(!inputs.cursor_in_window());
+        assert_eq!(inputs.cursor_position(), None);
+        inputs.end_step();
+    }
+
+    #[test]
+    fn injected_keys_have_a_logical_key() {
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.inject_key(KeyCode::KeyQ, true);
+        inputs.inject_key(KeyCode::Escape, true);
+        inputs.inject_key(KeyCode::NumpadAdd, true);
+        inputs.end_step();
+        assert!(inputs.key_pressed_logical(Key::Character("q")));
+        assert!(inputs.key_held_logical(Key::Character("q")));
+        assert!(inputs.key_pressed_logical(Key::Named(NamedKey::Escape)));
+        // Without a logical key the physical one is still reported
+        assert!(inputs.key_pressed(KeyCode::NumpadAdd));
+
+        inputs.step();
+        inputs.inject_key(KeyCode::KeyQ, false);
+        inputs.end_step();
+        assert!(inputs.key_released_logical(Key::Character("q")));
+        assert!(!inputs.key_held_logical(Key::Character("q")));
+    }
+
+    #[test]
+    fn injecting_while_unfocused_keeps_the_window_events_ignored() {
+        let device_id = winit::event::DeviceId::dummy();
+        let click = |button| WindowEvent::MouseInput {
+            device_id,
+            state: ElementState::Pressed,
+            button,
+        };
+        let mut inputs = Inputs::new();
+        inputs.step();
+        inputs.process_window_event(&WindowEvent::Focused(false));
+        inputs.inject_mouse_button(MouseButton::Left, true);
+        inputs.process_window_event(&click(MouseButton::Right));
+        inputs.process_device_event(&DeviceEvent::MouseMotion { delta: (5.0, 5.0) });
+        inputs.end_step();
+        assert!(inputs.mouse_held(MouseButton::Left));
+        assert!(!inputs.mouse_held(MouseButton::Right));
+        assert_eq!(inputs.mouse_diff(), (0.0, 0.0));
+
+        // Focusing back keeps the injected state and listens to the window again
+        inputs.step();
+        inputs.process_window_event(&WindowEvent::Focused(true));
+        inputs.process_window_event(&click(MouseButton::Right));
+        inputs.end_step();
+        assert!(inputs.mouse_held(MouseButton::Left));
+        assert!(inputs.mouse_held(MouseButton::Right));
+    }
+}
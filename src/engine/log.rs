@@ -0,0 +1,57 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of messages kept for the editor log panel, older ones are dropped
+pub const LOG_CAPACITY: usize = 512;
+
+#[derive(Debug, Clone)]
+pub struct LogMessage {
+    /// Time since the first message of the process
+    pub time: Duration,
+    pub text: String,
+}
+
+struct Log {
+    start: Option<Instant>,
+    messages: VecDeque<LogMessage>,
+}
+
+static LOG: Mutex<Log> = Mutex::new(Log {
+    start: None,
+    messages: VecDeque::new(),
+});
+
+/// Print `text` and keep it for the editor log panel, see the `log!` macro
+pub fn log(text: String) {
+    println!("{text}");
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    let time = log.start.get_or_insert_with(Instant::now).elapsed();
+    if log.messages.len() == LOG_CAPACITY {
+        log.messages.pop_front();
+    }
+    log.messages.push_back(LogMessage { time, text });
+}
+
+/// Kept messages, oldest first
+pub fn messages() -> Vec<LogMessage> {
+    let log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    log.messages.iter().cloned().collect()
+}
+
+pub fn clear() {
+    LOG.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .messages
+        .clear();
+}
+
+/// `println!` that also shows the message in the editor log panel
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::engine::log::log(format!($($arg)*))
+    };
+}
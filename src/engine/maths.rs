@@ -1,4 +1,10 @@
 pub use nalgebra as na;
+use serde::{Deserialize, Serialize};
+
+/// Homogeneous coordinates, `Vec3f::push(1.0)` for a point and `push(0.0)` for a direction
+pub type Vec4f = na::Vector4<f32>;
+pub type Vec4u = na::Vector4<u32>;
+pub type Vec4i = na::Vector4<i32>;
 
 pub type Vec3f = na::Vector3<f32>;
 pub type Vec3u = na::Vector3<u32>;
@@ -8,4 +14,403 @@ pub type Vec2f = na::Vector2<f32>;
 pub type Vec2u = na::Vector2<u32>;
 pub type Vec2i = na::Vector2<i32>;
 
+pub type Mat3f = na::Matrix3<f32>;
 pub type Mat4f = na::Matrix4<f32>;
+
+pub trait Mat4Ext {
+    /// Inverse transpose of the upper left 3x3, transforms normals so that they stay perpendicular
+    /// to the surface under non uniform scale. Falls back to the 3x3 itself when not invertible.
+    fn normal_matrix(&self) -> Mat3f;
+}
+
+impl Mat4Ext for Mat4f {
+    fn normal_matrix(&self) -> Mat3f {
+        let linear = self.fixed_view::<3, 3>(0, 0).into_owned();
+        linear
+            .try_inverse()
+            .map_or(linear, |inverse| inverse.transpose())
+    }
+}
+
+pub type Quatf = na::UnitQuaternion<f32>;
+
+/// Orientation of a camera looking down `-Z` with `+Y` up once rotated by yaw, pitch and roll.
+/// Yaw turns from `+X` towards `+Z` so a yaw of `-PI / 2` looks down `-Z`, pitch looks up and
+/// roll turns clockwise around the view direction, as `Camera` angles do.
+pub fn quat_from_yaw_pitch_roll(yaw: f32, pitch: f32, roll: f32) -> Quatf {
+    Quatf::from_axis_angle(&Vec3f::y_axis(), -(yaw + std::f32::consts::FRAC_PI_2))
+        * Quatf::from_axis_angle(&Vec3f::x_axis(), pitch)
+        * Quatf::from_axis_angle(&Vec3f::z_axis(), -roll)
+}
+
+/// Inverse of `quat_from_yaw_pitch_roll`, the yaw is arbitrary when looking straight up or down
+pub fn quat_to_yaw_pitch_roll(orientation: &Quatf) -> (f32, f32, f32) {
+    let forward = quat_forward(orientation);
+    let yaw = forward.z.atan2(forward.x);
+    let pitch = forward.y.clamp(-1.0, 1.0).asin();
+    let unrolled = quat_from_yaw_pitch_roll(yaw, pitch, 0.0);
+    // What remains is a rotation around the local Z axis
+    let roll = -(unrolled.inverse() * orientation).scaled_axis().z;
+    (yaw, pitch, roll)
+}
+
+/// Direction looked at, the rotated `-Z` axis
+pub fn quat_forward(orientation: &Quatf) -> Vec3f {
+    orientation * -Vec3f::z()
+}
+
+/// The rotated `+Y` axis
+pub fn quat_up(orientation: &Quatf) -> Vec3f {
+    orientation * Vec3f::y()
+}
+
+/// Translation, rotation and scale, applied to a point in the reverse order
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub translation: Vec3f,
+    pub rotation: Quatf,
+    pub scale: Vec3f,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3f::zeros(),
+            rotation: Quatf::identity(),
+            scale: Vec3f::repeat(1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn new(translation: Vec3f, rotation: Quatf, scale: Vec3f) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn from_translation(translation: Vec3f) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn to_matrix(&self) -> Mat4f {
+        Mat4f::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Mat4f::new_nonuniform_scaling(&self.scale)
+    }
+
+    /// Decompose an affine matrix, any shear is lost. A negative determinant flips the x scale.
+    pub fn from_matrix(matrix: &Mat4f) -> Self {
+        let linear = matrix.fixed_view::<3, 3>(0, 0).into_owned();
+        let mut scale = Vec3f::from_fn(|i, _| linear.column(i).norm());
+        if linear.determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+        let rotation_matrix = Mat3f::from_fn(|row, column| {
+            linear[(row, column)]
+                / scale[column]
+                    .abs()
+                    .max(f32::EPSILON)
+                    .copysign(scale[column])
+        });
+        Self {
+            translation: matrix.fixed_view::<3, 1>(0, 3).into(),
+            rotation: Quatf::from_rotation_matrix(&na::Rotation3::from_matrix(&rotation_matrix)),
+            scale,
+        }
+    }
+
+    pub fn transform_point(&self, point: &Vec3f) -> Vec3f {
+        self.translation + self.rotation * point.component_mul(&self.scale)
+    }
+}
+
+impl From<Transform> for Mat4f {
+    fn from(transform: Transform) -> Self {
+        transform.to_matrix()
+    }
+}
+
+/// `parent * child` places the child in the space of the parent. Exact unless a non uniform
+/// scale of the parent is combined with a rotation of the child, which would need a shear.
+impl std::ops::Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, child: Self) -> Self {
+        Self {
+            translation: self.transform_point(&child.translation),
+            rotation: self.rotation * child.rotation,
+            scale: self.scale.component_mul(&child.scale),
+        }
+    }
+}
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3f,
+    pub max: Vec3f,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3f, max: Vec3f) -> Self {
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3f {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3f {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn clamp_point(&self, point: &Vec3f) -> Vec3f {
+        point.zip_zip_map(&self.min, &self.max, |p, min, max| p.clamp(min, max))
+    }
+
+    pub fn contains_point(&self, point: &Vec3f) -> bool {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+
+    /// Smallest box containing both boxes
+    pub fn merge(&self, other: &Self) -> Self {
+        Self::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    /// Smallest box containing every point, None when there are no points
+    pub fn from_points(points: impl IntoIterator<Item = Vec3f>) -> Option<Self> {
+        points
+            .into_iter()
+            .map(|p| Self::new(p, p))
+            .reduce(|a, b| a.merge(&b))
+    }
+
+    /// Box containing this box once transformed
+    pub fn transformed(&self, transform: &Mat4f) -> Self {
+        let center = transform.transform_point(&self.center().into()).coords;
+        let half_extents = transform.fixed_view::<3, 3>(0, 0).abs() * self.half_extents();
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    /// Slab test, returns the distance along `direction` to the first intersection,
+    /// or 0 when the ray starts inside the box
+    pub fn intersects_ray(&self, origin: &Vec3f, direction: &Vec3f) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+
+        for i in 0..3 {
+            if direction[i].abs() < f32::EPSILON {
+                // Parallel to the slab, the origin must already be between its planes
+                if origin[i] < self.min[i] || origin[i] > self.max[i] {
+                    return None;
+                }
+                continue;
+            }
+
+            let inv = 1.0 / direction[i];
+            let t0 = (self.min[i] - origin[i]) * inv;
+            let t1 = (self.max[i] - origin[i]) * inv;
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+            if t_min > t_max {
+                return None;
+            }
+        }
+
+        Some(t_min)
+    }
+}
+
+/// Planes of the volume seen by a camera, pointing inward
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// (normal, distance) of the left, right, bottom, top, near and far planes
+    pub planes: [Vec4f; 6],
+}
+
+impl Frustum {
+    /// Extract the planes of a view projection matrix with a [-1, 1] clip depth range
+    pub fn from_view_proj(view_proj: &Mat4f) -> Self {
+        let row = |i: usize| view_proj.row(i).transpose();
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(3) + row(2),
+            row(3) - row(2),
+        ]
+        .map(|p| p / p.xyz().norm());
+        Self { planes }
+    }
+
+    /// Whether the box is at least partially inside the frustum grown by `margin` world units
+    pub fn intersects_aabb(&self, aabb: &Aabb, margin: f32) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner of the box furthest along the plane normal
+            let corner = Vec3f::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.xyz().dot(&corner) + plane.w >= -margin
+        })
+    }
+}
+
+/// Axis aligned 2D rectangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min: Vec2f,
+    pub max: Vec2f,
+}
+
+impl Rect {
+    pub fn new(min: Vec2f, max: Vec2f) -> Self {
+        Self { min, max }
+    }
+
+    pub fn contains(&self, point: &Vec2f) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    pub fn clamp_point(&self, point: &Vec2f) -> Vec2f {
+        point.zip_zip_map(&self.min, &self.max, |p, min, max| p.clamp(min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vec3f::repeat(-1.0), Vec3f::repeat(1.0))
+    }
+
+    #[test]
+    fn ray_hits_the_near_face() {
+        let t = unit_box().intersects_ray(&Vec3f::new(-5.0, 0.0, 0.0), &Vec3f::x());
+        assert_eq!(t, Some(4.0));
+    }
+
+    #[test]
+    fn ray_pointing_away_misses() {
+        let t = unit_box().intersects_ray(&Vec3f::new(-5.0, 0.0, 0.0), &-Vec3f::x());
+        assert_eq!(t, None);
+    }
+
+    #[test]
+    fn ray_starting_inside_hits_at_zero() {
+        let t = unit_box().intersects_ray(&Vec3f::new(0.2, -0.3, 0.5), &Vec3f::y());
+        assert_eq!(t, Some(0.0));
+    }
+
+    #[test]
+    fn ray_parallel_to_a_slab() {
+        let direction = Vec3f::z();
+        let between = unit_box().intersects_ray(&Vec3f::new(0.5, 0.5, -3.0), &direction);
+        assert_eq!(between, Some(2.0));
+        let outside = unit_box().intersects_ray(&Vec3f::new(1.5, 0.5, -3.0), &direction);
+        assert_eq!(outside, None);
+    }
+
+    #[test]
+    fn diagonal_ray_misses_past_the_corner() {
+        let direction = Vec3f::new(1.0, 1.0, 0.0).normalize();
+        let t = unit_box().intersects_ray(&Vec3f::new(-3.0, 0.5, 0.0), &direction);
+        assert_eq!(t, None);
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular_under_non_uniform_scale() {
+        let transform = Mat4f::new_translation(&Vec3f::new(3.0, -2.0, 7.0))
+            * Mat4f::new_nonuniform_scaling(&Vec3f::new(2.0, 1.0, 1.0));
+        let normal_matrix = transform.normal_matrix();
+        assert_eq!(
+            normal_matrix,
+            Mat3f::from_diagonal(&Vec3f::new(0.5, 1.0, 1.0))
+        );
+
+        // Plane at 45 degrees between X and Y
+        let normal = Vec3f::new(1.0, 1.0, 0.0);
+        let tangent = Vec3f::new(1.0, -1.0, 0.0);
+        let scaled_tangent = transform.transform_vector(&tangent);
+        assert_eq!((normal_matrix * normal).dot(&scaled_tangent), 0.0);
+        // Transforming the normal like a position would tilt it off the surface
+        assert_ne!(
+            transform.transform_vector(&normal).dot(&scaled_tangent),
+            0.0
+        );
+    }
+
+    fn default_camera_frustum() -> Frustum {
+        let camera = crate::engine::graphics::camera::Camera::default();
+        let (view, proj) = camera.get_view_proj_matrices(Vec2u::new(800, 600));
+        Frustum::from_view_proj(&(proj * view))
+    }
+
+    #[test]
+    fn frustum_rejects_a_box_behind_the_camera() {
+        // The default camera is at +5 on Z looking towards the origin
+        let frustum = default_camera_frustum();
+        let behind = Aabb::new(Vec3f::new(-1.0, -1.0, 7.0), Vec3f::new(1.0, 1.0, 9.0));
+        assert!(!frustum.intersects_aabb(&behind, 0.0));
+        assert!(frustum.intersects_aabb(&unit_box(), 0.0));
+    }
+
+    #[test]
+    fn frustum_margin_keeps_boxes_just_outside() {
+        let frustum = default_camera_frustum();
+        let beside = Aabb::new(Vec3f::new(20.0, -1.0, -1.0), Vec3f::new(22.0, 1.0, 1.0));
+        assert!(!frustum.intersects_aabb(&beside, 0.0));
+        assert!(frustum.intersects_aabb(&beside, 100.0));
+    }
+
+    #[test]
+    fn yaw_then_pitch_matches_the_euler_direction() {
+        for (yaw, pitch) in [(-FRAC_PI_2, 0.0), (-1.5, 0.1), (-1.6, -0.05), (0.3, 0.2)] {
+            let orientation = quat_from_yaw_pitch_roll(yaw, pitch, 0.0);
+            // Same formula as `Camera::update_direction_from_angles`
+            let euler = Vec3f::new(
+                pitch.cos() * yaw.cos(),
+                pitch.sin(),
+                pitch.cos() * yaw.sin(),
+            );
+            assert!((quat_forward(&orientation) - euler).norm() < 1e-5);
+            assert!(quat_up(&orientation).y > 0.0);
+        }
+    }
+
+    #[test]
+    fn yaw_pitch_roll_round_trip_through_a_quaternion() {
+        for (yaw, pitch, roll) in [(-FRAC_PI_2, 0.0, 0.0), (0.4, -0.7, 0.2), (2.5, 1.2, -1.0)] {
+            let (y, p, r) = quat_to_yaw_pitch_roll(&quat_from_yaw_pitch_roll(yaw, pitch, roll));
+            assert!((y - yaw).abs() < 1e-4, "{y} != {yaw}");
+            assert!((p - pitch).abs() < 1e-4, "{p} != {pitch}");
+            assert!((r - roll).abs() < 1e-4, "{r} != {roll}");
+        }
+    }
+}
@@ -1,7 +1,12 @@
+use std::marker::PhantomData;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use winit::event_loop::{ActiveEventLoop, EventLoop};
-use winit::window::{Window, WindowAttributes, WindowId};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy};
+use winit::monitor::{MonitorHandle, VideoModeHandle};
+use winit::window::{BadIcon, Fullscreen, Icon, Window, WindowAttributes, WindowId};
 use winit::{application::ApplicationHandler, event_loop::ControlFlow};
 use winit::{
     event::{DeviceEvent, DeviceId, WindowEvent},
@@ -11,21 +16,150 @@ use winit::{
 use crate::GameState;
 #[cfg(debug_assertions)]
 use editor::Editor;
-use graphics::{Graphics, renderer::Renderer};
+use graphics::{Graphics, renderer::Renderer, screenshot::Screenshot};
+use input_map::InputMap;
 use inputs::Inputs;
+use maths::{Aabb, Rect, Vec2f};
 
+pub mod assets;
 pub mod controller;
+#[cfg(debug_assertions)]
 pub mod editor;
 pub mod graphics;
+pub mod input_map;
 pub mod inputs;
+pub mod log;
 pub mod maths;
+pub mod scene;
+
+/// Most fixed updates run in a single frame, time beyond that is dropped after a stall
+/// so catching up does not make the next frame even longer
+const MAX_FIXED_STEPS: u32 = 8;
 
-#[derive(Default, Debug)]
-pub struct App {
+/// Runs the game, `T` is the type of the custom events sent to the event loop
+#[derive(Debug)]
+pub struct App<T: 'static = ()> {
     ctx: AppContext,
     viewport: Option<Viewport>,
     inputs: Inputs,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gilrs::Gilrs>,
     state: GameState,
+    /// Used to create the window when the app resumes
+    window_attributes: WindowAttributes,
+    /// Set on the window over the one of `window_attributes`, see `with_icon`
+    window_icon: Option<Icon>,
+    /// Duration of a fixed update, `None` updates once per frame with the frame duration
+    fixed_timestep: Option<Duration>,
+    /// Time not yet simulated by fixed updates
+    accumulator: Duration,
+    /// Progress between the last fixed update and the next one, passed to `GameState::render`
+    alpha: f32,
+    _user_event: PhantomData<fn(T)>,
+}
+
+impl<T> Default for App<T> {
+    fn default() -> Self {
+        Self {
+            ctx: AppContext::default(),
+            viewport: None,
+            inputs: Inputs::default(),
+            #[cfg(feature = "gamepad")]
+            gamepads: gilrs::Gilrs::new()
+                .map_err(|e| crate::log!("Gamepads are unavailable: {e}"))
+                .ok(),
+            state: GameState::default(),
+            window_attributes: WindowAttributes::default().with_title("Ocean game"),
+            window_icon: None,
+            fixed_timestep: None,
+            accumulator: Duration::ZERO,
+            alpha: 1.0,
+            _user_event: PhantomData,
+        }
+    }
+}
+
+impl<T> App<T> {
+    /// Run `GameState::update` at `hz` updates per second whatever the framerate, with an
+    /// interpolation factor passed to `GameState::render`. Makes the simulation deterministic.
+    pub fn with_fixed_timestep(mut self, hz: f32) -> Self {
+        self.fixed_timestep = Some(Duration::from_secs_f32(1.0 / hz.max(1.0)));
+        self
+    }
+
+    /// Title, initial size, size limits, decorations and other settings of the window,
+    /// e.g. `WindowAttributes::default().with_title("My game").with_inner_size(LogicalSize::new(1280, 720))`
+    pub fn with_window_attributes(mut self, attributes: WindowAttributes) -> Self {
+        self.window_attributes = attributes;
+        self
+    }
+
+    pub fn window_attributes(&self) -> &WindowAttributes {
+        &self.window_attributes
+    }
+
+    /// Attributes the window is created with, the first `Graphics::resize` uses its inner size
+    fn creation_attributes(&self) -> WindowAttributes {
+        match &self.window_icon {
+            Some(icon) => self
+                .window_attributes
+                .clone()
+                .with_window_icon(Some(icon.clone())),
+            None => self.window_attributes.clone(),
+        }
+    }
+
+    /// Window and taskbar icon from the content of an image file, e.g. with `include_bytes!`.
+    /// The OS default icon is kept when the image can't be decoded.
+    pub fn with_icon(mut self, bytes: &[u8]) -> Self {
+        match icon_from_bytes(bytes) {
+            Ok(icon) => self.window_icon = Some(icon),
+            Err(e) => crate::log!("Failed to load window icon: {e}"),
+        }
+        self
+    }
+
+    fn update(&mut self, dt: Duration) {
+        self.state.update(&mut self.ctx, &self.inputs, dt);
+        if let Some(input_map) = self.ctx.input_map.take() {
+            self.inputs.set_input_map(input_map);
+        }
+        // Per update rather than per frame, so presses are seen by exactly one update
+        self.inputs.step();
+    }
+
+    /// Updates covering `dt`: as many fixed steps as fit, or a single update without a fixed timestep
+    fn run_updates(&mut self, dt: Duration) {
+        match self.fixed_timestep {
+            Some(step) => {
+                // Inputs of frames without an update are kept for the next one
+                for _ in 0..fixed_steps(&mut self.accumulator, step, dt) {
+                    self.update(step);
+                }
+                self.alpha = self.accumulator.as_secs_f32() / step.as_secs_f32();
+            }
+            None => self.update(dt),
+        }
+    }
+}
+
+/// Number of fixed updates of `step` covering `dt`, leaving the remainder in `accumulator`.
+/// At most `MAX_FIXED_STEPS` run, time beyond that is dropped after a stall.
+fn fixed_steps(accumulator: &mut Duration, step: Duration, dt: Duration) -> u32 {
+    *accumulator = (*accumulator + dt).min(step * MAX_FIXED_STEPS);
+    let mut steps = 0;
+    while *accumulator >= step {
+        *accumulator -= step;
+        steps += 1;
+    }
+    steps
+}
+
+/// Receives the custom events sent to the event loop through an `EventLoopProxy`
+pub trait UserEventHandler<T: 'static> {
+    /// Called once before the loop starts, the proxy can be cloned and moved to other threads
+    fn on_event_proxy(&mut self, _proxy: EventLoopProxy<T>) {}
+    fn on_user_event(&mut self, _event: T) {}
 }
 
 #[derive(Debug)]
@@ -33,6 +167,64 @@ pub struct AppContext {
     update: bool,
 
     cursor_enabled: bool,
+    cursor_confine_region: Option<Rect>,
+    /// Whether the window has the keyboard focus, the cursor is released while it doesn't
+    focused: bool,
+    pause_when_unfocused: bool,
+
+    /// Screenshot to take at the next frame, true when the editor should be left out
+    screenshot: Option<bool>,
+
+    monitors: Vec<MonitorHandle>,
+    current_monitor: Option<MonitorHandle>,
+    refresh_monitors: bool,
+    fullscreen_request: Option<FullscreenRequest>,
+    fullscreen_mode: FullscreenMode,
+    video_mode: Option<VideoModeHandle>,
+
+    /// Bindings to hand to `Inputs` after the update
+    input_map: Option<InputMap>,
+    present_mode: Option<wgpu::PresentMode>,
+    /// Clear `Graphics::frame_stats` after the update
+    reset_frame_stats: bool,
+    /// World bounds of the models at the last render
+    model_bounds: Vec<Aabb>,
+}
+
+/// How the window covers the screen
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    /// Borderless window covering its monitor, keeping the desktop video mode
+    Borderless,
+    /// Exclusive fullscreen with the monitor's largest video mode at its highest refresh rate
+    Exclusive,
+}
+
+/// Deferred fullscreen change, applied in `AppContext::update`
+#[derive(Debug, Clone)]
+enum FullscreenRequest {
+    Windowed,
+    Borderless,
+    /// Best video mode of the current monitor
+    BestExclusive,
+    Exclusive {
+        monitor: MonitorHandle,
+        size: PhysicalSize<u32>,
+        refresh_rate_millihertz: u32,
+    },
+}
+
+/// Mode and video mode reported once `fullscreen` is set on the window
+fn applied_fullscreen(
+    fullscreen: Option<&Fullscreen>,
+) -> (FullscreenMode, Option<VideoModeHandle>) {
+    match fullscreen {
+        None => (FullscreenMode::Windowed, None),
+        Some(Fullscreen::Borderless(_)) => (FullscreenMode::Borderless, None),
+        Some(Fullscreen::Exclusive(mode)) => (FullscreenMode::Exclusive, Some(mode.clone())),
+    }
 }
 
 impl Default for AppContext {
@@ -40,29 +232,240 @@ impl Default for AppContext {
         Self {
             update: false,
             cursor_enabled: true,
+            cursor_confine_region: None,
+            focused: true,
+            pause_when_unfocused: true,
+            screenshot: None,
+            monitors: vec![],
+            current_monitor: None,
+            refresh_monitors: true,
+            fullscreen_request: None,
+            fullscreen_mode: FullscreenMode::Windowed,
+            video_mode: None,
+            input_map: None,
+            present_mode: None,
+            reset_frame_stats: false,
+            model_bounds: vec![],
         }
     }
 }
 impl AppContext {
     pub fn set_cursor_enabled(&mut self, cursor_enabled: bool) {
-        self.update = cursor_enabled ^ self.cursor_enabled;
+        self.update |= cursor_enabled ^ self.cursor_enabled;
         self.cursor_enabled = cursor_enabled;
     }
     pub fn is_cursor_enabled(&self) -> bool {
         self.cursor_enabled
     }
 
-    fn update(&mut self, window: &Window) {
+    /// World bounds of the models as of the last render, `Renderer` is only reachable from
+    /// `GameState::render` so updates pick models through these
+    pub fn model_bounds(&self) -> &[Aabb] {
+        &self.model_bounds
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Skip `GameState::update` while the window is unfocused, true by default.
+    /// Disable it for games that keep simulating in the background.
+    pub fn set_pause_when_unfocused(&mut self, pause: bool) {
+        self.pause_when_unfocused = pause;
+    }
+    pub fn pause_when_unfocused(&self) -> bool {
+        self.pause_when_unfocused
+    }
+
+    /// Whether updates are skipped this frame, see `set_pause_when_unfocused`
+    pub fn is_paused(&self) -> bool {
+        self.pause_when_unfocused && !self.focused
+    }
+
+    /// Whether the window holds and hides the cursor: the cursor is disabled and the window focused
+    pub fn is_cursor_grabbed(&self) -> bool {
+        !self.cursor_enabled && self.focused
+    }
+
+    /// The cursor grab is released while unfocused and restored on refocus if still disabled
+    fn set_focused(&mut self, focused: bool) {
+        self.update |= focused ^ self.focused;
+        self.focused = focused;
+    }
+
+    /// Keep the visible cursor inside a region of the window, in physical pixels.
+    ///
+    /// The cursor is moved back into the region every frame, allowing areas outside of it
+    /// (e.g. docked editor panels) to be reached as soon as the confinement is lifted.
+    pub fn set_cursor_confine_region(&mut self, region: Option<Rect>) {
+        self.cursor_confine_region = region;
+    }
+    pub fn cursor_confine_region(&self) -> Option<Rect> {
+        self.cursor_confine_region
+    }
+
+    /// Replace the action bindings read by `Inputs::action_held` and co, from the next step
+    pub fn set_input_map(&mut self, input_map: InputMap) {
+        self.input_map = Some(input_map);
+    }
+
+    /// Switch the surface present mode, e.g. `Immediate` to uncap the framerate.
+    /// Falls back to Fifo (vsync) when unsupported, see `Graphics::set_present_mode`.
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        self.present_mode = Some(mode);
+    }
+
+    /// Forget the recorded frame times, e.g. after a loading hitch, see `Graphics::frame_stats`
+    pub fn reset_frame_stats(&mut self) {
+        self.reset_frame_stats = true;
+    }
+
+    /// Apply the changes requested with `set_present_mode` and `reset_frame_stats`
+    fn apply_graphics_requests(&mut self, graphics: &mut Graphics) {
+        if let Some(mode) = self.present_mode.take() {
+            graphics.set_present_mode(mode);
+        }
+        if std::mem::take(&mut self.reset_frame_stats) {
+            graphics.frame_stats.clear();
+        }
+    }
+
+    /// Save the next frame to a PNG in the working directory. A clean screenshot hides the
+    /// editor for that frame, capturing the scene only.
+    pub fn take_screenshot(&mut self, clean: bool) {
+        self.screenshot = Some(clean);
+    }
+
+    /// Monitors connected when the window was created or last moved
+    pub fn monitors(&self) -> &[MonitorHandle] {
+        &self.monitors
+    }
+
+    /// Monitor the window is on
+    pub fn current_monitor(&self) -> Option<&MonitorHandle> {
+        self.current_monitor.as_ref()
+    }
+
+    /// Video mode of the exclusive fullscreen, None when windowed or borderless
+    pub fn video_mode(&self) -> Option<&VideoModeHandle> {
+        self.video_mode.as_ref()
+    }
+
+    /// Go exclusive fullscreen on `monitor` with the video mode matching `size` and refresh rate,
+    /// falls back to borderless fullscreen on that monitor if no such mode exists
+    pub fn set_exclusive_fullscreen(
+        &mut self,
+        monitor: &MonitorHandle,
+        size: PhysicalSize<u32>,
+        refresh_rate_millihertz: u32,
+    ) {
+        self.fullscreen_request = Some(FullscreenRequest::Exclusive {
+            monitor: monitor.clone(),
+            size,
+            refresh_rate_millihertz,
+        });
+    }
+
+    pub fn exit_fullscreen(&mut self) {
+        self.fullscreen_request = Some(FullscreenRequest::Windowed);
+    }
+
+    /// Switch the window between windowed, borderless and exclusive fullscreen on the monitor
+    /// it is on, applied after the update
+    pub fn set_fullscreen(&mut self, mode: FullscreenMode) {
+        self.fullscreen_request = Some(match mode {
+            FullscreenMode::Windowed => FullscreenRequest::Windowed,
+            FullscreenMode::Borderless => FullscreenRequest::Borderless,
+            FullscreenMode::Exclusive => FullscreenRequest::BestExclusive,
+        });
+    }
+
+    /// Mode of the last applied fullscreen change
+    pub fn fullscreen_mode(&self) -> FullscreenMode {
+        self.fullscreen_mode
+    }
+
+    fn update(&mut self, window: &Window, inputs: &Inputs) {
+        if std::mem::take(&mut self.refresh_monitors) {
+            self.monitors = window.available_monitors().collect();
+            self.current_monitor = window.current_monitor();
+        }
+
+        if let Some(request) = self.fullscreen_request.take() {
+            let fullscreen = match request {
+                FullscreenRequest::Windowed => None,
+                FullscreenRequest::Borderless => Some(Fullscreen::Borderless(None)),
+                FullscreenRequest::BestExclusive => {
+                    let monitor = window.current_monitor();
+                    let mode = monitor.as_ref().and_then(|monitor| {
+                        monitor.video_modes().max_by_key(|m| {
+                            let size = m.size();
+                            (
+                                size.width * size.height,
+                                m.refresh_rate_millihertz(),
+                                m.bit_depth(),
+                            )
+                        })
+                    });
+                    Some(match mode {
+                        Some(mode) => Fullscreen::Exclusive(mode),
+                        None => {
+                            crate::log!("No video mode available, using borderless fullscreen");
+                            Fullscreen::Borderless(monitor)
+                        }
+                    })
+                }
+                FullscreenRequest::Exclusive {
+                    monitor,
+                    size,
+                    refresh_rate_millihertz,
+                } => Some(
+                    match monitor.video_modes().find(|m| {
+                        m.size() == size && m.refresh_rate_millihertz() == refresh_rate_millihertz
+                    }) {
+                        Some(mode) => Fullscreen::Exclusive(mode),
+                        None => {
+                            crate::log!(
+                                "Video mode {}x{}@{}Hz unavailable, using borderless fullscreen",
+                                size.width,
+                                size.height,
+                                refresh_rate_millihertz as f32 / 1000.0
+                            );
+                            Fullscreen::Borderless(Some(monitor))
+                        }
+                    },
+                ),
+            };
+            (self.fullscreen_mode, self.video_mode) = applied_fullscreen(fullscreen.as_ref());
+            window.set_fullscreen(fullscreen);
+            self.refresh_monitors = true;
+        }
+
+        if let (true, Some(region), Some((x, y))) = (
+            self.cursor_enabled,
+            self.cursor_confine_region,
+            inputs.cursor(),
+        ) {
+            let cursor = Vec2f::new(x, y);
+            if !region.contains(&cursor) {
+                let clamped = region.clamp_point(&cursor);
+                window
+                    .set_cursor_position(PhysicalPosition::new(clamped.x, clamped.y))
+                    .unwrap_or_else(|_| crate::log!("Failed to set cursor position"));
+            }
+        }
+
         if self.update {
+            let grabbed = self.is_cursor_grabbed();
             window
-                .set_cursor_grab(if self.cursor_enabled {
-                    CursorGrabMode::None
-                } else {
+                .set_cursor_grab(if grabbed {
                     CursorGrabMode::Confined
+                } else {
+                    CursorGrabMode::None
                 })
-                .unwrap_or_else(|_| println!("Failed to set cursor grab"));
+                .unwrap_or_else(|_| crate::log!("Failed to set cursor grab"));
 
-            window.set_cursor_visible(self.cursor_enabled);
+            window.set_cursor_visible(!grabbed);
             self.update = false;
         }
     }
@@ -78,28 +481,52 @@ pub struct Viewport {
     editor: Editor,
 }
 
-impl App {
+impl<T: 'static> App<T>
+where
+    GameState: UserEventHandler<T>,
+{
     pub fn run(&mut self) {
-        let event_loop = EventLoop::new().expect("Failed to create event loop");
+        let event_loop = EventLoop::with_user_event()
+            .build()
+            .expect("Failed to create event loop");
+        self.run_with(event_loop);
+    }
+
+    /// Run on an event loop configured by the caller, e.g. with platform specific options
+    pub fn run_with(&mut self, event_loop: EventLoop<T>) {
+        #[cfg(feature = "tracy")]
+        profiling::tracy_client::Client::start();
+        #[cfg(feature = "puffin")]
+        profiling::puffin::set_scopes_on(true);
+
+        // Sending an event wakes the loop, and polling never waits on the next one anyway
         event_loop.set_control_flow(ControlFlow::Poll);
+        self.state.on_event_proxy(event_loop.create_proxy());
         event_loop
             .run_app(self)
             .unwrap_or_else(|e| panic!("Failed to run app: {e}"));
     }
 }
 
-impl ApplicationHandler for App {
+impl<T: 'static> ApplicationHandler<T> for App<T>
+where
+    GameState: UserEventHandler<T>,
+{
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let window = Arc::new(
             event_loop
-                .create_window(WindowAttributes::default().with_title("Ocean game"))
+                .create_window(self.creation_attributes())
                 .expect("Failed to create window"),
         );
         let graphics = Graphics::new(window.clone());
         let renderer = Renderer::new(&graphics);
 
         #[cfg(debug_assertions)]
-        let editor = Editor::new(window.clone());
+        let editor = {
+            let mut editor = Editor::new(window.clone(), &graphics);
+            self.state.register_editor_panels(&mut editor);
+            editor
+        };
 
         self.viewport.replace(Viewport {
             #[cfg(debug_assertions)]
@@ -116,45 +543,79 @@ impl ApplicationHandler for App {
 
         if let Some(viewport) = &mut self.viewport {
             #[cfg(debug_assertions)]
-            if viewport
-                .editor
-                .on_window_event_consume(&viewport.window, &event)
+            if viewport.editor.on_toggle_key(&event)
+                || viewport
+                    .editor
+                    .on_window_event_consume(&viewport.window, &event)
             {
-                println!("a");
                 return;
             }
 
             match event {
                 WindowEvent::RedrawRequested => {
                     if let Some(mut frame) = viewport.graphics.next_frame() {
-                        self.state
-                            .render(&viewport.graphics, &mut frame, &mut viewport.renderer);
+                        {
+                            profiling::scope!("render");
+                            viewport
+                                .renderer
+                                .begin_frame(&mut viewport.graphics, &mut frame);
+                            self.state.render(
+                                &viewport.graphics,
+                                &mut frame,
+                                &mut viewport.renderer,
+                                self.alpha,
+                            );
+                            viewport.renderer.end_frame(&viewport.graphics, &mut frame);
+                            self.ctx.model_bounds.clear();
+                            self.ctx
+                                .model_bounds
+                                .extend(viewport.renderer.model.world_bounds());
+                        }
 
+                        let screenshot = self.ctx.screenshot.take();
                         #[cfg(debug_assertions)]
-                        viewport.editor.render(
-                            &mut self.state,
-                            &viewport.window,
-                            &mut viewport.renderer.editor,
-                            &viewport.graphics,
-                            &mut frame,
-                        );
-
-                        viewport.graphics.present(frame);
+                        if screenshot != Some(true) {
+                            profiling::scope!("editor");
+                            viewport.editor.render(
+                                &mut self.state,
+                                &mut self.ctx,
+                                &viewport.window,
+                                &mut viewport.renderer,
+                                &viewport.graphics,
+                                &mut frame,
+                            );
+                        }
+                        let capture = screenshot
+                            .and_then(|_| Screenshot::capture(&viewport.graphics, &mut frame));
+
+                        {
+                            profiling::scope!("present");
+                            viewport.graphics.present(frame);
+                        }
+                        profiling::finish_frame!();
+
+                        if let Some(capture) = capture {
+                            save_screenshot(&viewport.graphics, capture);
+                        }
                     }
 
                     viewport.window.request_redraw();
                 }
-                WindowEvent::Resized(_)
-                | WindowEvent::ScaleFactorChanged {
-                    scale_factor: _,
-                    inner_size_writer: _,
-                } => {
+                WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                    // The editor picks the new scale factor up from the window on its next frame
+                    if let WindowEvent::ScaleFactorChanged { scale_factor, .. } = event {
+                        viewport.graphics.scale_factor = scale_factor;
+                        self.ctx.refresh_monitors = true;
+                    }
                     viewport
                         .graphics
                         .resize(viewport.window.inner_size().into());
                     viewport.renderer.on_resize(&viewport.graphics);
+                    self.state.on_resize(&viewport.graphics);
                 }
-                WindowEvent::CloseRequested => {
+                WindowEvent::Moved(_) => self.ctx.refresh_monitors = true,
+                WindowEvent::Focused(focused) => self.ctx.set_focused(focused),
+                WindowEvent::CloseRequested if self.state.on_close_requested() => {
                     event_loop.exit();
                 }
                 _ => (),
@@ -162,25 +623,253 @@ impl ApplicationHandler for App {
         }
     }
 
+    fn user_event(&mut self, _: &ActiveEventLoop, event: T) {
+        self.state.on_user_event(event);
+    }
+
     fn device_event(&mut self, _: &ActiveEventLoop, _: DeviceId, event: DeviceEvent) {
         self.inputs.process_device_event(&event);
 
         #[cfg(debug_assertions)]
-        if let DeviceEvent::MouseMotion { delta } = event {
-            if let Some(viewport) = &mut self.viewport {
-                viewport.editor.on_mouse_motion(delta);
-            }
+        if let DeviceEvent::MouseMotion { delta } = event
+            && let Some(viewport) = &mut self.viewport
+        {
+            viewport.editor.on_mouse_motion(delta);
         }
     }
 
     fn about_to_wait(&mut self, _: &ActiveEventLoop) {
+        profiling::scope!("update");
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepads) = &mut self.gamepads {
+            while let Some(event) = gamepads.next_event() {
+                self.inputs.process_gamepad_event(&event.event);
+            }
+        }
         self.inputs.end_step();
+        let dt = self.inputs.smoothed_delta_time().unwrap_or_default();
+
+        if self.ctx.is_paused() {
+            // Time spent unfocused is not caught up by fixed updates on refocus
+            self.accumulator = Duration::ZERO;
+            self.inputs.step();
+        } else {
+            self.run_updates(dt);
+        }
 
-        self.state.update(&mut self.ctx, &self.inputs);
         if let Some(viewport) = &mut self.viewport {
-            self.ctx.update(&viewport.window);
+            self.ctx.update(&viewport.window, &self.inputs);
+            self.ctx.apply_graphics_requests(&mut viewport.graphics);
         }
+    }
+}
 
-        self.inputs.step();
+#[derive(Debug)]
+pub enum IconError {
+    Image(image::ImageError),
+    Invalid(BadIcon),
+}
+
+impl std::fmt::Display for IconError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Image(e) => write!(f, "failed to decode icon image: {e}"),
+            Self::Invalid(e) => write!(f, "invalid icon: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for IconError {}
+
+/// Decode an image file into a window icon, see `App::with_icon`
+pub fn icon_from_bytes(bytes: &[u8]) -> Result<Icon, IconError> {
+    let image = image::load_from_memory(bytes)
+        .map_err(IconError::Image)?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).map_err(IconError::Invalid)
+}
+
+fn save_screenshot(graphics: &Graphics, capture: Screenshot) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = PathBuf::from(format!("screenshot_{timestamp}.png"));
+    match capture.save(graphics, &path) {
+        Ok(()) => crate::log!("Saved screenshot to {}", path.display()),
+        Err(e) => crate::log!("Failed to save screenshot: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_updates_cover_the_elapsed_time() {
+        let step = Duration::from_millis(10);
+        let mut accumulator = Duration::ZERO;
+        let updates: u32 = (0..10)
+            .map(|_| fixed_steps(&mut accumulator, step, Duration::from_millis(25)))
+            .sum();
+        assert_eq!(updates, 25);
+        assert_eq!(accumulator, Duration::ZERO);
+
+        // A frame shorter than a step runs no update and keeps the time for the next frame
+        assert_eq!(
+            fixed_steps(&mut accumulator, step, Duration::from_millis(4)),
+            0
+        );
+        assert_eq!(
+            fixed_steps(&mut accumulator, step, Duration::from_millis(6)),
+            1
+        );
+    }
+
+    #[test]
+    fn fixed_updates_are_capped_after_a_stall() {
+        let step = Duration::from_millis(10);
+        let mut accumulator = Duration::ZERO;
+        assert_eq!(
+            fixed_steps(&mut accumulator, step, Duration::from_secs(1)),
+            MAX_FIXED_STEPS
+        );
+        assert_eq!(accumulator, Duration::ZERO);
+    }
+
+    #[test]
+    fn cursor_grab_follows_the_cursor_and_focus_state() {
+        let mut ctx = AppContext::default();
+        assert!(ctx.is_cursor_enabled());
+        assert!(!ctx.is_cursor_grabbed());
+
+        ctx.set_cursor_enabled(false);
+        assert!(ctx.is_cursor_grabbed());
+        assert!(ctx.update, "the window must be synced");
+        ctx.update = false;
+
+        // Setting the same state again leaves the window alone
+        ctx.set_cursor_enabled(false);
+        assert!(!ctx.update);
+
+        ctx.set_focused(false);
+        assert!(!ctx.is_cursor_grabbed());
+        assert!(ctx.update);
+        ctx.update = false;
+
+        ctx.set_focused(true);
+        assert!(ctx.is_cursor_grabbed());
+
+        ctx.set_cursor_enabled(true);
+        assert!(!ctx.is_cursor_grabbed());
+    }
+
+    #[test]
+    fn cursor_enabled_while_unfocused_stays_released_on_refocus() {
+        let mut ctx = AppContext::default();
+        ctx.set_cursor_enabled(false);
+        ctx.set_focused(false);
+        ctx.set_cursor_enabled(true);
+        ctx.set_focused(true);
+        assert!(!ctx.is_cursor_grabbed());
+    }
+
+    #[test]
+    fn updates_pause_only_while_unfocused() {
+        let mut ctx = AppContext::default();
+        assert!(!ctx.is_paused());
+        ctx.set_focused(false);
+        assert!(ctx.is_paused());
+        ctx.set_focused(true);
+        assert!(!ctx.is_paused());
+    }
+
+    #[test]
+    fn background_updates_ignore_the_focus() {
+        let mut ctx = AppContext::default();
+        ctx.set_pause_when_unfocused(false);
+        ctx.set_focused(false);
+        assert!(!ctx.is_paused());
+        ctx.set_pause_when_unfocused(true);
+        assert!(ctx.is_paused());
+    }
+
+    #[test]
+    fn icon_is_decoded_from_an_embedded_image() {
+        assert!(icon_from_bytes(include_bytes!("../../assets/debug.png")).is_ok());
+    }
+
+    #[test]
+    fn icon_from_invalid_bytes_is_an_image_error() {
+        let error = icon_from_bytes(b"not an image").unwrap_err();
+        assert!(matches!(error, IconError::Image(_)), "{error}");
+    }
+
+    #[test]
+    fn fullscreen_changes_are_deferred_to_the_update() {
+        let mut ctx = AppContext::default();
+        ctx.set_fullscreen(FullscreenMode::Exclusive);
+        ctx.set_fullscreen(FullscreenMode::Borderless);
+        // The last request wins, the mode changes once it is applied
+        assert!(matches!(
+            ctx.fullscreen_request,
+            Some(FullscreenRequest::Borderless)
+        ));
+        assert_eq!(ctx.fullscreen_mode(), FullscreenMode::Windowed);
+
+        let borderless = Fullscreen::Borderless(None);
+        assert_eq!(
+            applied_fullscreen(Some(&borderless)),
+            (FullscreenMode::Borderless, None)
+        );
+        assert_eq!(applied_fullscreen(None), (FullscreenMode::Windowed, None));
+
+        ctx.exit_fullscreen();
+        assert!(matches!(
+            ctx.fullscreen_request,
+            Some(FullscreenRequest::Windowed)
+        ));
+    }
+
+    #[test]
+    fn window_attributes_are_kept_for_the_window_creation() {
+        let attributes = WindowAttributes::default()
+            .with_title("My game")
+            .with_inner_size(PhysicalSize::new(320, 200))
+            .with_resizable(false);
+        let app = App::<()>::default()
+            .with_window_attributes(attributes)
+            .with_icon(include_bytes!("../../assets/debug.png"));
+
+        let created = app.creation_attributes();
+        assert_eq!(created.title, "My game");
+        assert!(!created.resizable);
+        assert!(created.window_icon.is_some());
+        let size: PhysicalSize<u32> = created.inner_size.unwrap().to_physical(1.0);
+        assert_eq!(size, PhysicalSize::new(320, 200));
+    }
+
+    #[test]
+    fn frame_stats_reset_is_applied_after_the_update() {
+        let Some(mut graphics) = Graphics::headless(8, 8) else {
+            return;
+        };
+        graphics.frame_stats.push(Duration::from_millis(16));
+        let mut ctx = AppContext::default();
+
+        ctx.apply_graphics_requests(&mut graphics);
+        assert_eq!(graphics.frame_stats.len(), 1);
+
+        ctx.reset_frame_stats();
+        assert_eq!(graphics.frame_stats.len(), 1);
+        ctx.apply_graphics_requests(&mut graphics);
+        assert_eq!(graphics.frame_stats.len(), 0);
+        assert_eq!(graphics.frame_stats.average(), Duration::ZERO);
+
+        // Applied once
+        graphics.frame_stats.push(Duration::from_millis(16));
+        ctx.apply_graphics_requests(&mut graphics);
+        assert_eq!(graphics.frame_stats.len(), 1);
     }
 }
@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{
+    graphics::{
+        Graphics,
+        camera::Camera,
+        light::Light,
+        model::{
+            Model,
+            obj::ObjError,
+            renderer::{MaterialId, ModelId},
+        },
+        renderer::Renderer,
+    },
+    maths::Transform,
+};
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: Box<ron::error::SpannedError>,
+    },
+    Serialize(ron::Error),
+    Mesh {
+        path: PathBuf,
+        error: ObjError,
+    },
+    /// No material was given for this name to `Scene::spawn`
+    UnknownMaterial(String),
+}
+
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "failed to access {}: {error}", path.display()),
+            Self::Parse { path, error } => {
+                write!(f, "malformed scene {}: {error}", path.display())
+            }
+            Self::Serialize(e) => write!(f, "failed to serialize scene: {e}"),
+            Self::Mesh { path, error } => {
+                write!(f, "failed to load mesh {}: {error}", path.display())
+            }
+            Self::UnknownMaterial(name) => write!(f, "unknown material {name:?}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// A model of the scene, its mesh is loaded again from `mesh` by `Scene::spawn`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneModel {
+    /// Wavefront OBJ file, relative to the working directory
+    pub mesh: PathBuf,
+    /// Name the material is given in the map passed to `Scene::spawn`
+    pub material: String,
+    #[serde(default)]
+    pub transform: Transform,
+}
+
+/// Camera, light and models of a scene, saved as RON.
+///
+/// GPU resources are not serialized: materials are created by the game and referred to by name,
+/// meshes are referred to by path and loaded again when spawned.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    pub camera: Camera,
+    pub light: Light,
+    pub models: Vec<SceneModel>,
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|error| SceneError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        ron::from_str(&source).map_err(|error| SceneError::Parse {
+            path: path.to_owned(),
+            error: Box::new(error),
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(SceneError::Serialize)?;
+        std::fs::write(path, source).map_err(|error| SceneError::Io {
+            path: path.to_owned(),
+            error,
+        })
+    }
+
+    /// Load the meshes and add the models to the renderer with the materials named in
+    /// `materials`, and apply the light. Nothing is added when a model fails to load.
+    pub fn spawn(
+        &self,
+        ctx: &Graphics,
+        renderer: &mut Renderer,
+        materials: &HashMap<String, MaterialId>,
+    ) -> Result<Vec<ModelId>, SceneError> {
+        let mut loaded = Vec::with_capacity(self.models.len());
+        for model in &self.models {
+            let material = *materials
+                .get(&model.material)
+                .ok_or_else(|| SceneError::UnknownMaterial(model.material.clone()))?;
+            let mut mesh = Model::from_obj(ctx, &model.mesh).map_err(|error| SceneError::Mesh {
+                path: model.mesh.clone(),
+                error,
+            })?;
+            mesh.set_transform(model.transform.to_matrix());
+            loaded.push((mesh, material));
+        }
+
+        renderer.light = self.light;
+        Ok(loaded
+            .into_iter()
+            .map(|(mesh, material)| renderer.model.add_model(mesh, material))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        graphics::color::Color3f,
+        maths::{Quatf, Vec3f},
+    };
+
+    #[test]
+    fn scene_round_trips_through_ron() {
+        let mut scene = Scene::default();
+        scene.camera.position = Vec3f::new(1.0, 2.0, 3.0);
+        scene.camera.yaw = 0.5;
+        scene.light.color = Color3f::RED;
+        scene.models.push(SceneModel {
+            mesh: "assets/cube.obj".into(),
+            material: "stone".to_owned(),
+            transform: Transform::new(
+                Vec3f::new(4.0, 0.0, -1.0),
+                Quatf::from_euler_angles(0.0, 1.0, 0.0),
+                Vec3f::repeat(2.0),
+            ),
+        });
+
+        let path = std::env::temp_dir().join(format!("scene_{}.ron", std::process::id()));
+        scene.save(&path).unwrap();
+        let loaded = Scene::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.models, scene.models);
+        assert_eq!(loaded.light, scene.light);
+        assert_eq!(loaded.camera.position, scene.camera.position);
+        assert_eq!(loaded.camera.yaw, scene.camera.yaw);
+        assert_eq!(loaded.camera.projection, scene.camera.projection);
+    }
+
+    #[test]
+    fn missing_fields_take_their_default() {
+        let scene: Scene = ron::from_str("(models: [(mesh: \"a.obj\", material: \"m\")])").unwrap();
+        assert_eq!(scene.light, Light::default());
+        assert_eq!(scene.models[0].transform, Transform::default());
+    }
+}
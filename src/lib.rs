@@ -1,10 +1,18 @@
-use winit::{event::MouseButton, keyboard::KeyCode};
+use std::time::Duration;
 
+use winit::{event::MouseButton, event_loop::EventLoopProxy, keyboard::KeyCode};
+
+#[cfg(debug_assertions)]
+use crate::engine::editor::Editor;
 use crate::engine::{
-    AppContext,
-    controller::Controller,
+    AppContext, FullscreenMode, UserEventHandler,
+    controller::{
+        CameraPath, CameraPathPlayer, Controller, ControllerMode, Easing, PanController,
+        TurntableController, Waypoint,
+    },
     graphics::{Frame, Graphics, camera::Camera, model::Model, renderer::Renderer},
     inputs::Inputs,
+    maths::Vec3f,
 };
 
 //#[allow(dead_code)]
@@ -12,60 +20,177 @@ pub mod engine;
 
 mod visuals;
 
+/// Events sent to the main loop from other threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserEvent {
+    /// A background asset load finished
+    AssetReady(u64),
+}
+
 #[derive(Default, Debug)]
 pub struct GameState {
+    events: Option<EventLoopProxy<UserEvent>>,
     inputs_enabled: bool,
     camera: Camera,
     controller: Controller,
+    flythrough: Option<CameraPathPlayer>,
+    turntable: Option<TurntableController>,
+    pan: Option<PanController>,
 }
 
 impl GameState {
-    fn update(&mut self, ctx: &mut AppContext, inputs: &Inputs) {
+    /// Advance the game by `dt`, the frame duration or the fixed timestep set with
+    /// `App::with_fixed_timestep`.
+    fn update(&mut self, ctx: &mut AppContext, inputs: &Inputs, dt: Duration) {
+        if inputs.held_alt()
+            && inputs.mouse_pressed(MouseButton::Left)
+            && let (Some((x, y)), Some((w, h))) = (inputs.cursor(), inputs.resolution())
+        {
+            self.controller.pick_focus(
+                &self.camera,
+                [x, y].into(),
+                [w, h].into(),
+                ctx.model_bounds(),
+            );
+        }
+        if inputs.key_pressed(KeyCode::F12) {
+            ctx.take_screenshot(true);
+        }
+        if inputs.key_pressed(KeyCode::F11) {
+            ctx.set_fullscreen(match ctx.fullscreen_mode() {
+                FullscreenMode::Windowed => FullscreenMode::Borderless,
+                _ => FullscreenMode::Windowed,
+            });
+        }
+        if inputs.key_pressed(KeyCode::KeyF) {
+            self.controller.mode = ControllerMode::Fly;
+        }
+
+        let was_enabled = self.inputs_enabled;
         self.inputs_enabled &= !inputs.key_pressed(KeyCode::Escape);
         self.inputs_enabled |= inputs.mouse_pressed(MouseButton::Left);
         ctx.set_cursor_enabled(!self.inputs_enabled);
-        if self.inputs_enabled {
+        if was_enabled != self.inputs_enabled {
+            // Skip the transition frame so motion from the capturing click is not applied
+            self.controller.reset();
+        } else if self.inputs_enabled {
             self.controller.handle_inputs(inputs, true);
         }
 
-        if let Some(dt) = inputs.delta_time() {
-            self.controller.update_camera(&mut self.camera, &dt);
+        if inputs.key_pressed(KeyCode::KeyP) {
+            let target = Vec3f::zeros();
+            let mut player = CameraPathPlayer::new(
+                CameraPath::new(vec![
+                    Waypoint::new(Vec3f::new(0.0, 1.0, 6.0), target),
+                    Waypoint::new(Vec3f::new(6.0, 2.0, 0.0), target),
+                    Waypoint::new(Vec3f::new(0.0, 3.0, -6.0), target),
+                    Waypoint::new(Vec3f::new(-6.0, 2.0, 0.0), target),
+                ]),
+                4.0,
+            );
+            player.easing = Easing::SmoothStep;
+            player.play();
+            self.flythrough = Some(player);
+        }
+
+        if inputs.key_pressed(KeyCode::KeyT) {
+            self.turntable = match self.turntable {
+                Some(_) => None,
+                None => Some(TurntableController::default()),
+            };
+        }
+        if self.inputs_enabled
+            && let Some(turntable) = &mut self.turntable
+        {
+            turntable.handle_inputs(inputs);
+        }
+
+        if inputs.key_pressed(KeyCode::KeyG) {
+            self.pan = match self.pan {
+                Some(_) => None,
+                None => Some(PanController::default()),
+            };
+        }
+        // Edge panning needs the free cursor, so pan inputs are read while not captured too
+        if let Some(pan) = &mut self.pan {
+            pan.handle_inputs(inputs);
+        }
+
+        match (&mut self.flythrough, &mut self.turntable, &mut self.pan) {
+            (Some(player), _, _) if player.is_playing() => player.update(&mut self.camera, &dt),
+            (_, Some(turntable), _) => turntable.update_camera(&mut self.camera, &dt),
+            (_, _, Some(pan)) => pan.update_camera(&mut self.camera, &dt),
+            _ => self.controller.update_camera(&mut self.camera, &dt),
         }
     }
 
-    fn render(&self, ctx: &Graphics, frame: &mut Frame, renderer: &mut Renderer) {
+    /// Called after the surface is resized or the window moves to a monitor with another scale
+    /// factor, see `Graphics::viewport_size` and `Graphics::scale_factor`.
+    fn on_resize(&mut self, _ctx: &Graphics) {}
+
+    /// Called when the window close button is pressed, return false to cancel exiting.
+    fn on_close_requested(&mut self) -> bool {
+        true
+    }
+
+    /// `_alpha` is the progress from the last fixed update towards the next one in [0, 1), to
+    /// interpolate the rendered state. Always 1 without a fixed timestep.
+    fn render(&self, ctx: &Graphics, frame: &mut Frame, renderer: &mut Renderer, _alpha: f32) {
         if ctx.is_init() {
             let material = renderer
                 .model
                 .add_material(Box::new(visuals::TestMaterial::new(
                     ctx,
                     &renderer.camera_uniform,
+                    &renderer.light_uniform,
                 )));
             renderer.model.add_model(Model::cube(ctx, false), material);
         }
 
         renderer.update_camera(ctx, &self.camera);
+        renderer.update_light(ctx);
+
+        // World axes, gizmos only exist in debug builds like the editor
+        #[cfg(debug_assertions)]
+        {
+            use crate::engine::graphics::color::Color3f;
+
+            let gizmos = &mut renderer.model.gizmos;
+            gizmos.ray(Vec3f::zeros(), Vec3f::x(), Color3f::RED);
+            gizmos.ray(Vec3f::zeros(), Vec3f::y(), Color3f::GREEN);
+            gizmos.ray(Vec3f::zeros(), Vec3f::z(), Color3f::BLUE);
+        }
+
         renderer.model.render(ctx, frame, &renderer.camera_uniform);
     }
 
+    /// Register custom editor panels, they are toggled from the editor menu bar.
+    #[cfg(debug_assertions)]
+    fn register_editor_panels(&mut self, _editor: &mut Editor) {}
+
     #[cfg(debug_assertions)]
     fn editor_ui(&mut self, ctx: &egui::Context) {
-        use crate::engine::editor::{bool_label, colored_f32_label, colored_vec3_label};
+        use crate::engine::editor::bool_label;
 
         egui::Window::new("Editor panel").show(ctx, |ui| {
-            use egui::Color32;
-
             ui.heading("Hello world!");
 
             ui.separator();
 
-            colored_vec3_label(ui, "Camera Position:", &self.camera.position);
-            colored_f32_label(ui, "Camera Yaw:", self.camera.yaw, Color32::YELLOW);
-            colored_f32_label(ui, "Camera Pitch:", self.camera.pitch, Color32::MAGENTA);
             bool_label(ui, "Inputs Enabled:", self.inputs_enabled);
-            ui.add(
-                egui::Slider::new(&mut self.controller.sensitivity, 0.01..=1.).text("Sensitivity"),
-            )
         });
     }
 }
+
+impl UserEventHandler<UserEvent> for GameState {
+    fn on_event_proxy(&mut self, proxy: EventLoopProxy<UserEvent>) {
+        self.events = Some(proxy);
+    }
+
+    fn on_user_event(&mut self, event: UserEvent) {
+        match event {
+            // Take the loaded asset from wherever the loading thread stored it
+            UserEvent::AssetReady(_id) => {}
+        }
+    }
+}
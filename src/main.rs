@@ -1,3 +1,4 @@
 fn main() {
-    your_game_name::engine::App::default().run()
+    let mut app: your_game_name::engine::App<your_game_name::UserEvent> = Default::default();
+    app.run()
 }
@@ -1,49 +1,91 @@
 use wgpu::{
-    BindGroupLayout, BlendState, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
-    DepthStencilState, Face, FragmentState, FrontFace, IndexFormat, MultisampleState,
-    PipelineCompilationOptions, PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology,
-    RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor,
-    ShaderSource, StencilState, TextureFormat, VertexState,
+    BindGroup, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState,
+    Face, FragmentState, FrontFace, MultisampleState, PipelineCompilationOptions, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, StencilState,
+    TextureFormat, VertexState,
 };
 
 use crate::engine::graphics::{
     Graphics,
-    camera::CameraUniform,
+    camera::{CAMERA_WGSL, CameraUniform},
+    light::{LIGHT_WGSL, LightUniform},
     model::{
         Vertex,
-        renderer::{MaterialRenderer, ModelsIter},
+        instance::{INSTANCE_WGSL, InstanceData},
+        renderer::{BlendMode, MaterialRenderer, ModelsIter},
         texture::{ModelTexture, TextureUniform},
     },
 };
 
 pub struct TestMaterial {
     pipeline: RenderPipeline,
+    shader_module: ShaderModule,
+    pipeline_layout: PipelineLayout,
     texture_uniform: TextureUniform,
+    light_bind_group: BindGroup,
+    blend: BlendMode,
 }
 
 impl TestMaterial {
-    pub fn new(ctx: &Graphics, camera_uniform: &CameraUniform) -> Self {
+    pub fn new(
+        ctx: &Graphics,
+        camera_uniform: &CameraUniform,
+        light_uniform: &LightUniform,
+    ) -> Self {
+        Self::with_blend(ctx, camera_uniform, light_uniform, BlendMode::Replace)
+    }
+
+    pub fn with_blend(
+        ctx: &Graphics,
+        camera_uniform: &CameraUniform,
+        light_uniform: &LightUniform,
+        blend: BlendMode,
+    ) -> Self {
         let texture =
             ModelTexture::from_bytes(ctx, include_bytes!("../assets/debug.png"), "cobblestone")
                 .expect("Failed to load texture");
         let texture_uniform = TextureUniform::new(ctx, &texture);
 
         let shader_module = create_shader_module(ctx);
-        let pipeline = create_render_pipeline(
-            ctx,
-            &shader_module,
-            &camera_uniform.bind_group_layout,
-            &texture_uniform.bind_group_layout,
-        );
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    &camera_uniform.bind_group_layout,
+                    &texture_uniform.bind_group_layout,
+                    &light_uniform.bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let pipeline = create_render_pipeline(ctx, &shader_module, &pipeline_layout, blend);
 
         Self {
             pipeline,
+            shader_module,
+            pipeline_layout,
             texture_uniform,
+            light_bind_group: light_uniform.bind_group.clone(),
+            blend,
         }
     }
 }
 
 impl MaterialRenderer for TestMaterial {
+    fn name(&self) -> &str {
+        "Test material"
+    }
+
+    fn on_sample_count_changed(&mut self, ctx: &Graphics) {
+        self.pipeline =
+            create_render_pipeline(ctx, &self.shader_module, &self.pipeline_layout, self.blend);
+    }
+
+    fn is_transparent(&self) -> bool {
+        !self.blend.writes_depth()
+    }
+
     fn render(
         &mut self,
         _ctx: &Graphics,
@@ -54,22 +96,16 @@ impl MaterialRenderer for TestMaterial {
         render_pass.set_pipeline(&self.pipeline);
         render_pass.set_bind_group(0, &camera_uniform.bind_group, &[]);
         render_pass.set_bind_group(1, &self.texture_uniform.bind_group, &[]);
+        render_pass.set_bind_group(2, &self.light_bind_group, &[]);
 
         // draw models
         for model in models {
-            render_pass.set_vertex_buffer(0, model.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(model.index_buffer.slice(..), IndexFormat::Uint16);
-            render_pass.draw_indexed(0..model.indices_count(), 0, 0..1);
+            model.draw(render_pass);
         }
     }
 }
 
 const TEST_SHADER: &str = r#"
-struct CameraUniform {
-    view: mat4x4<f32>,
-    proj: mat4x4<f32>,
-};
-
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
@@ -78,59 +114,65 @@ var t_diffuse: texture_2d<f32>;
 @group(1) @binding(1)
 var s_diffuse: sampler;
 
+@group(2) @binding(0)
+var<uniform> light: LightUniform;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) normal: vec3<f32>,
 };
 
 @vertex
-fn vs_main(in: VertexInput) -> VertexOutput {
+fn vs_main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = camera.proj * camera.view * vec4<f32>(in.position, 1.0);
+    let model = instance_matrix(instance);
+    let world_position = model * vec4<f32>(in.position, 1.0);
+    out.clip_position = log_depth(camera.proj * camera.view * world_position, camera.depth);
     out.tex_coords = in.tex_coords;
+    out.color = instance.color;
+    // Exact for rotations and uniform scales
+    out.normal = (model * vec4<f32>(in.normal, 0.0)).xyz;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let albedo = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.color;
+    return vec4<f32>(albedo.rgb * lambert(light, in.normal), albedo.a);
 }
 "#;
 
 fn create_shader_module(ctx: &Graphics) -> ShaderModule {
     ctx.device.create_shader_module(ShaderModuleDescriptor {
         label: Some("Shader"),
-        source: ShaderSource::Wgsl(TEST_SHADER.into()),
+        source: ShaderSource::Wgsl(
+            format!("{CAMERA_WGSL}{LIGHT_WGSL}{INSTANCE_WGSL}{TEST_SHADER}").into(),
+        ),
     })
 }
 
 fn create_render_pipeline(
     ctx: &Graphics,
     shader_module: &ShaderModule,
-    camera_bind_group_layout: &BindGroupLayout,
-    texture_bind_group_layout: &BindGroupLayout,
+    pipeline_layout: &PipelineLayout,
+    blend: BlendMode,
 ) -> RenderPipeline {
-    let render_pipeline_layout = ctx
-        .device
-        .create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[camera_bind_group_layout, texture_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
     ctx.device
         .create_render_pipeline(&RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
+            layout: Some(pipeline_layout),
             vertex: VertexState {
                 module: shader_module,
                 entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
+                buffers: &[Vertex::desc(), InstanceData::desc()],
                 compilation_options: PipelineCompilationOptions::default(),
             },
             fragment: Some(FragmentState {
@@ -138,7 +180,7 @@ fn create_render_pipeline(
                 entry_point: Some("fs_main"),
                 targets: &[Some(ColorTargetState {
                     format: ctx.surface_format,
-                    blend: Some(BlendState::REPLACE),
+                    blend: Some(blend.blend_state()),
                     write_mask: ColorWrites::ALL,
                 })],
                 compilation_options: PipelineCompilationOptions::default(),
@@ -154,13 +196,13 @@ fn create_render_pipeline(
             },
             depth_stencil: Some(DepthStencilState {
                 format: TextureFormat::Depth32Float,
-                depth_write_enabled: true,
+                depth_write_enabled: blend.writes_depth(),
                 depth_compare: CompareFunction::Less,
                 stencil: StencilState::default(),
                 bias: DepthBiasState::default(),
             }),
             multisample: MultisampleState {
-                count: 1,
+                count: ctx.sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },